@@ -0,0 +1,82 @@
+// Encoder for bit-packed input packets (server/src/input_codec.rs).
+// Field widths must match the server.
+
+const MAX_INPUTS_PER_PACKET = 15;
+const COUNT_BITS = 4;
+const SEQUENCE_BITS = 32;
+const SEQUENCE_DELTA_BITS = 8;
+const TIMESTAMP_LOW_BITS = 32;
+const TIMESTAMP_HIGH_BITS = 16;
+const TIMESTAMP_DELTA_BITS = 16;
+const AXIS_BITS = 8;
+const AXIS_SCALE = 126; // even, so half values are exact
+
+export interface PackedInput {
+  sequence: number;
+  timestamp: number;
+  thrust: number;
+  turn: number;
+  primary_fire: boolean;
+  secondary_fire: boolean;
+}
+
+class BitWriter {
+  private bytes: number[] = [];
+  private bitLen = 0;
+
+  write(value: number, bits: number) {
+    for (let i = 0; i < bits; i++) {
+      if (this.bitLen % 8 === 0) {
+        this.bytes.push(0);
+      }
+      // Division instead of shifts keeps 32-bit values unsigned
+      if (Math.floor(value / 2 ** i) % 2 === 1) {
+        this.bytes[this.bytes.length - 1] |= 1 << (this.bitLen % 8);
+      }
+      this.bitLen++;
+    }
+  }
+
+  finish(): number[] {
+    return this.bytes;
+  }
+}
+
+function encodeAxis(value: number): number {
+  return Math.round(Math.max(-1, Math.min(1, value)) * AXIS_SCALE) + AXIS_SCALE;
+}
+
+// Pack inputs, newest first; inputs too far behind the newest are left out
+export function encodeInputs(inputs: PackedInput[]): number[] {
+  const writer = new BitWriter();
+  const newest = inputs[0];
+  if (!newest) {
+    writer.write(0, COUNT_BITS);
+    return writer.finish();
+  }
+
+  const bundled = [];
+  for (const input of inputs.slice(0, MAX_INPUTS_PER_PACKET)) {
+    if (newest.sequence - input.sequence >= 2 ** SEQUENCE_DELTA_BITS) break;
+    bundled.push(input);
+  }
+
+  writer.write(bundled.length, COUNT_BITS);
+  writer.write(newest.sequence, SEQUENCE_BITS);
+  writer.write(newest.timestamp % 2 ** 32, TIMESTAMP_LOW_BITS);
+  writer.write(Math.floor(newest.timestamp / 2 ** 32), TIMESTAMP_HIGH_BITS);
+
+  bundled.forEach((input, i) => {
+    if (i > 0) {
+      writer.write(newest.sequence - input.sequence, SEQUENCE_DELTA_BITS);
+      const age = Math.max(0, newest.timestamp - input.timestamp);
+      writer.write(Math.min(age, 2 ** TIMESTAMP_DELTA_BITS - 1), TIMESTAMP_DELTA_BITS);
+    }
+    writer.write(encodeAxis(input.thrust), AXIS_BITS);
+    writer.write(encodeAxis(input.turn), AXIS_BITS);
+    writer.write(input.primary_fire ? 1 : 0, 1);
+    writer.write(input.secondary_fire ? 1 : 0, 1);
+  });
+
+  return writer.finish();
+}
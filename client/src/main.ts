@@ -1,68 +1,32 @@
 import './style.css';
 import Phaser from 'phaser';
 import RAPIER from '@dimforge/rapier2d-compat';
+import { unpackSnapshot } from './snapshotCodec';
+import { encodeInputs } from './inputCodec';
+import { decode, encode } from './msgpack';
+import type {
+  ClientMessage,
+  DebugCollider,
+  DebugForce,
+  DebugJoint,
+  DebugRenderData,
+  DebugRigidBody,
+  DebugVelocity,
+  EntityInfo,
+  Encoding,
+  Frame,
+  GameEvent,
+  ServerMessage
+} from './protocol';
 
 // Game configuration
 const GAME_WIDTH = 1920;
 const GAME_HEIGHT = 1080;
 const INPUT_SAMPLE_RATE = 15; // TPS to match server (now 15 Hz)
 const INTERPOLATION_DELAY = 120; // ms
-
-// Debug rendering data structures (matching server)
-interface DebugRenderData {
-  sequence: number;
-  rigid_bodies: DebugRigidBody[];
-  colliders: DebugCollider[];
-  forces: DebugForce[];
-  velocities: DebugVelocity[];
-  joints: DebugJoint[];
-}
-
-interface DebugRigidBody {
-  handle: number;
-  position: [number, number];
-  rotation: number;
-  body_type: 'Dynamic' | 'Kinematic' | 'Static';
-  mass: number;
-  linear_damping: number;
-  angular_damping: number;
-}
-
-interface DebugCollider {
-  handle: number;
-  parent_body: number;
-  shape: DebugShape;
-  position: [number, number];
-  rotation: number;
-}
-
-interface DebugForce {
-  body_handle: number;
-  force: [number, number];
-  torque: number;
-  application_point: [number, number];
-}
-
-interface DebugVelocity {
-  body_handle: number;
-  linear_velocity: [number, number];
-  angular_velocity: number;
-}
-
-interface DebugJoint {
-  handle: number;
-  body1: number;
-  body2: number;
-  anchor1: [number, number];
-  anchor2: [number, number];
-  joint_type: 'Fixed' | 'Revolute' | 'Prismatic' | 'Distance' | 'Spring';
-}
-
-type DebugShape =
-  | { Ball: { radius: number } }
-  | { Cuboid: { half_extents: [number, number] } }
-  | { Triangle: { vertices: [[number, number], [number, number], [number, number]] } }
-  | { Polygon: { vertices: [number, number][] } };
+const PING_INTERVAL = 2000; // ms between clock sync pings
+const INPUT_REDUNDANCY = 4; // inputs bundled per packet, covering lost packets
+const PROTOCOL_VERSION = 2; // must match server protocol::PROTOCOL_VERSION
 
 interface InputData {
   sequence: number;
@@ -78,6 +42,7 @@ interface GameSnapshot {
   tick: number;
   timestamp: number;
   entities: EntitySnapshot[];
+  deferred?: number[]; // in range but not updated this snapshot
 }
 
 interface EntitySnapshot {
@@ -157,6 +122,7 @@ class GameScene extends Phaser.Scene {
 
   // Debug
   private debugText: Phaser.GameObjects.Text | null = null;
+  private serverClockOffset = 0; // server clock minus local clock, ms
   private debugInfo = {
     rtt: 0,
     snapshotAge: 0,
@@ -433,9 +399,30 @@ class GameScene extends Phaser.Scene {
     );
   }
 
+  // Per-tick events arrive reliably ahead of the snapshot for the same tick
+  handleEvents(events: GameEvent[]) {
+    for (const event of events) {
+      switch (event.type) {
+        case 'Despawned': {
+          // Remove right away rather than waiting for the entity to drop out of snapshots
+          this.projectiles.get(event.entity_id)?.destroy();
+          this.projectiles.delete(event.entity_id);
+          this.remoteShips.get(event.entity_id)?.destroy();
+          this.remoteShips.delete(event.entity_id);
+          this.interpolationStates.delete(event.entity_id);
+          break;
+        }
+        case 'Killed':
+          console.log(`Entity ${event.entity_id} destroyed by ${event.killer_id}`);
+          break;
+      }
+    }
+  }
+
   handleSnapshot(snapshot: GameSnapshot) {
     const now = Date.now();
-    this.debugInfo.snapshotAge = now - snapshot.timestamp;
+    // Snapshot timestamps are on the server clock
+    this.debugInfo.snapshotAge = Math.round(now + this.serverClockOffset - snapshot.timestamp);
     this.debugInfo.entityCount = snapshot.entities.length;
 
     // Track existing entities to clean up removed ones
@@ -456,6 +443,11 @@ class GameScene extends Phaser.Scene {
       }
     }
 
+    // Entities the server skipped this time (distant, lower update rate) are still alive
+    for (const entityId of snapshot.deferred ?? []) {
+      seenEntities.add(entityId);
+    }
+
     // Clean up projectiles that are no longer in the snapshot
     for (const [entityId, projectile] of this.projectiles.entries()) {
       if (!seenEntities.has(entityId)) {
@@ -1159,14 +1151,20 @@ class GameScene extends Phaser.Scene {
     }
   }
 
-  updateRTT(rtt: number) {
+  updateClockSync(rtt: number, offset: number) {
     this.debugInfo.rtt = rtt;
+    this.serverClockOffset = offset;
   }
 }
 
 class CosmicCrunchersClient {
   private ws: WebSocket | null = null;
   private currentRoom: string | null = null;
+  private frameSeq = 0; // sequence number of the last frame sent
+  private encoding: Encoding = 'json'; // negotiated in Welcome; Hello is always JSON
+  private pingTimer: number | null = null;
+  private entityInfo = new Map<number, EntityInfo>(); // names and ship config by entity id
+  private sentInputs: InputData[] = []; // most recent first, resent with each packet
   private playerName: string = '';
   private serverHost: string;
   private serverPort: string;
@@ -1335,18 +1333,54 @@ class CosmicCrunchersClient {
   private async connect(): Promise<void> {
     return new Promise((resolve, reject) => {
       this.ws = new WebSocket(`${this.wsUrl}/ws`);
+      this.ws.binaryType = 'arraybuffer';
 
       this.ws.onopen = () => {
-        console.log('Connected to server');
-        resolve();
+        console.log('Connected to server, negotiating protocol');
+        this.frameSeq = 0;
+        this.encoding = 'json';
+        this.sentInputs = []; // the server restarts sequence checks for a new connection
+        this.send({
+          type: 'Hello',
+          protocol_version: PROTOCOL_VERSION,
+          encodings: ['msgpack', 'json'],
+          features: ['resume', 'debug_render', 'leaderboard']
+        });
       };
 
+      let welcomed = false;
       this.ws.onmessage = (event) => {
-        this.handleMessage(JSON.parse(event.data));
+        // Every server message arrives wrapped in a { seq, sent_at, message } frame,
+        // as JSON text or, once msgpack is negotiated, a MessagePack binary frame
+        const frame =
+          typeof event.data === 'string'
+            ? JSON.parse(event.data)
+            : decode(new Uint8Array(event.data as ArrayBuffer));
+        const message = (frame as Frame<ServerMessage>).message;
+        if (message.type === 'Welcome') {
+          console.log('Protocol negotiated:', message);
+          this.encoding = message.encoding;
+          welcomed = true;
+          this.pingTimer = window.setInterval(() => {
+            this.send({ type: 'Ping', timestamp: Date.now() });
+          }, PING_INTERVAL);
+          resolve();
+          return;
+        }
+        if (!welcomed && message.type === 'Error') {
+          // Handshake rejected; the server closes the socket after this
+          reject(new Error(message.message));
+          return;
+        }
+        this.handleMessage(message);
       };
 
       this.ws.onclose = () => {
         console.log('Disconnected from server');
+        if (this.pingTimer !== null) {
+          clearInterval(this.pingTimer);
+          this.pingTimer = null;
+        }
         this.ws = null;
         this.currentRoom = null;
       };
@@ -1380,16 +1414,26 @@ class CosmicCrunchersClient {
       throw new Error('Not connected to server');
     }
 
-    const message = {
+    const message: ClientMessage = {
       type: 'Join',
       room_code: roomCode.toUpperCase(),
       player_name: this.playerName
     };
 
-    this.ws.send(JSON.stringify(message));
+    this.send(message);
+  }
+
+  private send(message: ClientMessage) {
+    this.frameSeq += 1;
+    const frame: Frame<ClientMessage> = {
+      seq: this.frameSeq,
+      sent_at: Date.now(),
+      message
+    };
+    this.ws!.send(this.encoding === 'msgpack' ? encode(frame) : JSON.stringify(frame));
   }
 
-  private handleMessage(message: any) {
+  private handleMessage(message: ServerMessage) {
     //console.log('Received message:', message);
 
     switch (message.type) {
@@ -1417,44 +1461,61 @@ class CosmicCrunchersClient {
       case 'Snapshot':
         if (this.gameScene) {
           try {
-            // Parse the actual snapshot data from the message.data bytes
-            const snapshotBytes = new Uint8Array(message.data);
-            const snapshotJson = new TextDecoder().decode(snapshotBytes);
-            const snapshotData = JSON.parse(snapshotJson);
-            //console.log('Received snapshot with', snapshotData.entities?.length || 0, 'entities');
-            this.gameScene.handleSnapshot(snapshotData);
+            const snapshot = unpackSnapshot(message, this.entityInfo);
+            //console.log('Received snapshot with', snapshot.entities.length, 'entities');
+            this.gameScene.handleSnapshot(snapshot);
           } catch (error) {
-            console.error('Failed to parse snapshot:', error);
-            console.error('Raw message:', message);
+            console.error('Failed to unpack snapshot:', error);
+          }
+        }
+        break;
+
+      case 'EntityInfo':
+        this.entityInfo.set(message.entity_id, message);
+        break;
+
+      case 'Events':
+        for (const event of message.events) {
+          if (event.type === 'Despawned') {
+            this.entityInfo.delete(event.entity_id);
           }
         }
+        if (this.gameScene) {
+          this.gameScene.handleEvents(message.events);
+        }
         break;
 
-      case 'Pong':
-        const rtt = Date.now() - message.timestamp;
+      case 'Pong': {
+        // NTP-style: subtract server processing time, average the two one-way offsets
+        const receivedAt = Date.now();
+        const serverHold = message.server_send_time - message.server_receive_time;
+        const rtt = receivedAt - message.timestamp - serverHold;
+        const offset = ((message.server_receive_time - message.timestamp) +
+          (message.server_send_time - receivedAt)) / 2;
         if (this.gameScene) {
-          this.gameScene.updateRTT(rtt);
+          this.gameScene.updateClockSync(rtt, offset);
         }
         break;
+      }
+
+      case 'Probe':
+        // The server times this round trip for its own latency estimate
+        this.send({ type: 'ProbeReply', server_time: message.server_time });
+        break;
 
       case 'DebugRender':
         if (this.gameScene) {
-          try {
-            // Parse the debug render data
-            const debugBytes = new Uint8Array(message.data);
-            const debugJson = new TextDecoder().decode(debugBytes);
-            const debugData = JSON.parse(debugJson);
-            console.log('Received debug render data:', debugData);
-            this.gameScene.handleDebugRenderData(debugData);
-          } catch (error) {
-            console.error('Failed to parse debug render data:', error);
-            console.error('Raw message:', message);
-          }
+          console.log('Received debug render data:', message);
+          this.gameScene.handleDebugRenderData(message);
         }
         break;
 
+      case 'ServerShuttingDown':
+        this.updateStatus(`Server shutting down in ${message.seconds_remaining}s`);
+        break;
+
       case 'Error':
-        this.updateStatus(`Server error: ${message.message}`);
+        this.updateStatus(`Server error (${message.code}): ${message.message}`);
         break;
     }
   }
@@ -1463,18 +1524,12 @@ class CosmicCrunchersClient {
     if (!this.ws || this.ws.readyState !== WebSocket.OPEN) return;
 
     try {
-      // Convert input to JSON bytes for server
-      const inputJson = JSON.stringify(input);
-      const inputBytes = Array.from(new TextEncoder().encode(inputJson));
-
-      const message = {
-        type: 'Input',
-        sequence: input.sequence,
-        timestamp: input.timestamp,
-        data: inputBytes
-      };
-
-      this.ws.send(JSON.stringify(message));
+      this.sentInputs.unshift(input);
+      this.sentInputs.length = Math.min(this.sentInputs.length, INPUT_REDUNDANCY);
+      const packed = encodeInputs(this.sentInputs);
+      // MessagePack carries the packet as raw bytes; JSON needs a plain array
+      const data = this.encoding === 'msgpack' ? Uint8Array.from(packed) : packed;
+      this.send({ type: 'Input', data });
       console.log('Sent input:', input.sequence, 'thrust:', input.thrust, 'turn:', input.turn);
     } catch (error) {
       console.error('Failed to send input:', error);
@@ -1485,12 +1540,12 @@ class CosmicCrunchersClient {
     if (!this.ws || this.ws.readyState !== WebSocket.OPEN) return;
 
     // Send a request for debug render data (you can extend this with specific debug modes later)
-    const message = {
+    const message: ClientMessage = {
       type: 'RequestDebugRender',
       timestamp: Date.now()
     };
 
-    this.ws.send(JSON.stringify(message));
+    this.send(message);
     console.log('Requested debug render data from server');
   }
 
@@ -0,0 +1,234 @@
+// Minimal MessagePack codec for the server's `msgpack` encoding
+// (server/src/protocol.rs). Covers the types serde produces: nil, booleans,
+// integers, floats, strings, binary, arrays and string-keyed maps.
+
+const textEncoder = new TextEncoder();
+const textDecoder = new TextDecoder();
+
+class Writer {
+  private buffer = new Uint8Array(256);
+  private view = new DataView(this.buffer.buffer);
+  private length = 0;
+
+  private reserve(bytes: number) {
+    if (this.length + bytes <= this.buffer.length) return;
+    let size = this.buffer.length * 2;
+    while (size < this.length + bytes) size *= 2;
+    const grown = new Uint8Array(size);
+    grown.set(this.buffer.subarray(0, this.length));
+    this.buffer = grown;
+    this.view = new DataView(grown.buffer);
+  }
+
+  u8(value: number) {
+    this.reserve(1);
+    this.view.setUint8(this.length, value);
+    this.length += 1;
+  }
+
+  u16(value: number) {
+    this.reserve(2);
+    this.view.setUint16(this.length, value);
+    this.length += 2;
+  }
+
+  u32(value: number) {
+    this.reserve(4);
+    this.view.setUint32(this.length, value);
+    this.length += 4;
+  }
+
+  u64(value: number) {
+    this.reserve(8);
+    this.view.setBigUint64(this.length, BigInt(value));
+    this.length += 8;
+  }
+
+  i64(value: number) {
+    this.reserve(8);
+    this.view.setBigInt64(this.length, BigInt(value));
+    this.length += 8;
+  }
+
+  f64(value: number) {
+    this.reserve(8);
+    this.view.setFloat64(this.length, value);
+    this.length += 8;
+  }
+
+  bytes(bytes: Uint8Array) {
+    this.reserve(bytes.length);
+    this.buffer.set(bytes, this.length);
+    this.length += bytes.length;
+  }
+
+  finish(): Uint8Array {
+    return this.buffer.slice(0, this.length);
+  }
+}
+
+function writeLength(writer: Writer, length: number, fix: number, fixMax: number, codes: number[]) {
+  if (length <= fixMax) {
+    writer.u8(fix | length);
+  } else if (codes[0] !== undefined && length < 0x100) {
+    writer.u8(codes[0]);
+    writer.u8(length);
+  } else if (length < 0x10000) {
+    writer.u8(codes[1]);
+    writer.u16(length);
+  } else {
+    writer.u8(codes[2]);
+    writer.u32(length);
+  }
+}
+
+function writeNumber(writer: Writer, value: number) {
+  if (!Number.isInteger(value)) {
+    writer.u8(0xcb);
+    writer.f64(value);
+  } else if (value >= 0) {
+    if (value < 0x80) {
+      writer.u8(value);
+    } else if (value < 0x100) {
+      writer.u8(0xcc);
+      writer.u8(value);
+    } else if (value < 0x10000) {
+      writer.u8(0xcd);
+      writer.u16(value);
+    } else if (value < 0x100000000) {
+      writer.u8(0xce);
+      writer.u32(value);
+    } else {
+      writer.u8(0xcf);
+      writer.u64(value);
+    }
+  } else if (value >= -32) {
+    writer.u8(value & 0xff); // negative fixint
+  } else {
+    writer.u8(0xd3);
+    writer.i64(value);
+  }
+}
+
+function writeValue(writer: Writer, value: unknown) {
+  if (value === null || value === undefined) {
+    writer.u8(0xc0);
+  } else if (typeof value === 'boolean') {
+    writer.u8(value ? 0xc3 : 0xc2);
+  } else if (typeof value === 'number') {
+    writeNumber(writer, value);
+  } else if (typeof value === 'string') {
+    const bytes = textEncoder.encode(value);
+    writeLength(writer, bytes.length, 0xa0, 31, [0xd9, 0xda, 0xdb]);
+    writer.bytes(bytes);
+  } else if (value instanceof Uint8Array) {
+    writeLength(writer, value.length, 0, -1, [0xc4, 0xc5, 0xc6]);
+    writer.bytes(value);
+  } else if (Array.isArray(value)) {
+    writeLength(writer, value.length, 0x90, 15, [undefined as unknown as number, 0xdc, 0xdd]);
+    for (const item of value) writeValue(writer, item);
+  } else if (typeof value === 'object') {
+    // Absent optional fields are left out, matching serde's `default`
+    const entries = Object.entries(value).filter(([, item]) => item !== undefined);
+    writeLength(writer, entries.length, 0x80, 15, [undefined as unknown as number, 0xde, 0xdf]);
+    for (const [key, item] of entries) {
+      writeValue(writer, key);
+      writeValue(writer, item);
+    }
+  } else {
+    throw new Error(`Cannot encode ${typeof value} as MessagePack`);
+  }
+}
+
+export function encode(value: unknown): Uint8Array {
+  const writer = new Writer();
+  writeValue(writer, value);
+  return writer.finish();
+}
+
+class Reader {
+  private view: DataView;
+  private pos = 0;
+
+  constructor(private bytes: Uint8Array) {
+    this.view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+  }
+
+  private advance(bytes: number): number {
+    if (this.pos + bytes > this.bytes.length) {
+      throw new Error(`MessagePack read past end of ${this.bytes.length} byte message`);
+    }
+    const at = this.pos;
+    this.pos += bytes;
+    return at;
+  }
+
+  u8() { return this.view.getUint8(this.advance(1)); }
+  u16() { return this.view.getUint16(this.advance(2)); }
+  u32() { return this.view.getUint32(this.advance(4)); }
+
+  raw(length: number): Uint8Array {
+    const at = this.advance(length);
+    return this.bytes.slice(at, at + length);
+  }
+
+  str(length: number): string {
+    return textDecoder.decode(this.raw(length));
+  }
+
+  array(length: number): unknown[] {
+    const items = [];
+    for (let i = 0; i < length; i++) items.push(this.value());
+    return items;
+  }
+
+  map(length: number): Record<string, unknown> {
+    const map: Record<string, unknown> = {};
+    for (let i = 0; i < length; i++) {
+      const key = this.value();
+      map[String(key)] = this.value();
+    }
+    return map;
+  }
+
+  value(): unknown {
+    const code = this.u8();
+    if (code < 0x80) return code;
+    if (code < 0x90) return this.map(code & 0x0f);
+    if (code < 0xa0) return this.array(code & 0x0f);
+    if (code < 0xc0) return this.str(code & 0x1f);
+    if (code >= 0xe0) return code - 0x100;
+
+    switch (code) {
+      case 0xc0: return null;
+      case 0xc2: return false;
+      case 0xc3: return true;
+      case 0xc4: return this.raw(this.u8());
+      case 0xc5: return this.raw(this.u16());
+      case 0xc6: return this.raw(this.u32());
+      case 0xca: return this.view.getFloat32(this.advance(4));
+      case 0xcb: return this.view.getFloat64(this.advance(8));
+      case 0xcc: return this.u8();
+      case 0xcd: return this.u16();
+      case 0xce: return this.u32();
+      case 0xcf: return Number(this.view.getBigUint64(this.advance(8)));
+      case 0xd0: return this.view.getInt8(this.advance(1));
+      case 0xd1: return this.view.getInt16(this.advance(2));
+      case 0xd2: return this.view.getInt32(this.advance(4));
+      case 0xd3: return Number(this.view.getBigInt64(this.advance(8)));
+      case 0xd9: return this.str(this.u8());
+      case 0xda: return this.str(this.u16());
+      case 0xdb: return this.str(this.u32());
+      case 0xdc: return this.array(this.u16());
+      case 0xdd: return this.array(this.u32());
+      case 0xde: return this.map(this.u16());
+      case 0xdf: return this.map(this.u32());
+      default:
+        throw new Error(`Unsupported MessagePack type 0x${code.toString(16)}`);
+    }
+  }
+}
+
+export function decode(bytes: Uint8Array): unknown {
+  return new Reader(bytes).value();
+}
@@ -0,0 +1,67 @@
+// Generated from server/src/protocol.rs; do not edit.
+// Regenerate with `UPDATE_CLIENT_TYPES=1 cargo test client_types` in server/.
+
+export type Arena = { min: [number, number], max: [number, number], };
+
+export type ClientMessage = { "type": "Hello", protocol_version: number, encodings: Array<string>, features: Array<string>, } | { "type": "Join", room_code: string, player_name: string, ship_class?: string, } | { "type": "Input", data: Uint8Array | number[], } | { "type": "Resume", room_code: string, resume_token: string, } | { "type": "Ping", timestamp: number, } | { "type": "ProbeReply", server_time: number, } | { "type": "RequestDebugRender", timestamp: number, } | { "type": "Leave" };
+
+export type DebugBodyType = "Dynamic" | "Kinematic" | "Static";
+
+export type DebugCollider = { handle: number, parent_body: number, shape: DebugShape, position: [number, number], rotation: number, };
+
+export type DebugForce = { body_handle: number, force: [number, number], torque: number, application_point: [number, number], };
+
+export type DebugJoint = { handle: number, body1: number, body2: number, anchor1: [number, number], anchor2: [number, number], joint_type: DebugJointType, };
+
+export type DebugJointType = "Fixed" | "Revolute" | "Prismatic" | "Distance" | "Spring";
+
+export type DebugRenderData = { sequence: number, rigid_bodies: Array<DebugRigidBody>, colliders: Array<DebugCollider>, forces: Array<DebugForce>, velocities: Array<DebugVelocity>, joints: Array<DebugJoint>, };
+
+export type DebugRigidBody = { handle: number, position: [number, number], rotation: number, body_type: DebugBodyType, mass: number, linear_damping: number, angular_damping: number, };
+
+export type DebugShape = { "Ball": { radius: number, } } | { "Cuboid": { half_extents: [number, number], } } | { "Triangle": { vertices: [[number, number], [number, number], [number, number]], } } | { "Polygon": { vertices: Array<[number, number]>, } };
+
+export type DebugVelocity = { body_handle: number, linear_velocity: [number, number], angular_velocity: number, };
+
+export type DespawnReason = "expired" | "hit" | "destroyed" | "left";
+
+export type Encoding = "msgpack" | "json";
+
+export type EntityInfo = { entity_id: number, player: PlayerInfo | null, ship: Ship | null, };
+
+export type EntityKind = "player" | "projectile" | "enemy";
+
+export type ErrorCode = "handshake_required" | "incompatible_protocol" | "unsupported_encoding" | "malformed_message" | "join_failed" | "resume_failed" | "profile_unavailable" | "server_shutting_down" | "send_queue_overflow";
+
+export type Frame<M> = { 
+/**
+ * Per-connection, per-direction sequence number
+ */
+seq: number, 
+/**
+ * Sender clock in milliseconds when the frame was written (server frames use
+ * the monotonic server clock)
+ */
+sent_at: number, message: M, };
+
+export type GameEvent = { "type": "Spawned", entity_id: number, kind: EntityKind, position: [number, number], } | { "type": "Despawned", entity_id: number, reason: DespawnReason, } | { "type": "Hit", entity_id: number, shooter_id: string, damage: number, position: [number, number], } | { "type": "Killed", entity_id: number, killer_id: string, } | { "type": "Pickup", entity_id: number, player_id: string, item: string, } | { "type": "Explosion", position: [number, number], radius: number, };
+
+export type LeaderboardPage = { period: LeaderboardPeriod, ship_class: string | null, total: number, offset: number, entries: Array<RankedScore>, };
+
+export type LeaderboardPeriod = "all_time" | "weekly";
+
+export type PackedSnapshot = { sequence: number, tick: number, timestamp: number, arena: Arena, data: Uint8Array | number[], 
+/**
+ * Entities still in range but skipped this snapshot; clients keep their last state
+ */
+deferred: Array<number>, };
+
+export type PlayerInfo = { id: string, name: string, };
+
+export type PlayerProfile = { name: string, created_at: number, last_seen: number, matches_played: number, lifetime_kills: number, lifetime_deaths: number, lifetime_score: number, best_score: number, credits: number, last_ship_class: string | null, unlocked_ships: Array<string>, unlocked_weapons: Array<string>, upgrades: { [key in string]?: number }, };
+
+export type RankedScore = { rank: number, name: string, score: number, kills: number, ship_class: string, recorded_at: number, };
+
+export type ServerMessage = { "type": "Welcome", protocol_version: number, encoding: Encoding, features: Array<string>, tick_rate: number, snapshot_rate: number, } | { "type": "RoomJoined", room_code: string, player_id: string, entity_id: number, resume_token: string, } | { "type": "RoomCreated", room_code: string, } | { "type": "PlayerJoined", player_id: string, player_name: string, } | { "type": "PlayerLeft", player_id: string, } | { "type": "PlayerDisconnected", player_id: string, grace_secs: number, } | { "type": "PlayerReconnected", player_id: string, } | { "type": "ProfileLoaded", profile: PlayerProfile, } | { "type": "NewHighScore", player_name: string, score: number, ship_class: string, leaderboard: LeaderboardPage, } | { "type": "Snapshot" } & PackedSnapshot | { "type": "EntityInfo" } & EntityInfo | { "type": "Events", tick: number, events: Array<GameEvent>, } | { "type": "Pong", timestamp: number, server_receive_time: number, server_send_time: number, tick: number, } | { "type": "Probe", server_time: number, } | { "type": "DebugRender" } & DebugRenderData | { "type": "ServerShuttingDown", seconds_remaining: number, } | { "type": "Error", code: ErrorCode, message: string, };
+
+export type Ship = { class: string, thrust_power: number, turn_rate: number, max_speed: number, mass: number, size: number, };
@@ -0,0 +1,112 @@
+// Decoder for the server's bit-packed snapshots (server/src/snapshot_codec.rs).
+// Field widths and ranges must match the server.
+
+import type { EntityInfo, PackedSnapshot } from './protocol';
+
+const POSITION_BITS = 16;
+const ROTATION_BITS = 16;
+const VELOCITY_BITS = 16;
+const MAX_VELOCITY = 1024.0;
+const ANGULAR_VELOCITY_BITS = 12;
+const MAX_ANGULAR_VELOCITY = 32.0;
+const FRACTION_BITS = 8;
+const COUNT_BITS = 16;
+const VARINT_GROUP_BITS = 7;
+const KIND_BITS = 2;
+
+const KIND_PLAYER = 0;
+const KIND_PROJECTILE = 1;
+
+class BitReader {
+  private bitPos = 0;
+
+  constructor(private bytes: Uint8Array) {}
+
+  read(bits: number): number {
+    if (this.bitPos + bits > this.bytes.length * 8) {
+      throw new Error(`Read of ${bits} bits past end of ${this.bytes.length} byte snapshot`);
+    }
+    let value = 0;
+    for (let i = 0; i < bits; i++) {
+      const byte = this.bytes[this.bitPos >> 3];
+      if ((byte >> (this.bitPos & 7)) & 1) {
+        value += 2 ** i; // avoids sign issues with 32-bit shifts
+      }
+      this.bitPos++;
+    }
+    return value;
+  }
+
+  // 7-bit groups, low first, each followed by a "more follows" bit; exact up to 2^53
+  readVarint(): number {
+    let value = 0;
+    for (let shift = 0; shift < 64; shift += VARINT_GROUP_BITS) {
+      value += this.read(VARINT_GROUP_BITS) * 2 ** shift;
+      if (this.read(1) === 0) {
+        return value;
+      }
+    }
+    throw new Error('Varint longer than 64 bits');
+  }
+}
+
+function dequantize(value: number, min: number, max: number, bits: number): number {
+  return min + (value / (2 ** bits - 1)) * (max - min);
+}
+
+// Unpack a snapshot into the entity shape the game scene consumes
+export function unpackSnapshot(packed: PackedSnapshot, entityInfo: Map<number, EntityInfo>) {
+  const reader = new BitReader(new Uint8Array(packed.data));
+  const { min, max } = packed.arena;
+  const count = reader.read(COUNT_BITS);
+  const entities = [];
+
+  for (let i = 0; i < count; i++) {
+    const entityId = reader.readVarint();
+    const kind = reader.read(KIND_BITS);
+    const x = dequantize(reader.read(POSITION_BITS), min[0], max[0], POSITION_BITS);
+    const y = dequantize(reader.read(POSITION_BITS), min[1], max[1], POSITION_BITS);
+    const rotation = dequantize(reader.read(ROTATION_BITS), -Math.PI, Math.PI, ROTATION_BITS);
+    const vx = dequantize(reader.read(VELOCITY_BITS), -MAX_VELOCITY, MAX_VELOCITY, VELOCITY_BITS);
+    const vy = dequantize(reader.read(VELOCITY_BITS), -MAX_VELOCITY, MAX_VELOCITY, VELOCITY_BITS);
+    const angular = dequantize(
+      reader.read(ANGULAR_VELOCITY_BITS),
+      -MAX_ANGULAR_VELOCITY,
+      MAX_ANGULAR_VELOCITY,
+      ANGULAR_VELOCITY_BITS
+    );
+
+    let health = null;
+    if (kind !== KIND_PROJECTILE) {
+      health = {
+        fraction: dequantize(reader.read(FRACTION_BITS), 0, 1, FRACTION_BITS),
+        shield_fraction: dequantize(reader.read(FRACTION_BITS), 0, 1, FRACTION_BITS)
+      };
+    }
+
+    const info = entityInfo.get(entityId);
+    let entityType: any = 'Enemy';
+    if (kind === KIND_PLAYER) {
+      entityType = { Player: info?.player ?? {} };
+    } else if (kind === KIND_PROJECTILE) {
+      entityType = { Projectile: {} };
+    }
+
+    entities.push({
+      entity_id: entityId,
+      entity_type: entityType,
+      transform: { position: [x, y] as [number, number], rotation },
+      velocity: { linear: [vx, vy] as [number, number], angular },
+      health,
+      ship: info?.ship ?? undefined
+    });
+  }
+
+  return {
+    sequence: packed.sequence,
+    tick: packed.tick,
+    timestamp: packed.timestamp,
+    entities,
+    deferred: packed.deferred ?? []
+  };
+}
@@ -0,0 +1,126 @@
+//! Bit-level packing helpers for compact network encodings
+//!
+//! Values are written least significant bit first into a byte buffer, so a
+//! field can take any number of bits from 1 to 32 without byte alignment.
+//! Unbounded integers such as network IDs use [`BitWriter::write_varint`].
+//! Floats are sent as fixed-point integers over a known range with
+//! [`quantize`] and [`dequantize`].
+
+#![allow(dead_code)] // Some helpers only serve the test and tooling side of each codec
+
+const VARINT_GROUP_BITS: u32 = 7;
+const VARINT_GROUP_MASK: u64 = (1 << VARINT_GROUP_BITS) - 1;
+
+/// Appends fixed-width values to a byte buffer
+#[derive(Debug, Default)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    bit_len: usize,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write the low `bits` bits of `value`
+    pub fn write(&mut self, value: u32, bits: u32) {
+        debug_assert!(bits <= 32);
+        for i in 0..bits {
+            if self.bit_len.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            if (value >> i) & 1 == 1 {
+                let last = self.bytes.len() - 1;
+                self.bytes[last] |= 1 << (self.bit_len % 8);
+            }
+            self.bit_len += 1;
+        }
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write(value as u32, 1);
+    }
+
+    /// Write `value` in groups of 7 bits, low group first, each followed by a
+    /// bit saying whether another group follows
+    pub fn write_varint(&mut self, mut value: u64) {
+        loop {
+            self.write((value & VARINT_GROUP_MASK) as u32, VARINT_GROUP_BITS);
+            value >>= VARINT_GROUP_BITS;
+            self.write_bool(value != 0);
+            if value == 0 {
+                break;
+            }
+        }
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads values written by [`BitWriter`]
+#[derive(Debug)]
+pub struct BitReader<'a> {
+    bytes: &'a [u8],
+    bit_pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, bit_pos: 0 }
+    }
+
+    pub fn read(&mut self, bits: u32) -> Result<u32, String> {
+        if self.bit_pos + bits as usize > self.bytes.len() * 8 {
+            return Err(format!(
+                "Read of {} bits past end of {} byte buffer",
+                bits,
+                self.bytes.len()
+            ));
+        }
+        let mut value = 0u32;
+        for i in 0..bits {
+            let byte = self.bytes[self.bit_pos / 8];
+            if (byte >> (self.bit_pos % 8)) & 1 == 1 {
+                value |= 1 << i;
+            }
+            self.bit_pos += 1;
+        }
+        Ok(value)
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, String> {
+        Ok(self.read(1)? == 1)
+    }
+
+    pub fn read_varint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        for group in 0..u64::BITS.div_ceil(VARINT_GROUP_BITS) {
+            value |= (self.read(VARINT_GROUP_BITS)? as u64) << (group * VARINT_GROUP_BITS);
+            if !self.read_bool()? {
+                return Ok(value);
+            }
+        }
+        Err("Varint longer than 64 bits".to_string())
+    }
+}
+
+/// Map `value` in `min..=max` onto an unsigned integer of `bits` bits, clamping out of range values
+pub fn quantize(value: f32, min: f32, max: f32, bits: u32) -> u32 {
+    let steps = ((1u64 << bits) - 1) as f32;
+    let normalized = ((value - min) / (max - min)).clamp(0.0, 1.0);
+    (normalized * steps).round() as u32
+}
+
+/// Inverse of [`quantize`]
+pub fn dequantize(value: u32, min: f32, max: f32, bits: u32) -> f32 {
+    let steps = ((1u64 << bits) - 1) as f32;
+    min + (value as f32 / steps) * (max - min)
+}
+
+/// Largest error [`quantize`] introduces for in-range values
+pub fn max_error(min: f32, max: f32, bits: u32) -> f32 {
+    (max - min) / ((1u64 << bits) - 1) as f32 / 2.0
+}
@@ -0,0 +1,201 @@
+//! Room checkpoints
+//!
+//! A checkpoint is everything needed to bring a room back after the server
+//! stops: its code, a world save ([`crate::world_save`]) of its simulation and
+//! the players in it with their resume tokens. Checkpoints are stored in redb
+//! alongside profiles, keyed by room code, and a room has at most one. The
+//! whole table is rewritten each time, so rooms that closed drop out of it.
+
+use redb::{Database, ReadableTable, TableDefinition};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+use uuid::Uuid;
+
+/// Checkpoints table: room code -> MessagePack encoded RoomCheckpoint
+const CHECKPOINTS: TableDefinition<&str, &[u8]> = TableDefinition::new("room_checkpoints");
+
+/// A player held in a checkpointed room
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckpointPlayer {
+    pub id: Uuid,
+    pub name: String,
+    pub ship_class: String,
+    pub unlocked_ships: BTreeSet<String>,
+    pub resume_token: String,
+}
+
+/// One room as it was when checkpointed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoomCheckpoint {
+    pub room_code: String,
+    pub saved_at: u64, // unix millis
+    #[serde(with = "serde_bytes")]
+    pub world: Vec<u8>,
+    pub players: Vec<CheckpointPlayer>,
+}
+
+impl RoomCheckpoint {
+    pub fn new(room_code: &str, world: Vec<u8>, players: Vec<CheckpointPlayer>) -> Self {
+        Self {
+            room_code: room_code.to_string(),
+            saved_at: unix_millis(),
+            world,
+            players,
+        }
+    }
+
+    /// Time since the checkpoint was taken
+    pub fn age(&self) -> Duration {
+        Duration::from_millis(unix_millis().saturating_sub(self.saved_at))
+    }
+
+    /// Whether the players' rejoin grace ran out while the server was down
+    pub fn is_expired(&self, grace: Duration) -> bool {
+        self.age() >= grace
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Synchronous access to the checkpoints table
+#[derive(Clone)]
+pub struct CheckpointStore {
+    db: Arc<Database>,
+}
+
+impl CheckpointStore {
+    /// Wrap a database, creating the checkpoints table if needed
+    pub fn open(db: Arc<Database>) -> Result<Self, String> {
+        let txn = db.begin_write().map_err(|e| e.to_string())?;
+        txn.open_table(CHECKPOINTS).map_err(|e| e.to_string())?;
+        txn.commit().map_err(|e| e.to_string())?;
+        Ok(Self { db })
+    }
+
+    /// Replace every stored checkpoint with `checkpoints` in a single write
+    /// transaction, dropping those of rooms that have since closed
+    pub fn replace_all(&self, checkpoints: &[RoomCheckpoint]) -> Result<(), String> {
+        let txn = self.db.begin_write().map_err(|e| e.to_string())?;
+        {
+            let mut table = txn.open_table(CHECKPOINTS).map_err(|e| e.to_string())?;
+            table.retain(|_, _| false).map_err(|e| e.to_string())?;
+            for checkpoint in checkpoints {
+                let bytes = rmp_serde::to_vec_named(checkpoint).map_err(|e| e.to_string())?;
+                table
+                    .insert(checkpoint.room_code.as_str(), bytes.as_slice())
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        txn.commit().map_err(|e| e.to_string())
+    }
+
+    /// Every stored checkpoint; ones that fail to decode are skipped
+    pub fn all(&self) -> Result<Vec<RoomCheckpoint>, String> {
+        let txn = self.db.begin_read().map_err(|e| e.to_string())?;
+        let table = txn.open_table(CHECKPOINTS).map_err(|e| e.to_string())?;
+        let mut checkpoints = Vec::new();
+        for row in table.iter().map_err(|e| e.to_string())? {
+            let (room_code, value) = row.map_err(|e| e.to_string())?;
+            match rmp_serde::from_slice(value.value()) {
+                Ok(checkpoint) => checkpoints.push(checkpoint),
+                Err(e) => warn!(
+                    "Skipping corrupt checkpoint of room {}: {}",
+                    room_code.value(),
+                    e
+                ),
+            }
+        }
+        Ok(checkpoints)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const GRACE: Duration = Duration::from_secs(120);
+
+    /// A store backed by a fresh database file that is unlinked once opened
+    fn temp_store() -> CheckpointStore {
+        let path = std::env::temp_dir().join(format!("checkpoints-{}.redb", Uuid::new_v4()));
+        let db = Database::create(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        CheckpointStore::open(Arc::new(db)).unwrap()
+    }
+
+    fn checkpoint(room_code: &str, players: usize) -> RoomCheckpoint {
+        let players = (0..players)
+            .map(|i| CheckpointPlayer {
+                id: Uuid::new_v4(),
+                name: format!("pilot{}", i),
+                ship_class: "standard".to_string(),
+                unlocked_ships: BTreeSet::from(["standard".to_string()]),
+                resume_token: Uuid::new_v4().to_string(),
+            })
+            .collect();
+        RoomCheckpoint::new(room_code, vec![1, 2, 3, room_code.len() as u8], players)
+    }
+
+    fn sorted(mut checkpoints: Vec<RoomCheckpoint>) -> Vec<RoomCheckpoint> {
+        checkpoints.sort_by(|a, b| a.room_code.cmp(&b.room_code));
+        checkpoints
+    }
+
+    #[test]
+    fn replaced_checkpoints_read_back_unchanged() {
+        let store = temp_store();
+        assert!(store.all().unwrap().is_empty());
+
+        let saved = vec![checkpoint("BBBB", 2), checkpoint("AAAA", 1)];
+        store.replace_all(&saved).unwrap();
+
+        assert_eq!(sorted(store.all().unwrap()), sorted(saved));
+    }
+
+    #[test]
+    fn replace_all_drops_closed_rooms() {
+        let store = temp_store();
+        store
+            .replace_all(&[checkpoint("AAAA", 1), checkpoint("BBBB", 1)])
+            .unwrap();
+
+        let mut reopened = checkpoint("BBBB", 3);
+        reopened.saved_at += 1;
+        store
+            .replace_all(&[reopened.clone(), checkpoint("CCCC", 0)])
+            .unwrap();
+
+        let rooms = sorted(store.all().unwrap());
+        let codes: Vec<&str> = rooms.iter().map(|c| c.room_code.as_str()).collect();
+        assert_eq!(codes, ["BBBB", "CCCC"]);
+        assert_eq!(rooms[0], reopened);
+
+        store.replace_all(&[]).unwrap();
+        assert!(store.all().unwrap().is_empty());
+    }
+
+    #[test]
+    fn checkpoint_older_than_grace_is_expired() {
+        let store = temp_store();
+        let fresh = checkpoint("AAAA", 1);
+        let mut stale = checkpoint("BBBB", 1);
+        stale.saved_at -= GRACE.as_millis() as u64 + 1_000;
+        store.replace_all(&[fresh, stale]).unwrap();
+
+        let rooms = sorted(store.all().unwrap());
+        assert!(!rooms[0].is_expired(GRACE));
+        assert!(rooms[1].is_expired(GRACE));
+        assert!(rooms[1].age() > GRACE);
+    }
+}
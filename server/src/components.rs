@@ -81,6 +81,21 @@ pub struct Health {
     pub last_damage_time: f64,
 }
 
+impl Health {
+    /// Take damage on the shield first, then the hull less armor; returns true if this hit was lethal
+    pub fn take_damage(&mut self, amount: f32, time: f64) -> bool {
+        if self.current <= 0.0 {
+            return false;
+        }
+        let absorbed = amount.min(self.shield);
+        self.shield -= absorbed;
+        let hull_damage = (amount - absorbed - self.armor).max(0.0);
+        self.current = (self.current - hull_damage).max(0.0);
+        self.last_damage_time = time;
+        self.current <= 0.0
+    }
+}
+
 impl Default for Health {
     fn default() -> Self {
         Self {
@@ -108,7 +123,7 @@ pub struct Player {
 }
 
 /// Buffered input data with timestamps
-#[derive(Debug, Clone, SerdeSerialize, SerdeDeserialize)]
+#[derive(Debug, Clone, PartialEq, SerdeSerialize, SerdeDeserialize)]
 pub struct InputData {
     pub sequence: u32,
     pub timestamp: u64,
@@ -132,7 +147,7 @@ impl Default for InputData {
 }
 
 /// Input buffer for processing delayed inputs
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, SerdeSerialize, SerdeDeserialize)]
 pub struct InputBuffer {
     pub buffer: VecDeque<InputData>,
     pub last_processed_sequence: u32,
@@ -150,7 +165,18 @@ impl Default for InputBuffer {
 }
 
 impl InputBuffer {
-    pub fn add_input(&mut self, input: InputData) {
+    /// Buffer an input; returns false for inputs already buffered or processed
+    pub fn add_input(&mut self, input: InputData) -> bool {
+        // Clients resend recent inputs in every packet, so most repeats land here
+        if input.sequence <= self.last_processed_sequence
+            || self
+                .buffer
+                .iter()
+                .any(|existing| existing.sequence == input.sequence)
+        {
+            return false;
+        }
+
         // Insert in sequence order
         let mut insert_pos = self.buffer.len();
         for (i, existing) in self.buffer.iter().enumerate().rev() {
@@ -166,6 +192,7 @@ impl InputBuffer {
         while self.buffer.len() > self.max_buffer_size {
             self.buffer.pop_front();
         }
+        true
     }
 
     pub fn get_next_input(&mut self) -> Option<InputData> {
@@ -197,20 +224,22 @@ impl InputBuffer {
 }
 
 /// Physics body reference
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, SerdeSerialize, SerdeDeserialize)]
 pub struct RigidBody {
     pub handle: RigidBodyHandle,
 }
 
 /// Physics collider reference
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, SerdeSerialize, SerdeDeserialize)]
 pub struct Collider {
     pub handle: ColliderHandle,
 }
 
 /// Ship-specific properties
-#[derive(Debug, Clone, SerdeSerialize, SerdeDeserialize)]
+#[derive(Debug, Clone, PartialEq, SerdeSerialize, SerdeDeserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
 pub struct Ship {
+    pub class: String, // content registry ship class id
     pub thrust_power: f32,
     pub turn_rate: f32,
     pub max_speed: f32,
@@ -221,6 +250,7 @@ pub struct Ship {
 impl Default for Ship {
     fn default() -> Self {
         Self {
+            class: "standard".to_string(),
             thrust_power: 2000.0,
             turn_rate: 5.0, // radians per second
             max_speed: 100.0,
@@ -239,14 +269,29 @@ pub struct Projectile {
     pub owner_id: Uuid,
 }
 
+/// Marks a ship whose player disconnected; it holds still until they resume
+#[derive(Debug, Clone, SerdeSerialize, SerdeDeserialize)]
+pub struct Frozen;
+
+/// Marks an entity every player receives regardless of distance, such as a boss
+#[derive(Debug, Clone, SerdeSerialize, SerdeDeserialize)]
+pub struct AlwaysRelevant;
+
+/// Stable ID identifying an entity on the wire
+///
+/// Allocated from a per-room counter and never reused, unlike `hecs::Entity`
+/// slots, so clients can't mistake a new entity for a despawned one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SerdeSerialize, SerdeDeserialize)]
+pub struct NetId(pub u64);
+
 /// Lifetime tracking for temporary entities
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, SerdeSerialize, SerdeDeserialize)]
 pub struct Lifetime {
     pub remaining: f32, // seconds
 }
 
 /// Enemy AI component
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, SerdeSerialize, SerdeDeserialize)]
 pub struct Enemy {
     pub ai_type: EnemyType,
     pub target: Option<hecs::Entity>,
@@ -254,13 +299,14 @@ pub struct Enemy {
     pub last_action_time: f64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, SerdeSerialize, SerdeDeserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum EnemyType {
     Chaser { speed: f32 },
     Shooter { range: f32, fire_rate: f32 },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, SerdeSerialize, SerdeDeserialize)]
 pub enum EnemyState {
     Idle,
     Seeking,
@@ -269,15 +315,17 @@ pub enum EnemyState {
 }
 
 /// Weapon system component
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, SerdeSerialize, SerdeDeserialize)]
 pub struct Weapon {
+    pub id: String, // content registry weapon id
     pub weapon_type: WeaponType,
     pub last_fire_time: f64,
     pub ammo: Option<u32>, // None for unlimited
     pub cooldown: f32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, SerdeSerialize, SerdeDeserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum WeaponType {
     RapidFire {
         rate: f32,
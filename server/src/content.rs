@@ -0,0 +1,836 @@
+//! Data-driven game content
+//!
+//! Ship classes, weapons, enemies, waves and drop tables are defined in TOML
+//! files inside a content directory (see `server/content/`) and loaded into a
+//! [`ContentRegistry`] at startup. The registry is validated as a whole so
+//! designers get every problem reported at once instead of one per restart.
+
+#![allow(dead_code)] // Enemies, waves and drops are consumed in Phase 4
+
+use crate::components::{EnemyType, Health, Ship, Weapon, WeaponType};
+use crate::state_hash::StateHasher;
+use rapier2d::prelude::{ColliderBuilder, Point};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    fs,
+    path::Path,
+    time::SystemTime,
+};
+
+/// Data file names inside the content directory
+const SHIPS_FILE: &str = "ships.toml";
+const WEAPONS_FILE: &str = "weapons.toml";
+const ENEMIES_FILE: &str = "enemies.toml";
+const WAVES_FILE: &str = "waves.toml";
+const DROPS_FILE: &str = "drops.toml";
+
+/// Content shipped with the server, used when no content directory is given
+const BUILTIN_SHIPS: &str = include_str!("../content/ships.toml");
+const BUILTIN_WEAPONS: &str = include_str!("../content/weapons.toml");
+const BUILTIN_ENEMIES: &str = include_str!("../content/enemies.toml");
+const BUILTIN_WAVES: &str = include_str!("../content/waves.toml");
+const BUILTIN_DROPS: &str = include_str!("../content/drops.toml");
+
+/// Shared health/shield tuning for ships and enemies
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthDef {
+    pub max: f32,
+    pub armor: f32,
+    pub shield_max: f32,
+    pub shield_recharge_rate: f32,
+    pub shield_recharge_delay: f32,
+}
+
+impl HealthDef {
+    /// Build a fresh Health component at full health and shield
+    pub fn to_component(&self) -> Health {
+        Health {
+            current: self.max,
+            max: self.max,
+            armor: self.armor,
+            shield: self.shield_max,
+            shield_max: self.shield_max,
+            shield_recharge_rate: self.shield_recharge_rate,
+            shield_recharge_delay: self.shield_recharge_delay,
+            last_damage_time: 0.0,
+        }
+    }
+}
+
+/// Collider shape of a ship hull, in ship-local space with the nose along +x
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum HullShape {
+    Ball { radius: f32 },
+    Cuboid { half_width: f32, half_height: f32 },
+    Polygon { points: Vec<[f32; 2]> },
+}
+
+/// Physical properties of a ship hull
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HullDef {
+    #[serde(flatten)]
+    pub shape: HullShape,
+    pub mass: f32,
+    pub friction: f32,
+    pub restitution: f32,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+}
+
+impl HullDef {
+    /// Bounding radius of the hull, used for the Ship component's size
+    pub fn radius(&self) -> f32 {
+        match &self.shape {
+            HullShape::Ball { radius } => *radius,
+            HullShape::Cuboid {
+                half_width,
+                half_height,
+            } => half_width.hypot(*half_height),
+            HullShape::Polygon { points } => {
+                points.iter().map(|[x, y]| x.hypot(*y)).fold(0.0, f32::max)
+            }
+        }
+    }
+
+    /// Collider for this hull, or None if a polygon hull is degenerate
+    pub fn collider(&self) -> Option<ColliderBuilder> {
+        let builder = match &self.shape {
+            HullShape::Ball { radius } => ColliderBuilder::ball(*radius),
+            HullShape::Cuboid {
+                half_width,
+                half_height,
+            } => ColliderBuilder::cuboid(*half_width, *half_height),
+            HullShape::Polygon { points } => {
+                let points: Vec<Point<f32>> =
+                    points.iter().map(|[x, y]| Point::new(*x, *y)).collect();
+                ColliderBuilder::convex_hull(&points)?
+            }
+        };
+
+        Some(
+            builder
+                .mass(self.mass)
+                .friction(self.friction)
+                .restitution(self.restitution),
+        )
+    }
+}
+
+/// A selectable ship class
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShipClassDef {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub unlocked_by_default: bool,
+    pub thrust_power: f32,
+    pub turn_rate: f32,
+    pub max_speed: f32,
+    /// Score for destroying a ship of this class
+    pub score: u32,
+    pub primary_weapon: String,
+    pub hull: HullDef,
+    pub health: HealthDef,
+}
+
+impl ShipClassDef {
+    /// Build the Ship component; mass is filled in from rapier after spawning
+    pub fn to_component(&self) -> Ship {
+        Ship {
+            class: self.id.clone(),
+            thrust_power: self.thrust_power,
+            turn_rate: self.turn_rate,
+            max_speed: self.max_speed,
+            mass: 0.0,
+            size: self.hull.radius(),
+        }
+    }
+}
+
+/// Bodies spawned by a weapon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectileDef {
+    pub radius: f32,
+    pub density: f32,
+    pub lifetime: f32,
+    pub spawn_offset: f32,
+}
+
+/// A weapon archetype with its tuning
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeaponDef {
+    pub id: String,
+    pub name: String,
+    pub cooldown: f32,
+    #[serde(default)]
+    pub ammo: Option<u32>,
+    pub kind: WeaponType,
+    pub projectile: ProjectileDef,
+}
+
+impl WeaponDef {
+    /// Build a ready-to-fire Weapon component
+    pub fn to_component(&self) -> Weapon {
+        Weapon {
+            id: self.id.clone(),
+            weapon_type: self.kind.clone(),
+            last_fire_time: 0.0,
+            ammo: self.ammo,
+            cooldown: self.cooldown,
+        }
+    }
+}
+
+/// An enemy archetype
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnemyDef {
+    pub id: String,
+    pub name: String,
+    pub radius: f32,
+    pub score: u32,
+    pub drop_table: Option<String>,
+    pub ai: EnemyType,
+    pub health: HealthDef,
+}
+
+/// A group of enemies spawned during a wave
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WaveSpawnDef {
+    pub enemy: String,
+    pub count: u32,
+}
+
+/// A single wave of the progression
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WaveDef {
+    pub number: u32,
+    pub duration: f32,
+    pub spawn_interval: f32,
+    pub spawns: Vec<WaveSpawnDef>,
+    #[serde(default)]
+    pub boss: Option<String>,
+}
+
+/// Items that can drop from destroyed enemies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DropItem {
+    Ammo,
+    Fuel,
+    SecondaryAmmo,
+    Credits,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DropEntryDef {
+    pub item: DropItem,
+    pub chance: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DropTableDef {
+    pub id: String,
+    pub entries: Vec<DropEntryDef>,
+}
+
+/// File layouts as they appear on disk
+#[derive(Debug, Deserialize)]
+struct ShipsFile {
+    default_class: String,
+    #[serde(rename = "ship", default)]
+    ships: Vec<ShipClassDef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WeaponsFile {
+    #[serde(rename = "weapon", default)]
+    weapons: Vec<WeaponDef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EnemiesFile {
+    #[serde(rename = "enemy", default)]
+    enemies: Vec<EnemyDef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WavesFile {
+    #[serde(rename = "wave", default)]
+    waves: Vec<WaveDef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DropsFile {
+    #[serde(rename = "table", default)]
+    tables: Vec<DropTableDef>,
+}
+
+/// All game content, validated and indexed by id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentRegistry {
+    pub default_ship_class: String,
+    pub ships: HashMap<String, ShipClassDef>,
+    pub weapons: HashMap<String, WeaponDef>,
+    pub enemies: HashMap<String, EnemyDef>,
+    pub waves: Vec<WaveDef>,
+    pub drop_tables: HashMap<String, DropTableDef>,
+}
+
+impl Default for ContentRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl ContentRegistry {
+    /// Hash identifying this exact content, independent of map ordering
+    pub fn fingerprint(&self) -> u64 {
+        // serde_json objects are sorted maps, which makes the encoding canonical
+        let canonical = serde_json::to_value(self)
+            .and_then(|value| serde_json::to_vec(&value))
+            .unwrap_or_default();
+        let mut hasher = StateHasher::default();
+        hasher.write_bytes(&canonical);
+        hasher.finish()
+    }
+
+    /// Load and validate every content file in `dir`
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let read = |name: &str| {
+            let path = dir.join(name);
+            fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))
+        };
+
+        Self::from_sources(
+            &read(SHIPS_FILE)?,
+            &read(WEAPONS_FILE)?,
+            &read(ENEMIES_FILE)?,
+            &read(WAVES_FILE)?,
+            &read(DROPS_FILE)?,
+        )
+        .map_err(|e| format!("Invalid content in {}:\n{}", dir.display(), e))
+    }
+
+    /// Most recent modification time across the content files in `dir`
+    pub fn modified(dir: &Path) -> Option<SystemTime> {
+        [
+            SHIPS_FILE,
+            WEAPONS_FILE,
+            ENEMIES_FILE,
+            WAVES_FILE,
+            DROPS_FILE,
+        ]
+        .iter()
+        .filter_map(|name| fs::metadata(dir.join(name)).ok()?.modified().ok())
+        .max()
+    }
+
+    /// Content compiled into the server binary
+    pub fn builtin() -> Self {
+        Self::from_sources(
+            BUILTIN_SHIPS,
+            BUILTIN_WEAPONS,
+            BUILTIN_ENEMIES,
+            BUILTIN_WAVES,
+            BUILTIN_DROPS,
+        )
+        .expect("Built-in content must be valid")
+    }
+
+    /// Parse the raw file contents and validate the result
+    fn from_sources(
+        ships: &str,
+        weapons: &str,
+        enemies: &str,
+        waves: &str,
+        drops: &str,
+    ) -> Result<Self, String> {
+        let ships: ShipsFile = parse(SHIPS_FILE, ships)?;
+        let weapons: WeaponsFile = parse(WEAPONS_FILE, weapons)?;
+        let enemies: EnemiesFile = parse(ENEMIES_FILE, enemies)?;
+        let mut waves: WavesFile = parse(WAVES_FILE, waves)?;
+        let drops: DropsFile = parse(DROPS_FILE, drops)?;
+
+        let mut errors = Vec::new();
+        waves.waves.sort_by_key(|wave| wave.number);
+
+        let registry = Self {
+            default_ship_class: ships.default_class,
+            ships: index(SHIPS_FILE, ships.ships, |s| &s.id, &mut errors),
+            weapons: index(WEAPONS_FILE, weapons.weapons, |w| &w.id, &mut errors),
+            enemies: index(ENEMIES_FILE, enemies.enemies, |e| &e.id, &mut errors),
+            waves: waves.waves,
+            drop_tables: index(DROPS_FILE, drops.tables, |t| &t.id, &mut errors),
+        };
+
+        registry.validate(&mut errors);
+
+        if errors.is_empty() {
+            Ok(registry)
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    /// Check value ranges and cross references between files
+    fn validate(&self, errors: &mut Vec<String>) {
+        match self.ships.get(&self.default_ship_class) {
+            None => errors.push(format!(
+                "{}: default_class '{}' is not a defined ship",
+                SHIPS_FILE, self.default_ship_class
+            )),
+            Some(ship) if !ship.unlocked_by_default => errors.push(format!(
+                "{}: default_class '{}' must be unlocked_by_default",
+                SHIPS_FILE, self.default_ship_class
+            )),
+            Some(_) => {}
+        }
+
+        for ship in self.ships.values() {
+            let ctx = format!("{}: ship '{}'", SHIPS_FILE, ship.id);
+            check_positive(errors, &ctx, "thrust_power", ship.thrust_power);
+            check_positive(errors, &ctx, "turn_rate", ship.turn_rate);
+            check_positive(errors, &ctx, "max_speed", ship.max_speed);
+            check_hull(errors, &ctx, &ship.hull);
+            check_positive(errors, &ctx, "hull.mass", ship.hull.mass);
+            check_non_negative(errors, &ctx, "hull.friction", ship.hull.friction);
+            check_non_negative(errors, &ctx, "hull.restitution", ship.hull.restitution);
+            check_non_negative(
+                errors,
+                &ctx,
+                "hull.linear_damping",
+                ship.hull.linear_damping,
+            );
+            check_non_negative(
+                errors,
+                &ctx,
+                "hull.angular_damping",
+                ship.hull.angular_damping,
+            );
+            check_health(errors, &ctx, &ship.health);
+            if !self.weapons.contains_key(&ship.primary_weapon) {
+                errors.push(format!(
+                    "{}: primary_weapon '{}' is not a defined weapon",
+                    ctx, ship.primary_weapon
+                ));
+            }
+        }
+
+        for weapon in self.weapons.values() {
+            let ctx = format!("{}: weapon '{}'", WEAPONS_FILE, weapon.id);
+            check_positive(errors, &ctx, "cooldown", weapon.cooldown);
+            check_positive(errors, &ctx, "projectile.radius", weapon.projectile.radius);
+            check_positive(
+                errors,
+                &ctx,
+                "projectile.density",
+                weapon.projectile.density,
+            );
+            check_positive(
+                errors,
+                &ctx,
+                "projectile.lifetime",
+                weapon.projectile.lifetime,
+            );
+            check_non_negative(
+                errors,
+                &ctx,
+                "projectile.spawn_offset",
+                weapon.projectile.spawn_offset,
+            );
+            check_weapon_kind(errors, &ctx, &weapon.kind);
+        }
+
+        for enemy in self.enemies.values() {
+            let ctx = format!("{}: enemy '{}'", ENEMIES_FILE, enemy.id);
+            check_positive(errors, &ctx, "radius", enemy.radius);
+            check_health(errors, &ctx, &enemy.health);
+            if let Some(table) = &enemy.drop_table
+                && !self.drop_tables.contains_key(table)
+            {
+                errors.push(format!(
+                    "{}: drop_table '{}' is not a defined drop table",
+                    ctx, table
+                ));
+            }
+        }
+
+        for (i, wave) in self.waves.iter().enumerate() {
+            let ctx = format!("{}: wave {}", WAVES_FILE, wave.number);
+            if i > 0 && self.waves[i - 1].number == wave.number {
+                errors.push(format!("{}: duplicate wave number", ctx));
+            }
+            check_positive(errors, &ctx, "duration", wave.duration);
+            check_positive(errors, &ctx, "spawn_interval", wave.spawn_interval);
+            for spawn in &wave.spawns {
+                if !self.enemies.contains_key(&spawn.enemy) {
+                    errors.push(format!(
+                        "{}: spawn enemy '{}' is not a defined enemy",
+                        ctx, spawn.enemy
+                    ));
+                }
+            }
+            if let Some(boss) = &wave.boss
+                && !self.enemies.contains_key(boss)
+            {
+                errors.push(format!("{}: boss '{}' is not a defined enemy", ctx, boss));
+            }
+        }
+
+        for table in self.drop_tables.values() {
+            let ctx = format!("{}: table '{}'", DROPS_FILE, table.id);
+            let mut total = 0.0;
+            for entry in &table.entries {
+                if !(0.0..=1.0).contains(&entry.chance) {
+                    errors.push(format!(
+                        "{}: chance for {:?} must be between 0.0 and 1.0 (got {})",
+                        ctx, entry.item, entry.chance
+                    ));
+                }
+                total += entry.chance;
+            }
+            if total > 1.0 + f32::EPSILON {
+                errors.push(format!(
+                    "{}: chances add up to {:.2}, which exceeds 1.0",
+                    ctx, total
+                ));
+            }
+        }
+    }
+
+    /// The class players spawn with unless they pick another one
+    pub fn default_ship(&self) -> &ShipClassDef {
+        &self.ships[&self.default_ship_class]
+    }
+
+    pub fn ship(&self, id: &str) -> Option<&ShipClassDef> {
+        self.ships.get(id)
+    }
+
+    /// Classes any player may fly without unlocking them first
+    pub fn default_unlocked_ships(&self) -> impl Iterator<Item = &ShipClassDef> {
+        self.ships.values().filter(|ship| ship.unlocked_by_default)
+    }
+
+    pub fn weapon(&self, id: &str) -> Option<&WeaponDef> {
+        self.weapons.get(id)
+    }
+}
+
+/// Parse a single TOML file, prefixing errors with the file name
+fn parse<T: serde::de::DeserializeOwned>(file: &str, source: &str) -> Result<T, String> {
+    toml::from_str(source).map_err(|e| format!("{}: {}", file, e))
+}
+
+/// Index definitions by id, reporting duplicates
+fn index<T>(
+    file: &str,
+    defs: Vec<T>,
+    id: impl Fn(&T) -> &String,
+    errors: &mut Vec<String>,
+) -> HashMap<String, T> {
+    let mut map = HashMap::new();
+    for def in defs {
+        match map.entry(id(&def).clone()) {
+            Entry::Occupied(entry) => {
+                errors.push(format!("{}: duplicate id '{}'", file, entry.key()));
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(def);
+            }
+        }
+    }
+    map
+}
+
+fn check_positive(errors: &mut Vec<String>, ctx: &str, field: &str, value: f32) {
+    if !(value > 0.0 && value.is_finite()) {
+        errors.push(format!(
+            "{}: {} must be positive (got {})",
+            ctx, field, value
+        ));
+    }
+}
+
+fn check_non_negative(errors: &mut Vec<String>, ctx: &str, field: &str, value: f32) {
+    if !(value >= 0.0 && value.is_finite()) {
+        errors.push(format!(
+            "{}: {} must not be negative (got {})",
+            ctx, field, value
+        ));
+    }
+}
+
+fn check_hull(errors: &mut Vec<String>, ctx: &str, hull: &HullDef) {
+    match &hull.shape {
+        HullShape::Ball { radius } => check_positive(errors, ctx, "hull.radius", *radius),
+        HullShape::Cuboid {
+            half_width,
+            half_height,
+        } => {
+            check_positive(errors, ctx, "hull.half_width", *half_width);
+            check_positive(errors, ctx, "hull.half_height", *half_height);
+        }
+        HullShape::Polygon { .. } => {
+            if hull.collider().is_none() {
+                errors.push(format!(
+                    "{}: hull.points must describe a convex polygon with at least 3 distinct points",
+                    ctx
+                ));
+            }
+        }
+    }
+}
+
+fn check_weapon_kind(errors: &mut Vec<String>, ctx: &str, kind: &WeaponType) {
+    match kind {
+        WeaponType::RapidFire {
+            rate,
+            damage,
+            speed,
+        } => {
+            check_positive(errors, ctx, "kind.rate", *rate);
+            check_positive(errors, ctx, "kind.damage", *damage);
+            check_positive(errors, ctx, "kind.speed", *speed);
+        }
+        WeaponType::Beam {
+            damage_per_second,
+            range,
+        } => {
+            check_positive(errors, ctx, "kind.damage_per_second", *damage_per_second);
+            check_positive(errors, ctx, "kind.range", *range);
+        }
+        WeaponType::Spread {
+            count,
+            spread_angle,
+            damage,
+            speed,
+        } => {
+            if *count == 0 {
+                errors.push(format!("{}: kind.count must be at least 1", ctx));
+            }
+            check_non_negative(errors, ctx, "kind.spread_angle", *spread_angle);
+            check_positive(errors, ctx, "kind.damage", *damage);
+            check_positive(errors, ctx, "kind.speed", *speed);
+        }
+        WeaponType::Homing {
+            damage,
+            speed,
+            turn_rate,
+        } => {
+            check_positive(errors, ctx, "kind.damage", *damage);
+            check_positive(errors, ctx, "kind.speed", *speed);
+            check_positive(errors, ctx, "kind.turn_rate", *turn_rate);
+        }
+        WeaponType::AreaNuke { damage, radius } => {
+            check_positive(errors, ctx, "kind.damage", *damage);
+            check_positive(errors, ctx, "kind.radius", *radius);
+        }
+    }
+}
+
+fn check_health(errors: &mut Vec<String>, ctx: &str, health: &HealthDef) {
+    check_positive(errors, ctx, "health.max", health.max);
+    check_non_negative(errors, ctx, "health.armor", health.armor);
+    check_non_negative(errors, ctx, "health.shield_max", health.shield_max);
+    check_non_negative(
+        errors,
+        ctx,
+        "health.shield_recharge_rate",
+        health.shield_recharge_rate,
+    );
+    check_non_negative(
+        errors,
+        ctx,
+        "health.shield_recharge_delay",
+        health.shield_recharge_delay,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Load the built-in content with one file's source replaced
+    fn load_with(file: &str, source: &str) -> Result<ContentRegistry, String> {
+        let pick = |name: &str, builtin: &'static str| {
+            if name == file {
+                source.to_string()
+            } else {
+                builtin.to_string()
+            }
+        };
+        ContentRegistry::from_sources(
+            &pick(SHIPS_FILE, BUILTIN_SHIPS),
+            &pick(WEAPONS_FILE, BUILTIN_WEAPONS),
+            &pick(ENEMIES_FILE, BUILTIN_ENEMIES),
+            &pick(WAVES_FILE, BUILTIN_WAVES),
+            &pick(DROPS_FILE, BUILTIN_DROPS),
+        )
+    }
+
+    fn expect_error(result: Result<ContentRegistry, String>, needle: &str) {
+        match result {
+            Ok(_) => panic!("content loaded, expected an error containing {:?}", needle),
+            Err(e) => assert!(e.contains(needle), "{:?} not found in:\n{}", needle, e),
+        }
+    }
+
+    fn validate_weapon_kind(kind: WeaponType) -> Vec<String> {
+        let mut errors = Vec::new();
+        check_weapon_kind(&mut errors, "weapon", &kind);
+        errors
+    }
+
+    #[test]
+    fn builtin_content_loads() {
+        let content = ContentRegistry::builtin();
+        assert_eq!(content.default_ship().id, content.default_ship_class);
+        assert!(content.default_ship().unlocked_by_default);
+        assert!(
+            content
+                .weapon(&content.default_ship().primary_weapon)
+                .is_some()
+        );
+        assert!(!content.waves.is_empty());
+        assert!(
+            content
+                .waves
+                .windows(2)
+                .all(|pair| pair[0].number < pair[1].number)
+        );
+        assert_eq!(
+            content.fingerprint(),
+            ContentRegistry::builtin().fingerprint()
+        );
+    }
+
+    #[test]
+    fn duplicate_id_is_rejected() {
+        let first = BUILTIN_WEAPONS.find("[[weapon]]").unwrap();
+        let second = first + 1 + BUILTIN_WEAPONS[first + 1..].find("[[weapon]]").unwrap();
+        let weapons = format!("{}\n{}", BUILTIN_WEAPONS, &BUILTIN_WEAPONS[first..second]);
+        expect_error(
+            load_with(WEAPONS_FILE, &weapons),
+            "weapons.toml: duplicate id 'rapid_fire'",
+        );
+    }
+
+    #[test]
+    fn dangling_references_are_rejected() {
+        let ships = BUILTIN_SHIPS.replacen(
+            "primary_weapon = \"rapid_fire\"",
+            "primary_weapon = \"railgun\"",
+            1,
+        );
+        expect_error(
+            load_with(SHIPS_FILE, &ships),
+            "primary_weapon 'railgun' is not a defined weapon",
+        );
+
+        let waves = BUILTIN_WAVES.replacen("enemy = \"chaser\"", "enemy = \"lurker\"", 1);
+        expect_error(
+            load_with(WAVES_FILE, &waves),
+            "waves.toml: wave 1: spawn enemy 'lurker' is not a defined enemy",
+        );
+
+        let waves = format!(
+            "{}\n[[wave]]\nnumber = 99\nduration = 60.0\nspawn_interval = 5.0\nspawns = []\nboss = \"mothership\"\n",
+            BUILTIN_WAVES
+        );
+        expect_error(
+            load_with(WAVES_FILE, &waves),
+            "waves.toml: wave 99: boss 'mothership' is not a defined enemy",
+        );
+
+        let enemies =
+            BUILTIN_ENEMIES.replacen("drop_table = \"common\"", "drop_table = \"rare\"", 1);
+        expect_error(
+            load_with(ENEMIES_FILE, &enemies),
+            "drop_table 'rare' is not a defined drop table",
+        );
+    }
+
+    #[test]
+    fn non_positive_values_are_rejected() {
+        let ships = BUILTIN_SHIPS.replacen("max_speed = ", "max_speed = -", 1);
+        expect_error(load_with(SHIPS_FILE, &ships), "max_speed must be positive");
+
+        let weapons = BUILTIN_WEAPONS.replacen("cooldown = 0.2", "cooldown = 0.0", 1);
+        expect_error(
+            load_with(WEAPONS_FILE, &weapons),
+            "weapons.toml: weapon 'rapid_fire': cooldown must be positive (got 0)",
+        );
+
+        let mut errors = Vec::new();
+        check_positive(&mut errors, "ctx", "nan", f32::NAN);
+        check_positive(&mut errors, "ctx", "infinite", f32::INFINITY);
+        check_positive(&mut errors, "ctx", "valid", 0.5);
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+    }
+
+    #[test]
+    fn weapon_kind_tuning_is_checked() {
+        let errors = validate_weapon_kind(WeaponType::RapidFire {
+            rate: 0.0,
+            damage: 10.0,
+            speed: -1.0,
+        });
+        assert_eq!(
+            errors,
+            vec![
+                "weapon: kind.rate must be positive (got 0)",
+                "weapon: kind.speed must be positive (got -1)",
+            ]
+        );
+
+        let errors = validate_weapon_kind(WeaponType::Spread {
+            count: 0,
+            spread_angle: -0.5,
+            damage: 10.0,
+            speed: 100.0,
+        });
+        assert_eq!(
+            errors,
+            vec![
+                "weapon: kind.count must be at least 1",
+                "weapon: kind.spread_angle must not be negative (got -0.5)",
+            ]
+        );
+
+        let errors = validate_weapon_kind(WeaponType::Beam {
+            damage_per_second: 30.0,
+            range: 0.0,
+        });
+        assert_eq!(errors, vec!["weapon: kind.range must be positive (got 0)"]);
+
+        let errors = validate_weapon_kind(WeaponType::Homing {
+            damage: 10.0,
+            speed: 100.0,
+            turn_rate: 0.0,
+        });
+        assert_eq!(
+            errors,
+            vec!["weapon: kind.turn_rate must be positive (got 0)"]
+        );
+
+        let errors = validate_weapon_kind(WeaponType::AreaNuke {
+            damage: -5.0,
+            radius: 200.0,
+        });
+        assert_eq!(
+            errors,
+            vec!["weapon: kind.damage must be positive (got -5)"]
+        );
+
+        let errors = validate_weapon_kind(WeaponType::Spread {
+            count: 3,
+            spread_angle: 0.0,
+            damage: 10.0,
+            speed: 100.0,
+        });
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+}
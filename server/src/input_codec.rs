@@ -0,0 +1,194 @@
+//! Compact client input packets
+//!
+//! Each packet carries up to [`MAX_INPUTS_PER_PACKET`] inputs: the client's
+//! newest input followed by the ones it sent before, newest first, so a lost
+//! packet is covered by the next one. Older inputs are stored as
+//! sequence and timestamp deltas from the newest. Thrust and turn use 8 bits
+//! each, scaled so 0, ±0.5 and ±1 survive exactly; fire buttons are single bits.
+//! The server drops inputs it has already seen via [`InputBuffer`].
+//!
+//! [`InputBuffer`]: crate::components::InputBuffer
+
+#![allow(dead_code)] // Clients encode; the server encoder serves tests and bots
+
+use crate::bitpack::{BitReader, BitWriter};
+use crate::components::InputData;
+
+/// Most inputs one packet can bundle
+pub const MAX_INPUTS_PER_PACKET: usize = 15;
+
+const COUNT_BITS: u32 = 4;
+const SEQUENCE_BITS: u32 = 32;
+const SEQUENCE_DELTA_BITS: u32 = 8;
+const TIMESTAMP_LOW_BITS: u32 = 32;
+const TIMESTAMP_HIGH_BITS: u32 = 16; // 48-bit unix millis
+const TIMESTAMP_DELTA_BITS: u32 = 16;
+const AXIS_BITS: u32 = 8;
+const AXIS_SCALE: f32 = 126.0; // even, so half values are exact
+
+fn encode_axis(value: f32) -> u32 {
+    ((value.clamp(-1.0, 1.0) * AXIS_SCALE).round() + AXIS_SCALE) as u32
+}
+
+fn decode_axis(value: u32) -> Result<f32, String> {
+    if value > 2 * AXIS_SCALE as u32 {
+        return Err(format!("Axis value {} out of range", value));
+    }
+    Ok((value as f32 - AXIS_SCALE) / AXIS_SCALE)
+}
+
+/// Pack inputs, newest first; anything past the packet limit or too old to delta-encode is left out
+pub fn encode(inputs: &[InputData]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    let Some(newest) = inputs.first() else {
+        writer.write(0, COUNT_BITS);
+        return writer.finish();
+    };
+
+    let bundled: Vec<&InputData> = inputs
+        .iter()
+        .take(MAX_INPUTS_PER_PACKET)
+        .take_while(|input| {
+            newest.sequence.wrapping_sub(input.sequence) < (1 << SEQUENCE_DELTA_BITS)
+        })
+        .collect();
+
+    writer.write(bundled.len() as u32, COUNT_BITS);
+    writer.write(newest.sequence, SEQUENCE_BITS);
+    writer.write(newest.timestamp as u32, TIMESTAMP_LOW_BITS);
+    writer.write((newest.timestamp >> 32) as u32, TIMESTAMP_HIGH_BITS);
+
+    for (i, input) in bundled.iter().enumerate() {
+        if i > 0 {
+            writer.write(
+                newest.sequence.wrapping_sub(input.sequence),
+                SEQUENCE_DELTA_BITS,
+            );
+            let age = newest.timestamp.saturating_sub(input.timestamp);
+            writer.write(
+                age.min((1 << TIMESTAMP_DELTA_BITS) - 1) as u32,
+                TIMESTAMP_DELTA_BITS,
+            );
+        }
+        writer.write(encode_axis(input.thrust), AXIS_BITS);
+        writer.write(encode_axis(input.turn), AXIS_BITS);
+        writer.write_bool(input.primary_fire);
+        writer.write_bool(input.secondary_fire);
+    }
+
+    writer.finish()
+}
+
+/// Unpack a packet into its inputs, oldest first
+pub fn decode(data: &[u8]) -> Result<Vec<InputData>, String> {
+    let mut reader = BitReader::new(data);
+    let count = reader.read(COUNT_BITS)? as usize;
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+
+    let sequence = reader.read(SEQUENCE_BITS)?;
+    let timestamp =
+        reader.read(TIMESTAMP_LOW_BITS)? as u64 | (reader.read(TIMESTAMP_HIGH_BITS)? as u64) << 32;
+
+    let mut inputs = Vec::with_capacity(count);
+    for i in 0..count {
+        let (sequence, timestamp) = if i == 0 {
+            (sequence, timestamp)
+        } else {
+            let sequence_delta = reader.read(SEQUENCE_DELTA_BITS)?;
+            let age = reader.read(TIMESTAMP_DELTA_BITS)? as u64;
+            (
+                sequence.wrapping_sub(sequence_delta),
+                timestamp.saturating_sub(age),
+            )
+        };
+        inputs.push(InputData {
+            sequence,
+            timestamp,
+            thrust: decode_axis(reader.read(AXIS_BITS)?)?,
+            turn: decode_axis(reader.read(AXIS_BITS)?)?,
+            primary_fire: reader.read_bool()?,
+            secondary_fire: reader.read_bool()?,
+        });
+    }
+
+    inputs.reverse();
+    Ok(inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::InputBuffer;
+
+    const EPOCH_MS: u64 = 1_700_000_000_000;
+
+    fn input(sequence: u32, thrust: f32, turn: f32, primary: bool, secondary: bool) -> InputData {
+        InputData {
+            sequence,
+            timestamp: EPOCH_MS + sequence as u64 * 66,
+            thrust,
+            turn,
+            primary_fire: primary,
+            secondary_fire: secondary,
+        }
+    }
+
+    /// A client's resend bundle, newest first, arriving out of order and with a repeat
+    fn bundle() -> Vec<InputData> {
+        vec![
+            input(10, 1.0, -0.5, true, false),
+            input(8, 0.5, 0.0, false, false),
+            input(9, 0.0, 1.0, false, true),
+            input(8, 0.5, 0.0, false, false),
+        ]
+    }
+
+    /// `encodeInputs` in client/src/inputCodec.ts produces these bytes for `bundle()`
+    const CLIENT_PACKET: [u8; 29] = [
+        0xa4, 0x00, 0x00, 0x00, 0x40, 0xa9, 0x56, 0xfe, 0xbc, 0x18, 0xc0, 0xff, 0x93, 0x00, 0x21,
+        0x40, 0xaf, 0x1f, 0x01, 0x42, 0x00, 0x7e, 0xfc, 0x0a, 0x10, 0x02, 0xf4, 0xfa, 0x01,
+    ];
+
+    #[test]
+    fn encoding_matches_client_layout() {
+        assert_eq!(encode(&bundle()), CLIENT_PACKET);
+    }
+
+    #[test]
+    fn client_packet_decodes_oldest_first() {
+        let mut expected = bundle();
+        expected.reverse();
+        assert_eq!(decode(&CLIENT_PACKET).unwrap(), expected);
+    }
+
+    #[test]
+    fn buffer_keeps_each_sequence_once_in_order() {
+        let mut buffer = InputBuffer::default();
+        let accepted = decode(&CLIENT_PACKET)
+            .unwrap()
+            .into_iter()
+            .filter(|input| buffer.add_input(input.clone()))
+            .count();
+        assert_eq!(accepted, 3);
+
+        let sequences: Vec<u32> = std::iter::from_fn(|| buffer.get_next_input())
+            .map(|input| input.sequence)
+            .collect();
+        assert_eq!(sequences, [8, 9, 10]);
+
+        // The next packet repeats all of them alongside one new input
+        let mut next = bundle();
+        next.insert(0, input(11, 0.0, 0.0, false, false));
+        for input in decode(&encode(&next)).unwrap() {
+            let fresh = input.sequence == 11;
+            assert_eq!(buffer.add_input(input), fresh);
+        }
+    }
+
+    #[test]
+    fn truncated_packet_is_rejected() {
+        assert!(decode(&CLIENT_PACKET[..CLIENT_PACKET.len() - 2]).is_err());
+    }
+}
@@ -0,0 +1,549 @@
+//! Interest management
+//!
+//! Splits the room-wide snapshot into one snapshot per player, keeping only
+//! the entities near that player's ship:
+//! - Entities within the near radius are sent every snapshot
+//! - Entities within the far radius are sent every [`FAR_INTERVAL`] snapshots
+//! - Entities a player has not been sent yet, such as fresh spawns, are sent
+//!   straight away at any distance inside the far radius
+//! - Players and entities marked always relevant (bosses) are always sent
+//!
+//! Both radii are fractions of the arena diagonal ([`InterestRadii`]), so
+//! larger arenas filter more. Entities are bucketed into a [`SpatialGrid`]
+//! once per tick so each player only looks at the cells around their ship.
+//! Relevant entities that are skipped this snapshot are listed in `deferred`
+//! so clients keep showing them.
+//!
+//! Gameplay events are filtered the same way ([`events_for`]): a player hears
+//! about entities their client knows, things happening inside their far
+//! radius and anything they did themselves.
+
+use crate::simulation::{EntitySnapshot, GameBounds, GameEvent, GameSnapshot};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// Near radius as a fraction of the arena diagonal
+const NEAR_FRACTION: f32 = 0.25;
+
+/// Far radius as a fraction of the arena diagonal; in the default 1920x1080
+/// arena this is about 1430 units, just past the corner of the client's view
+const FAR_FRACTION: f32 = 0.65;
+
+/// Distant entities are sent once per this many snapshots
+pub const FAR_INTERVAL: u64 = 3;
+
+/// Most entities in one player's snapshot; the nearest win
+pub const MAX_ENTITIES: usize = 256;
+
+/// Distances that decide how often an entity is sent to a player
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InterestRadii {
+    /// Entities closer than this are updated every snapshot
+    pub near: f32,
+    /// Entities beyond this are not sent at all
+    pub far: f32,
+}
+
+impl InterestRadii {
+    pub fn for_bounds(bounds: &GameBounds) -> Self {
+        let diagonal = bounds.width.hypot(bounds.height);
+        Self {
+            near: diagonal * NEAR_FRACTION,
+            far: diagonal * FAR_FRACTION,
+        }
+    }
+}
+
+/// Grid cell size, chosen so a near-radius query touches only a few cells
+const CELL_SIZE: f32 = 600.0;
+
+/// Uniform grid of entity indices keyed by cell
+#[derive(Debug, Default)]
+pub struct SpatialGrid {
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialGrid {
+    fn cell(position: [f32; 2]) -> (i32, i32) {
+        (
+            (position[0] / CELL_SIZE).floor() as i32,
+            (position[1] / CELL_SIZE).floor() as i32,
+        )
+    }
+
+    pub fn insert(&mut self, position: [f32; 2], index: usize) {
+        self.cells
+            .entry(Self::cell(position))
+            .or_default()
+            .push(index);
+    }
+
+    /// Indices in every cell overlapping the square around `center`
+    pub fn query(&self, center: [f32; 2], radius: f32) -> impl Iterator<Item = usize> + '_ {
+        let (min_x, min_y) = Self::cell([center[0] - radius, center[1] - radius]);
+        let (max_x, max_y) = Self::cell([center[0] + radius, center[1] + radius]);
+        (min_x..=max_x)
+            .flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+}
+
+/// A player receiving snapshots and where their ship is
+#[derive(Debug, Clone)]
+pub struct Viewer {
+    pub player_id: Uuid,
+    pub entity_id: u64,
+    pub position: [f32; 2],
+}
+
+/// Entities each player's client currently knows about: those sent to it
+/// or deferred in its last snapshot
+#[derive(Debug, Default)]
+pub struct InterestState {
+    known: HashMap<Uuid, HashSet<u64>>,
+}
+
+/// Build each viewer's snapshot from the full room snapshot
+pub fn partition(
+    snapshot: &GameSnapshot,
+    viewers: &[Viewer],
+    always_relevant: &HashSet<u64>,
+    radii: InterestRadii,
+    state: &mut InterestState,
+) -> HashMap<Uuid, GameSnapshot> {
+    let mut grid = SpatialGrid::default();
+    for (index, entity) in snapshot.entities.iter().enumerate() {
+        if !always_relevant.contains(&entity.entity_id) {
+            grid.insert(entity.transform.position, index);
+        }
+    }
+
+    // Players who left or froze start over when they come back
+    state
+        .known
+        .retain(|player_id, _| viewers.iter().any(|viewer| viewer.player_id == *player_id));
+
+    viewers
+        .iter()
+        .map(|viewer| {
+            let known = state.known.entry(viewer.player_id).or_default();
+            let partitioned = snapshot_for(snapshot, viewer, &grid, always_relevant, radii, known);
+            (viewer.player_id, partitioned)
+        })
+        .collect()
+}
+
+fn snapshot_for(
+    snapshot: &GameSnapshot,
+    viewer: &Viewer,
+    grid: &SpatialGrid,
+    always_relevant: &HashSet<u64>,
+    radii: InterestRadii,
+    known: &mut HashSet<u64>,
+) -> GameSnapshot {
+    let mut entities: Vec<EntitySnapshot> = snapshot
+        .entities
+        .iter()
+        .filter(|entity| {
+            entity.entity_id == viewer.entity_id || always_relevant.contains(&entity.entity_id)
+        })
+        .cloned()
+        .collect();
+    let mut deferred = Vec::new();
+
+    // Nearest first, so the entity cap drops the least important ones
+    let mut nearby: Vec<(f32, &EntitySnapshot)> = grid
+        .query(viewer.position, radii.far)
+        .map(|index| &snapshot.entities[index])
+        .filter(|entity| entity.entity_id != viewer.entity_id)
+        .map(|entity| (distance(viewer.position, entity.transform.position), entity))
+        .filter(|(distance, _)| *distance <= radii.far)
+        .collect();
+    nearby.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut now_known = HashSet::with_capacity(nearby.len());
+    for (distance, entity) in nearby {
+        // Spread distant updates across ticks instead of bunching them
+        let due = distance <= radii.near
+            || !known.contains(&entity.entity_id)
+            || (snapshot.tick + entity.entity_id).is_multiple_of(FAR_INTERVAL);
+        if due && entities.len() < MAX_ENTITIES {
+            entities.push(entity.clone());
+        } else {
+            deferred.push(entity.entity_id);
+            if known.contains(&entity.entity_id) {
+                now_known.insert(entity.entity_id);
+            }
+        }
+    }
+    now_known.extend(entities.iter().map(|entity| entity.entity_id));
+    *known = now_known;
+
+    GameSnapshot {
+        sequence: snapshot.sequence,
+        tick: snapshot.tick,
+        timestamp: snapshot.timestamp,
+        entities,
+        deferred,
+    }
+}
+
+/// Split a tick's events between viewers; call before [`partition`] moves the
+/// interest state on, so viewers still hear about entities that just left
+pub fn events_for(
+    events: &[GameEvent],
+    viewers: &[Viewer],
+    always_relevant: &HashSet<u64>,
+    radii: InterestRadii,
+    state: &InterestState,
+) -> HashMap<Uuid, Vec<GameEvent>> {
+    let no_entities = HashSet::new();
+    viewers
+        .iter()
+        .filter_map(|viewer| {
+            let known = state.known.get(&viewer.player_id).unwrap_or(&no_entities);
+            let relevant: Vec<GameEvent> = events
+                .iter()
+                .filter(|event| event_is_relevant(event, viewer, always_relevant, radii, known))
+                .cloned()
+                .collect();
+            (!relevant.is_empty()).then_some((viewer.player_id, relevant))
+        })
+        .collect()
+}
+
+fn event_is_relevant(
+    event: &GameEvent,
+    viewer: &Viewer,
+    always_relevant: &HashSet<u64>,
+    radii: InterestRadii,
+    known: &HashSet<u64>,
+) -> bool {
+    let knows = |entity_id: &u64| {
+        *entity_id == viewer.entity_id
+            || always_relevant.contains(entity_id)
+            || known.contains(entity_id)
+    };
+    let within = |position: &[f32; 2], margin: f32| {
+        distance(viewer.position, *position) <= radii.far + margin
+    };
+
+    match event {
+        GameEvent::Spawned {
+            entity_id,
+            position,
+            ..
+        } => knows(entity_id) || within(position, 0.0),
+        GameEvent::Despawned { entity_id, .. } => knows(entity_id),
+        GameEvent::Hit {
+            entity_id,
+            shooter_id,
+            position,
+            ..
+        } => *shooter_id == viewer.player_id || knows(entity_id) || within(position, 0.0),
+        GameEvent::Killed {
+            entity_id,
+            killer_id,
+        } => *killer_id == viewer.player_id || knows(entity_id),
+        GameEvent::Pickup {
+            entity_id,
+            player_id,
+            ..
+        } => *player_id == viewer.player_id || knows(entity_id),
+        GameEvent::Explosion { position, radius } => within(position, *radius),
+    }
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{Health, Transform, Velocity};
+    use crate::simulation::{DespawnReason, EntityType};
+
+    const RADII: InterestRadii = InterestRadii {
+        near: 500.0,
+        far: 1000.0,
+    };
+
+    fn entity(entity_id: u64, position: [f32; 2]) -> EntitySnapshot {
+        EntitySnapshot {
+            entity_id,
+            entity_type: EntityType::Enemy,
+            transform: Transform {
+                position,
+                ..Transform::default()
+            },
+            velocity: Velocity::default(),
+            health: Health::default(),
+            ship: None,
+        }
+    }
+
+    fn snapshot(tick: u64, entities: Vec<EntitySnapshot>) -> GameSnapshot {
+        GameSnapshot {
+            sequence: tick as u32,
+            tick,
+            timestamp: 0,
+            entities,
+            deferred: Vec::new(),
+        }
+    }
+
+    fn viewer(player_id: Uuid) -> Viewer {
+        Viewer {
+            player_id,
+            entity_id: 1,
+            position: [0.0, 0.0],
+        }
+    }
+
+    fn sent(snapshot: &GameSnapshot) -> Vec<u64> {
+        let mut ids: Vec<u64> = snapshot.entities.iter().map(|e| e.entity_id).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn grid_query_covers_cells_around_center() {
+        let mut grid = SpatialGrid::default();
+        grid.insert([10.0, 10.0], 0);
+        grid.insert([-590.0, 10.0], 1); // neighbouring cell
+        grid.insert([5000.0, 5000.0], 2);
+
+        let mut found: Vec<usize> = grid.query([0.0, 0.0], 100.0).collect();
+        found.sort();
+        assert_eq!(found, vec![0, 1]);
+        assert_eq!(
+            grid.query([5000.0, 5000.0], 1.0).collect::<Vec<_>>(),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn radii_scale_with_the_arena() {
+        let small = InterestRadii::for_bounds(&GameBounds::default());
+        let large = InterestRadii::for_bounds(&GameBounds {
+            width: 3840.0,
+            height: 2160.0,
+            ..GameBounds::default()
+        });
+        assert!(small.far < 1920f32.hypot(1080.0));
+        assert_eq!(large.near, small.near * 2.0);
+        assert_eq!(large.far, small.far * 2.0);
+    }
+
+    #[test]
+    fn leaves_out_entities_beyond_far_radius() {
+        let player_id = Uuid::new_v4();
+        let full = snapshot(
+            0,
+            vec![
+                entity(1, [0.0, 0.0]),
+                entity(2, [100.0, 0.0]),
+                entity(3, [1500.0, 0.0]),
+            ],
+        );
+        let mut state = InterestState::default();
+        let partitioned = partition(
+            &full,
+            &[viewer(player_id)],
+            &HashSet::new(),
+            RADII,
+            &mut state,
+        );
+
+        assert_eq!(sent(&partitioned[&player_id]), vec![1, 2]);
+        assert!(partitioned[&player_id].deferred.is_empty());
+    }
+
+    #[test]
+    fn always_relevant_entities_are_sent_at_any_range() {
+        let player_id = Uuid::new_v4();
+        let teammate = entity(7, [20_000.0, 0.0]);
+        let full = snapshot(0, vec![entity(1, [0.0, 0.0]), teammate]);
+        let mut state = InterestState::default();
+        let partitioned = partition(
+            &full,
+            &[viewer(player_id)],
+            &HashSet::from([7]),
+            RADII,
+            &mut state,
+        );
+
+        assert_eq!(sent(&partitioned[&player_id]), vec![1, 7]);
+    }
+
+    #[test]
+    fn far_entities_are_sent_once_per_interval_after_first_sight() {
+        let player_id = Uuid::new_v4();
+        let viewers = [viewer(player_id)];
+        let mut state = InterestState::default();
+        let far = || vec![entity(1, [0.0, 0.0]), entity(9, [800.0, 0.0])];
+
+        // Never sent before, so it goes out on the first tick whatever the interval
+        let first = partition(
+            &snapshot(1, far()),
+            &viewers,
+            &HashSet::new(),
+            RADII,
+            &mut state,
+        );
+        assert_eq!(sent(&first[&player_id]), vec![1, 9]);
+
+        let mut sent_ticks = Vec::new();
+        for tick in 2..2 + FAR_INTERVAL * 2 {
+            let partitioned = partition(
+                &snapshot(tick, far()),
+                &viewers,
+                &HashSet::new(),
+                RADII,
+                &mut state,
+            );
+            let snapshot = &partitioned[&player_id];
+            if snapshot.entities.iter().any(|e| e.entity_id == 9) {
+                sent_ticks.push(tick);
+            } else {
+                assert_eq!(snapshot.deferred, vec![9]);
+            }
+        }
+        assert_eq!(sent_ticks.len(), 2);
+        assert_eq!(sent_ticks[1] - sent_ticks[0], FAR_INTERVAL);
+    }
+
+    #[test]
+    fn entities_back_in_range_are_sent_straight_away() {
+        let player_id = Uuid::new_v4();
+        let viewers = [viewer(player_id)];
+        let mut state = InterestState::default();
+        let at = |x: f32| vec![entity(1, [0.0, 0.0]), entity(9, [x, 0.0])];
+
+        partition(
+            &snapshot(1, at(800.0)),
+            &viewers,
+            &HashSet::new(),
+            RADII,
+            &mut state,
+        );
+        partition(
+            &snapshot(2, at(5000.0)),
+            &viewers,
+            &HashSet::new(),
+            RADII,
+            &mut state,
+        );
+
+        // Tick 4 is not due for entity 9, but the client forgot it while it was away
+        assert!(!(4 + 9u64).is_multiple_of(FAR_INTERVAL));
+        let back = partition(
+            &snapshot(4, at(800.0)),
+            &viewers,
+            &HashSet::new(),
+            RADII,
+            &mut state,
+        );
+        assert_eq!(sent(&back[&player_id]), vec![1, 9]);
+    }
+
+    fn hit(entity_id: u64, shooter_id: Uuid, position: [f32; 2]) -> GameEvent {
+        GameEvent::Hit {
+            entity_id,
+            shooter_id,
+            damage: 10.0,
+            position,
+        }
+    }
+
+    fn heard(events: &HashMap<Uuid, Vec<GameEvent>>, player_id: Uuid) -> Vec<String> {
+        events
+            .get(&player_id)
+            .into_iter()
+            .flatten()
+            .map(|event| format!("{:?}", event))
+            .collect()
+    }
+
+    #[test]
+    fn events_reach_only_viewers_they_concern() {
+        let near_id = Uuid::new_v4();
+        let far_id = Uuid::new_v4();
+        let viewers = [
+            viewer(near_id),
+            Viewer {
+                player_id: far_id,
+                entity_id: 2,
+                position: [10_000.0, 0.0],
+            },
+        ];
+        let stranger = Uuid::new_v4();
+        let events = [
+            hit(9, stranger, [300.0, 0.0]),
+            hit(9, far_id, [300.0, 0.0]),
+            GameEvent::Explosion {
+                position: [10_000.0, 1_500.0],
+                radius: 600.0,
+            },
+            GameEvent::Killed {
+                entity_id: 2,
+                killer_id: stranger,
+            },
+        ];
+
+        let state = InterestState::default();
+        let split = events_for(&events, &viewers, &HashSet::new(), RADII, &state);
+
+        // Both hits land nearby; the kill is of a ship this viewer has never seen
+        assert_eq!(
+            heard(&split, near_id),
+            vec![format!("{:?}", events[0]), format!("{:?}", events[1])]
+        );
+        assert_eq!(
+            heard(&split, far_id),
+            vec![
+                format!("{:?}", events[1]),
+                format!("{:?}", events[2]),
+                format!("{:?}", events[3]),
+            ]
+        );
+
+        // Ships are always relevant, so everyone hears about the kill
+        let split = events_for(&events, &viewers, &HashSet::from([1, 2]), RADII, &state);
+        assert_eq!(heard(&split, near_id).len(), 3);
+    }
+
+    #[test]
+    fn viewers_hear_about_known_entities_leaving() {
+        let player_id = Uuid::new_v4();
+        let viewers = [viewer(player_id)];
+        let mut state = InterestState::default();
+        partition(
+            &snapshot(1, vec![entity(1, [0.0, 0.0]), entity(9, [800.0, 0.0])]),
+            &viewers,
+            &HashSet::new(),
+            RADII,
+            &mut state,
+        );
+
+        let events = [
+            GameEvent::Despawned {
+                entity_id: 9,
+                reason: DespawnReason::Destroyed,
+            },
+            GameEvent::Despawned {
+                entity_id: 12,
+                reason: DespawnReason::Expired,
+            },
+        ];
+        let split = events_for(&events, &viewers, &HashSet::new(), RADII, &state);
+        assert_eq!(heard(&split, player_id), vec![format!("{:?}", events[0])]);
+
+        // Nothing to hear means no message at all
+        let split = events_for(&events[1..], &viewers, &HashSet::new(), RADII, &state);
+        assert!(split.is_empty());
+    }
+}
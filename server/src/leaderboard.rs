@@ -0,0 +1,467 @@
+//! Global high score leaderboard
+//!
+//! Every finished match score is stored in redb alongside player profiles, so
+//! the leaderboard spans all rooms and survives server restarts. Scores are
+//! keyed by the time they were recorded, and each one is also written to a
+//! rank index ordered by (period, ship class, score descending) for both its
+//! all-time and its weekly board, with and without the class filter. Queries
+//! read a page straight out of the index instead of sorting every score.
+//! Weekly boards cover calendar weeks starting Monday 00:00 UTC.
+
+use redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::info;
+
+/// Scores table: (recorded_at unix millis, sequence) -> JSON encoded ScoreEntry
+const SCORES: TableDefinition<(u64, u64), &[u8]> = TableDefinition::new("scores");
+
+/// Rank index: (period, ship class or "" for every class, u32::MAX - score,
+/// recorded_at, sequence), where the last two locate the row in SCORES
+const RANKS: TableDefinition<(u64, &str, u32, u64, u64), ()> = TableDefinition::new("score_ranks");
+
+/// Entries per board: (period, ship class or "") -> count
+const BOARD_SIZES: TableDefinition<(u64, &str), u64> = TableDefinition::new("score_board_sizes");
+
+/// Counters that must survive restarts
+const META: TableDefinition<&str, u64> = TableDefinition::new("leaderboard_meta");
+const NEXT_SEQUENCE: &str = "next_sequence";
+
+/// Period key of the all-time board; weekly boards use their week number + 1
+const ALL_TIME: u64 = 0;
+
+const WEEK: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// The unix epoch fell on a Thursday; shift so weeks start on Monday
+const WEEK_START_OFFSET: Duration = Duration::from_secs(3 * 24 * 60 * 60);
+
+/// One player's final score from one match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct ScoreEntry {
+    pub name: String,
+    pub score: u32,
+    pub kills: u32,
+    pub ship_class: String,
+    pub recorded_at: u64, // unix millis
+}
+
+/// Time window a leaderboard query covers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardPeriod {
+    #[default]
+    AllTime,
+    Weekly,
+}
+
+/// Filters and pagination for a leaderboard query
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LeaderboardQuery {
+    #[serde(default)]
+    pub period: LeaderboardPeriod,
+    pub ship_class: Option<String>,
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+/// A ranked page of the leaderboard
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct LeaderboardPage {
+    pub period: LeaderboardPeriod,
+    pub ship_class: Option<String>,
+    pub total: usize,
+    pub offset: usize,
+    pub entries: Vec<RankedScore>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct RankedScore {
+    pub rank: usize,
+    #[serde(flatten)]
+    pub entry: ScoreEntry,
+}
+
+/// Default and maximum page sizes for queries
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Period key of the weekly board covering `at` (unix millis)
+fn week_period(at: u64) -> u64 {
+    (at + WEEK_START_OFFSET.as_millis() as u64) / WEEK.as_millis() as u64 + 1
+}
+
+impl LeaderboardPeriod {
+    fn key(self, now: u64) -> u64 {
+        match self {
+            LeaderboardPeriod::AllTime => ALL_TIME,
+            LeaderboardPeriod::Weekly => week_period(now),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Leaderboard {
+    db: Arc<Database>,
+    best_score: Arc<Mutex<Option<u32>>>, // cached all-time top score
+}
+
+impl Leaderboard {
+    /// Wrap a database, creating the tables if needed and indexing scores
+    /// stored before the rank index existed
+    pub fn open(db: Arc<Database>) -> Result<Self, String> {
+        let txn = db.begin_write().map_err(|e| e.to_string())?;
+        {
+            let scores = txn.open_table(SCORES).map_err(|e| e.to_string())?;
+            let mut ranks = txn.open_table(RANKS).map_err(|e| e.to_string())?;
+            let mut sizes = txn.open_table(BOARD_SIZES).map_err(|e| e.to_string())?;
+            let mut meta = txn.open_table(META).map_err(|e| e.to_string())?;
+
+            let unindexed = ranks.is_empty().map_err(|e| e.to_string())?
+                && !scores.is_empty().map_err(|e| e.to_string())?;
+            if unindexed {
+                let mut next_sequence = 0;
+                let mut count = 0;
+                for row in scores.iter().map_err(|e| e.to_string())? {
+                    let (key, value) = row.map_err(|e| e.to_string())?;
+                    let (recorded_at, sequence) = key.value();
+                    next_sequence = next_sequence.max(sequence + 1);
+                    if let Ok(entry) = serde_json::from_slice::<ScoreEntry>(value.value()) {
+                        index_score(&mut ranks, &mut sizes, &entry, recorded_at, sequence)?;
+                        count += 1;
+                    }
+                }
+                meta.insert(NEXT_SEQUENCE, next_sequence)
+                    .map_err(|e| e.to_string())?;
+                info!("Indexed {} leaderboard scores", count);
+            }
+        }
+        txn.commit().map_err(|e| e.to_string())?;
+
+        let leaderboard = Self {
+            db,
+            best_score: Arc::new(Mutex::new(None)),
+        };
+        *leaderboard.best_score.lock().unwrap() = leaderboard
+            .query(&LeaderboardQuery {
+                limit: Some(1),
+                ..LeaderboardQuery::default()
+            })?
+            .entries
+            .first()
+            .map(|ranked| ranked.entry.score);
+        Ok(leaderboard)
+    }
+
+    /// Store a final match score; returns true if it beats every recorded score
+    pub fn record(
+        &self,
+        name: &str,
+        score: u32,
+        kills: u32,
+        ship_class: &str,
+    ) -> Result<bool, String> {
+        let entry = ScoreEntry {
+            name: name.to_string(),
+            score,
+            kills,
+            ship_class: ship_class.to_string(),
+            recorded_at: unix_millis(),
+        };
+        let bytes = serde_json::to_vec(&entry).map_err(|e| e.to_string())?;
+        let txn = self.db.begin_write().map_err(|e| e.to_string())?;
+        {
+            let mut meta = txn.open_table(META).map_err(|e| e.to_string())?;
+            let sequence = meta
+                .get(NEXT_SEQUENCE)
+                .map_err(|e| e.to_string())?
+                .map(|value| value.value())
+                .unwrap_or(0);
+            meta.insert(NEXT_SEQUENCE, sequence + 1)
+                .map_err(|e| e.to_string())?;
+
+            let mut scores = txn.open_table(SCORES).map_err(|e| e.to_string())?;
+            scores
+                .insert((entry.recorded_at, sequence), bytes.as_slice())
+                .map_err(|e| e.to_string())?;
+            let mut ranks = txn.open_table(RANKS).map_err(|e| e.to_string())?;
+            let mut sizes = txn.open_table(BOARD_SIZES).map_err(|e| e.to_string())?;
+            index_score(&mut ranks, &mut sizes, &entry, entry.recorded_at, sequence)?;
+        }
+        txn.commit().map_err(|e| e.to_string())?;
+
+        let mut best_score = self.best_score.lock().unwrap();
+        let is_record = best_score.is_none_or(|best| score > best);
+        if is_record {
+            *best_score = Some(score);
+        }
+        Ok(is_record)
+    }
+
+    /// Ranked, filtered and paginated scores
+    pub fn query(&self, query: &LeaderboardQuery) -> Result<LeaderboardPage, String> {
+        let period = query.period.key(unix_millis());
+        let class = query.ship_class.as_deref().unwrap_or("");
+        let offset = query.offset.unwrap_or(0);
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+
+        let txn = self.db.begin_read().map_err(|e| e.to_string())?;
+        let ranks = txn.open_table(RANKS).map_err(|e| e.to_string())?;
+        let sizes = txn.open_table(BOARD_SIZES).map_err(|e| e.to_string())?;
+        let scores = txn.open_table(SCORES).map_err(|e| e.to_string())?;
+
+        let total = sizes
+            .get((period, class))
+            .map_err(|e| e.to_string())?
+            .map(|value| value.value() as usize)
+            .unwrap_or(0);
+
+        // Highest score first; earlier scores win ties
+        let board = (period, class, 0, 0, 0)..=(period, class, u32::MAX, u64::MAX, u64::MAX);
+        let mut entries = Vec::new();
+        for (i, row) in ranks
+            .range(board)
+            .map_err(|e| e.to_string())?
+            .enumerate()
+            .skip(offset)
+            .take(limit)
+        {
+            let (key, _) = row.map_err(|e| e.to_string())?;
+            let (_, _, _, recorded_at, sequence) = key.value();
+            let Some(value) = scores
+                .get((recorded_at, sequence))
+                .map_err(|e| e.to_string())?
+            else {
+                continue;
+            };
+            if let Ok(entry) = serde_json::from_slice(value.value()) {
+                entries.push(RankedScore { rank: i + 1, entry });
+            }
+        }
+
+        Ok(LeaderboardPage {
+            period: query.period,
+            ship_class: query.ship_class.clone(),
+            total,
+            offset,
+            entries,
+        })
+    }
+}
+
+/// Add a score to the four boards it belongs to
+fn index_score(
+    ranks: &mut redb::Table<(u64, &str, u32, u64, u64), ()>,
+    sizes: &mut redb::Table<(u64, &str), u64>,
+    entry: &ScoreEntry,
+    recorded_at: u64,
+    sequence: u64,
+) -> Result<(), String> {
+    let rank = u32::MAX - entry.score;
+    let mut classes = vec![""];
+    if !entry.ship_class.is_empty() {
+        classes.push(entry.ship_class.as_str());
+    }
+    for period in [ALL_TIME, week_period(recorded_at)] {
+        for class in &classes {
+            let class = *class;
+            ranks
+                .insert((period, class, rank, recorded_at, sequence), ())
+                .map_err(|e| e.to_string())?;
+            let size = sizes
+                .get((period, class))
+                .map_err(|e| e.to_string())?
+                .map(|value| value.value())
+                .unwrap_or(0);
+            sizes
+                .insert((period, class), size + 1)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// 2024-01-01 00:00 UTC, a Monday
+    const MONDAY: u64 = 1_704_067_200_000;
+    const DAY: u64 = 24 * 60 * 60 * 1000;
+
+    /// A fresh database file that is unlinked once opened
+    fn temp_db() -> Arc<Database> {
+        let path = std::env::temp_dir().join(format!("leaderboard-{}.redb", uuid::Uuid::new_v4()));
+        let db = Database::create(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        Arc::new(db)
+    }
+
+    fn entry(name: &str, score: u32, ship_class: &str, recorded_at: u64) -> ScoreEntry {
+        ScoreEntry {
+            name: name.to_string(),
+            score,
+            kills: 0,
+            ship_class: ship_class.to_string(),
+            recorded_at,
+        }
+    }
+
+    /// Write scores the way servers did before the rank index existed
+    fn write_unindexed(db: &Database, rows: &[(u64, ScoreEntry)]) {
+        let txn = db.begin_write().unwrap();
+        {
+            let mut scores = txn.open_table(SCORES).unwrap();
+            for (sequence, entry) in rows {
+                let bytes = serde_json::to_vec(entry).unwrap();
+                scores
+                    .insert((entry.recorded_at, *sequence), bytes.as_slice())
+                    .unwrap();
+            }
+        }
+        txn.commit().unwrap();
+    }
+
+    fn names(page: &LeaderboardPage) -> Vec<&str> {
+        page.entries.iter().map(|r| r.entry.name.as_str()).collect()
+    }
+
+    fn query(period: LeaderboardPeriod, ship_class: Option<&str>) -> LeaderboardQuery {
+        LeaderboardQuery {
+            period,
+            ship_class: ship_class.map(str::to_string),
+            ..LeaderboardQuery::default()
+        }
+    }
+
+    #[test]
+    fn scores_rank_highest_first() {
+        let leaderboard = Leaderboard::open(temp_db()).unwrap();
+        assert!(leaderboard.record("low", 100, 1, "standard").unwrap());
+        assert!(leaderboard.record("high", 500, 5, "gunship").unwrap());
+        assert!(!leaderboard.record("mid", 300, 3, "standard").unwrap());
+        assert!(!leaderboard.record("zero", 0, 0, "standard").unwrap());
+        assert!(leaderboard.record("max", u32::MAX, 9, "tank").unwrap());
+
+        let page = leaderboard.query(&LeaderboardQuery::default()).unwrap();
+        assert_eq!(page.total, 5);
+        assert_eq!(names(&page), ["max", "high", "mid", "low", "zero"]);
+        let ranks: Vec<usize> = page.entries.iter().map(|r| r.rank).collect();
+        assert_eq!(ranks, [1, 2, 3, 4, 5]);
+
+        let page = leaderboard
+            .query(&query(LeaderboardPeriod::AllTime, Some("standard")))
+            .unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(names(&page), ["mid", "low", "zero"]);
+
+        let page = leaderboard
+            .query(&LeaderboardQuery {
+                offset: Some(1),
+                limit: Some(2),
+                ..LeaderboardQuery::default()
+            })
+            .unwrap();
+        assert_eq!(names(&page), ["high", "mid"]);
+        assert_eq!(page.entries[0].rank, 2);
+    }
+
+    #[test]
+    fn earlier_scores_win_ties() {
+        let db = temp_db();
+        write_unindexed(
+            &db,
+            &[
+                (3, entry("later", 200, "standard", MONDAY + 2_000)),
+                (1, entry("second", 200, "standard", MONDAY + 1_000)),
+                (0, entry("first", 200, "standard", MONDAY + 1_000)),
+                (2, entry("best", 300, "standard", MONDAY + 5_000)),
+            ],
+        );
+        let leaderboard = Leaderboard::open(db).unwrap();
+        leaderboard.record("newest", 200, 0, "standard").unwrap();
+
+        let page = leaderboard.query(&LeaderboardQuery::default()).unwrap();
+        assert_eq!(names(&page), ["best", "first", "second", "later", "newest"]);
+    }
+
+    #[test]
+    fn weekly_boards_roll_over_on_monday() {
+        let sunday_night = MONDAY - 1;
+        assert_ne!(week_period(sunday_night), week_period(MONDAY));
+        assert_eq!(week_period(sunday_night), week_period(MONDAY - 7 * DAY));
+        assert_eq!(week_period(MONDAY), week_period(MONDAY + 7 * DAY - 1));
+        assert_eq!(week_period(MONDAY) + 1, week_period(MONDAY + 7 * DAY));
+        assert_ne!(week_period(0), ALL_TIME);
+
+        // A score from last week only counts toward the all-time board
+        let this_week = week_period(unix_millis());
+        let last_week = unix_millis() - 7 * DAY;
+        assert_eq!(week_period(last_week) + 1, this_week);
+
+        let db = temp_db();
+        write_unindexed(&db, &[(0, entry("veteran", 900, "tank", last_week))]);
+        let leaderboard = Leaderboard::open(db).unwrap();
+        leaderboard.record("rookie", 50, 0, "tank").unwrap();
+
+        let all_time = leaderboard.query(&LeaderboardQuery::default()).unwrap();
+        assert_eq!(names(&all_time), ["veteran", "rookie"]);
+
+        let weekly = leaderboard
+            .query(&query(LeaderboardPeriod::Weekly, Some("tank")))
+            .unwrap();
+        assert_eq!(weekly.total, 1);
+        assert_eq!(names(&weekly), ["rookie"]);
+    }
+
+    #[test]
+    fn old_database_is_indexed_on_open() {
+        let db = temp_db();
+        write_unindexed(
+            &db,
+            &[
+                (0, entry("alpha", 100, "standard", MONDAY)),
+                (1, entry("bravo", 400, "gunship", MONDAY + DAY)),
+                (7, entry("charlie", 250, "", MONDAY + 2 * DAY)),
+            ],
+        );
+
+        let leaderboard = Leaderboard::open(db.clone()).unwrap();
+        let page = leaderboard.query(&LeaderboardQuery::default()).unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(names(&page), ["bravo", "charlie", "alpha"]);
+        let gunships = leaderboard
+            .query(&query(LeaderboardPeriod::AllTime, Some("gunship")))
+            .unwrap();
+        assert_eq!(names(&gunships), ["bravo"]);
+
+        // The cached best comes from the index, and new scores don't reuse a sequence
+        assert!(!leaderboard.record("delta", 400, 0, "standard").unwrap());
+        assert!(leaderboard.record("echo", 401, 0, "standard").unwrap());
+        let page = leaderboard.query(&LeaderboardQuery::default()).unwrap();
+        assert_eq!(page.total, 5);
+        assert_eq!(names(&page), ["echo", "bravo", "delta", "charlie", "alpha"]);
+
+        // Reopening an indexed database doesn't index its scores twice
+        drop(leaderboard);
+        let reopened = Leaderboard::open(db).unwrap();
+        assert_eq!(
+            reopened.query(&LeaderboardQuery::default()).unwrap().total,
+            5
+        );
+    }
+}
@@ -1,37 +1,70 @@
 use axum::{
     Router,
     extract::{
-        State,
-        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
     },
-    http::StatusCode,
+    http::{StatusCode, header},
     response::IntoResponse,
     routing::{get, post},
 };
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
     time::{Duration, Instant},
 };
-use tokio::{sync::broadcast, time};
+use tokio::{signal, task::JoinHandle, time};
 use tower_http::cors::{Any, CorsLayer};
-use tracing::{error, info, warn};
+use tracing::{Instrument, Span, debug, error, field, info, info_span, trace, warn};
 use uuid::Uuid;
 
+mod bitpack;
+mod checkpoints;
 mod components;
+mod content;
+mod input_codec;
+mod interest;
+mod leaderboard;
+mod metrics;
+mod outbound;
+mod profiles;
+mod protocol;
+mod replay;
+mod rewind;
 mod simulation;
-mod wire_format;
-
-use components::InputData;
+mod snapshot_codec;
+mod state_hash;
+mod timesync;
+mod world_save;
+
+use checkpoints::{CheckpointPlayer, CheckpointStore, RoomCheckpoint};
+use content::ContentRegistry;
+use leaderboard::{Leaderboard, LeaderboardQuery};
+use metrics::{METRICS, RoomMetrics, RoomSample};
+use outbound::{Outbound, OutboundSender};
+use profiles::{MatchResult, ProfileStore, ProfileWriter, validate_name};
+use protocol::{
+    ClientMessage, Encoding, ErrorCode, Frame, PROTOCOL_VERSION, SUPPORTED_FEATURES, ServerMessage,
+    WireData,
+};
 use rapier2d::prelude::Vector;
-use simulation::GameSimulation;
+use replay::{MAX_RECORDED_TICKS, ReplayStore};
+use simulation::{
+    DespawnReason, GameSimulation, InputOutcome, SIM_TICK_DURATION, SIM_TICK_RATE, SNAPSHOT_RATE,
+};
+use timesync::{LatencyEstimate, PROBE_INTERVAL, advance_server_time, server_time_ms};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RoomCode(String);
 
 impl RoomCode {
+    /// Codes aren't simulation state, so they come from the thread RNG rather than a room's seeded one
     pub fn generate() -> Self {
         // Generate 8-character alphanumeric room code
         let mut code = String::new();
@@ -50,109 +83,83 @@ impl RoomCode {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type")]
-pub enum ClientMessage {
-    Join {
-        room_code: String,
-        player_name: String,
-    },
-    Input {
-        sequence: u32,
-        timestamp: u64,
-        data: Vec<u8>,
-    },
-    Ping {
-        timestamp: u64,
-    },
-    RequestDebugRender {
-        timestamp: u64,
-    },
-    Leave,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type")]
-pub enum ServerMessage {
-    RoomJoined {
-        room_code: String,
-        player_id: String,
-        entity_id: u64, // Add the hecs entity ID
-    },
-    RoomCreated {
-        room_code: String,
-    },
-    PlayerJoined {
-        player_id: String,
-        player_name: String,
-    },
-    PlayerLeft {
-        player_id: String,
-    },
-    Snapshot {
-        sequence: u32,
-        timestamp: u64,
-        data: Vec<u8>,
-    },
-    Pong {
-        timestamp: u64,
-    },
-    DebugRender {
-        sequence: u32,
-        timestamp: u64,
-        data: Vec<u8>,
-    },
-    Error {
-        message: String,
-    },
-}
-
 #[derive(Debug)]
 pub struct Player {
     pub id: Uuid,
     pub name: String,
+    pub ship_class: String,
+    pub unlocked_ships: BTreeSet<String>, // unlocks from the player's profile
+    pub resume_token: String,
     pub last_seen: Instant,
-    pub sender: broadcast::Sender<ServerMessage>,
+    pub latency: LatencyEstimate, // measured by this connection's probes
+    pub sender: OutboundSender,
+}
+
+/// How long a dropped player's ship is held for them to reconnect
+const REJOIN_GRACE: Duration = Duration::from_secs(120);
+
+/// A player whose connection dropped, held until they resume or the grace window ends
+#[derive(Debug)]
+pub struct DisconnectedPlayer {
+    pub player: Player,
+    pub disconnected_at: Instant,
 }
 
 pub struct Room {
     pub code: RoomCode,
     pub players: HashMap<Uuid, Player>,
+    pub disconnected: HashMap<Uuid, DisconnectedPlayer>,
     pub created_at: Instant,
     pub last_activity: Instant,
     pub simulation: GameSimulation,
     pub player_entities: HashMap<Uuid, hecs::Entity>, // Map player IDs to their ship entities
+    pub metrics: RoomMetrics,
 }
 
 impl Default for Room {
     fn default() -> Self {
-        Self::new()
+        Self::new(Arc::new(ContentRegistry::default()), rand::random())
     }
 }
 
 impl Room {
-    pub fn new() -> Self {
+    pub fn new(content: Arc<ContentRegistry>, seed: u64) -> Self {
         let now = Instant::now();
         Self {
             code: RoomCode::generate(),
             players: HashMap::new(),
+            disconnected: HashMap::new(),
             created_at: now,
             last_activity: now,
-            simulation: GameSimulation::new(),
+            simulation: GameSimulation::with_seed(content, seed),
             player_entities: HashMap::new(),
+            metrics: RoomMetrics::default(),
         }
     }
 
     pub fn add_player(&mut self, player: Player) -> Result<(), String> {
-        if self.players.len() >= 10 {
+        if self.players.len() + self.disconnected.len() >= 10 {
             return Err("Room is full".to_string());
         }
 
+        match self.simulation.content.ship(&player.ship_class) {
+            None => return Err(format!("Unknown ship class: {}", player.ship_class)),
+            Some(class)
+                if !class.unlocked_by_default && !player.unlocked_ships.contains(&class.id) =>
+            {
+                return Err(format!("Ship class {} is locked", class.name));
+            }
+            Some(_) => {}
+        }
+
         // Spawn ship entity in simulation
         let spawn_position = self.get_spawn_position();
-        let ship_entity =
-            self.simulation
-                .spawn_player_ship(player.id, player.name.clone(), spawn_position);
+        let ship_entity = self.simulation.spawn_player_ship(
+            player.id,
+            player.name.clone(),
+            &player.ship_class,
+            spawn_position,
+        );
 
         // Map player to their ship entity
         self.player_entities.insert(player.id, ship_entity);
@@ -167,11 +174,19 @@ impl Room {
             let _ = existing_player.sender.send(join_msg.clone());
         }
 
+        self.send_entity_infos(&player);
         self.players.insert(player.id, player);
         self.last_activity = Instant::now();
         Ok(())
     }
 
+    /// Bring a (re)joining player up to date on every entity's static data
+    fn send_entity_infos(&self, player: &Player) {
+        for info in self.simulation.entity_infos() {
+            let _ = player.sender.send(ServerMessage::EntityInfo(info));
+        }
+    }
+
     fn get_spawn_position(&self) -> Vector<f32> {
         // Simple spawn positioning - spread players around the center
         let player_count = self.players.len() as f32;
@@ -180,30 +195,214 @@ impl Room {
         Vector::new(angle.cos() * radius, angle.sin() * radius)
     }
 
-    pub fn remove_player(&mut self, player_id: Uuid) {
-        if self.players.remove(&player_id).is_some() {
-            // Remove ship entity from simulation
-            if let Some(ship_entity) = self.player_entities.remove(&player_id) {
-                self.simulation.despawn_entity(ship_entity);
+    /// Remove a player, returning their name and match results for their profile
+    pub fn remove_player(&mut self, player_id: Uuid) -> Option<(String, MatchResult)> {
+        let player = self.players.remove(&player_id).or_else(|| {
+            self.disconnected
+                .remove(&player_id)
+                .map(|disconnected| disconnected.player)
+        })?;
+        let mut result = None;
+
+        // Remove ship entity from simulation
+        if let Some(ship_entity) = self.player_entities.remove(&player_id) {
+            if let Ok(stats) = self
+                .simulation
+                .world
+                .get::<&components::Player>(ship_entity)
+            {
+                result = Some(MatchResult::single(
+                    stats.kills,
+                    stats.deaths,
+                    stats.score,
+                    stats.credits,
+                    &player.ship_class,
+                ));
             }
+            self.simulation
+                .despawn_entity(ship_entity, DespawnReason::Left);
+        }
+        self.simulation.player_latency.remove(&player_id);
 
-            let leave_msg = ServerMessage::PlayerLeft {
-                player_id: player_id.to_string(),
-            };
+        let leave_msg = ServerMessage::PlayerLeft {
+            player_id: player_id.to_string(),
+        };
 
-            for existing_player in self.players.values() {
-                let _ = existing_player.sender.send(leave_msg.clone());
-            }
+        for existing_player in self.players.values() {
+            let _ = existing_player.sender.send(leave_msg.clone());
+        }
+
+        self.last_activity = Instant::now();
+        result.map(|result| (player.name, result))
+    }
+
+    /// Hold a dropped player's ship frozen so they can resume within the grace window
+    pub fn disconnect_player(&mut self, player_id: Uuid) {
+        let Some(player) = self.players.remove(&player_id) else {
+            return;
+        };
+
+        if let Some(ship_entity) = self.player_entities.get(&player_id) {
+            self.simulation.freeze_entity(*ship_entity);
+        }
+
+        let disconnect_msg = ServerMessage::PlayerDisconnected {
+            player_id: player_id.to_string(),
+            grace_secs: REJOIN_GRACE.as_secs(),
+        };
 
-            self.last_activity = Instant::now();
+        for existing_player in self.players.values() {
+            let _ = existing_player.sender.send(disconnect_msg.clone());
         }
+
+        self.disconnected.insert(
+            player_id,
+            DisconnectedPlayer {
+                player,
+                disconnected_at: Instant::now(),
+            },
+        );
+        self.last_activity = Instant::now();
     }
 
+    /// Reattach a new connection to a disconnected player holding `resume_token`
+    pub fn resume_player(
+        &mut self,
+        resume_token: &str,
+        sender: OutboundSender,
+    ) -> Result<Uuid, String> {
+        let player_id = self
+            .disconnected
+            .iter()
+            .find(|(_, disconnected)| disconnected.player.resume_token == resume_token)
+            .map(|(id, _)| *id)
+            .ok_or_else(|| "Resume token is invalid or expired".to_string())?;
+
+        let mut player = self.disconnected.remove(&player_id).unwrap().player;
+        player.sender = sender;
+        player.last_seen = Instant::now();
+        player.resume_token = Uuid::new_v4().to_string(); // tokens are single use
+
+        if let Some(ship_entity) = self.player_entities.get(&player_id) {
+            self.simulation.thaw_entity(*ship_entity);
+        }
+
+        let reconnect_msg = ServerMessage::PlayerReconnected {
+            player_id: player_id.to_string(),
+        };
+
+        for existing_player in self.players.values() {
+            let _ = existing_player.sender.send(reconnect_msg.clone());
+        }
+
+        self.send_entity_infos(&player);
+        self.players.insert(player_id, player);
+        self.last_activity = Instant::now();
+        Ok(player_id)
+    }
+
+    /// Remove disconnected players whose grace window has run out
+    pub fn expire_disconnected(&mut self) -> Vec<(String, MatchResult)> {
+        let expired: Vec<Uuid> = self
+            .disconnected
+            .iter()
+            .filter(|(_, disconnected)| disconnected.disconnected_at.elapsed() >= REJOIN_GRACE)
+            .map(|(id, _)| *id)
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(|player_id| {
+                info!("Rejoin grace expired for player: {}", player_id);
+                self.remove_player(player_id)
+            })
+            .collect()
+    }
+
+    /// True if nobody is connected or waiting to resume
     pub fn is_empty(&self) -> bool {
-        self.players.is_empty()
+        self.players.is_empty() && self.disconnected.is_empty()
     }
 
-    pub fn cleanup_inactive_players(&mut self) {
+    pub fn has_connected_players(&self) -> bool {
+        !self.players.is_empty()
+    }
+
+    /// Capture the room and everyone in it, connected or waiting to resume
+    pub fn checkpoint(&self) -> Result<RoomCheckpoint, String> {
+        let world = self.simulation.save_state()?;
+        let players = self
+            .players
+            .values()
+            .chain(
+                self.disconnected
+                    .values()
+                    .map(|disconnected| &disconnected.player),
+            )
+            .map(|player| CheckpointPlayer {
+                id: player.id,
+                name: player.name.clone(),
+                ship_class: player.ship_class.clone(),
+                unlocked_ships: player.unlocked_ships.clone(),
+                resume_token: player.resume_token.clone(),
+            })
+            .collect();
+        Ok(RoomCheckpoint::new(self.code.as_str(), world, players))
+    }
+
+    /// Rebuild a checkpointed room; everyone in it is held as disconnected,
+    /// with the grace window counted from when the checkpoint was taken
+    ///
+    /// The simulation's clock is left as saved; resume it before stepping.
+    pub fn restore(
+        checkpoint: RoomCheckpoint,
+        content: Arc<ContentRegistry>,
+    ) -> Result<Self, String> {
+        let simulation = GameSimulation::restore_state(&checkpoint.world, content)?;
+
+        let now = Instant::now();
+        let disconnected_at = now.checked_sub(checkpoint.age()).unwrap_or(now);
+        let mut room = Self {
+            code: RoomCode(checkpoint.room_code),
+            players: HashMap::new(),
+            disconnected: HashMap::new(),
+            created_at: now,
+            last_activity: now,
+            simulation,
+            player_entities: HashMap::new(),
+            metrics: RoomMetrics::default(),
+        };
+
+        for player in checkpoint.players {
+            if let Some(ship_entity) = room.simulation.player_entity(player.id) {
+                room.simulation.freeze_entity(ship_entity);
+                room.player_entities.insert(player.id, ship_entity);
+            }
+
+            // The old connection is gone; resuming swaps in the new one
+            let (sender, _) = outbound::channel(0);
+            sender.close(None);
+            room.disconnected.insert(
+                player.id,
+                DisconnectedPlayer {
+                    player: Player {
+                        id: player.id,
+                        name: player.name,
+                        ship_class: player.ship_class,
+                        unlocked_ships: player.unlocked_ships,
+                        resume_token: player.resume_token,
+                        last_seen: now,
+                        latency: LatencyEstimate::default(),
+                        sender,
+                    },
+                    disconnected_at,
+                },
+            );
+        }
+        Ok(room)
+    }
+
+    pub fn cleanup_inactive_players(&mut self) -> Vec<(String, MatchResult)> {
         let cutoff = Instant::now() - Duration::from_secs(120); // 2 minutes timeout
         let inactive_players: Vec<Uuid> = self
             .players
@@ -212,23 +411,49 @@ impl Room {
             .map(|(id, _)| *id)
             .collect();
 
-        for player_id in inactive_players {
-            warn!("Removing inactive player: {}", player_id);
-            self.remove_player(player_id);
-        }
+        inactive_players
+            .into_iter()
+            .filter_map(|player_id| {
+                warn!("Removing inactive player: {}", player_id);
+                self.remove_player(player_id)
+            })
+            .collect()
     }
 }
 
 pub type SharedRooms = Arc<Mutex<HashMap<String, Room>>>;
+pub type SharedContent = Arc<Mutex<Arc<ContentRegistry>>>;
 
 #[derive(Clone)]
 pub struct AppState {
     pub rooms: SharedRooms,
+    pub content: SharedContent,
+    pub profiles: ProfileStore,
+    pub profile_writer: ProfileWriter,
+    pub leaderboard: Leaderboard,
+    pub bandwidth: u64, // per-connection bytes per second, 0 for unlimited
+    pub replays: Option<ReplayStore>, // where finished matches are saved, if anywhere
+    pub checkpoints: Option<CheckpointStore>, // where live rooms are saved, if anywhere
+    pub shutting_down: Arc<AtomicBool>,
+}
+
+/// Log levels come from `RUST_LOG` (for example `info,cosmic_crunchers_server::simulation=debug`);
+/// `COSMIC_LOG_FORMAT=json` switches to one JSON object per line
+fn init_logging() {
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match std::env::var("COSMIC_LOG_FORMAT").as_deref() {
+        Ok("json") => builder.json().with_current_span(true).init(),
+        Ok("text") | Err(_) => builder.init(),
+        Ok(other) => panic!("COSMIC_LOG_FORMAT must be text or json, not {}", other),
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    init_logging();
 
     // Get configuration from environment variables
     let server_host =
@@ -243,32 +468,155 @@ async fn main() {
     let client_port = std::env::var("CLIENT_PORT").unwrap_or_else(|_| "5173".to_string());
     let client_url = format!("http://{}:{}", client_host, client_port);
 
+    // Load ship, weapon, enemy, wave and drop definitions
+    let content_dir = std::env::var("COSMIC_CONTENT_DIR").unwrap_or_else(|_| "content".to_string());
+    let content = ContentRegistry::load(Path::new(&content_dir))
+        .unwrap_or_else(|e| panic!("Failed to load game content:\n{}", e));
+    info!(
+        "Loaded content from {}: {} ships, {} weapons, {} enemies, {} waves, {} drop tables",
+        content_dir,
+        content.ships.len(),
+        content.weapons.len(),
+        content.enemies.len(),
+        content.waves.len(),
+        content.drop_tables.len()
+    );
+
+    // Open the embedded database holding player profiles
+    let db_path =
+        std::env::var("COSMIC_DB_PATH").unwrap_or_else(|_| "cosmic_crunchers.redb".to_string());
+    let db = Arc::new(redb::Database::create(&db_path).expect("Failed to open database"));
+    let profiles = ProfileStore::open(db.clone()).expect("Failed to open profile store");
+    let leaderboard = Leaderboard::open(db.clone()).expect("Failed to open leaderboard");
+    info!("Opened database at {}", db_path);
+
+    // Live rooms are checkpointed unless COSMIC_CHECKPOINT_ROOMS is false
+    let checkpoint_rooms = std::env::var("COSMIC_CHECKPOINT_ROOMS")
+        .map(|value| {
+            value
+                .parse::<bool>()
+                .expect("COSMIC_CHECKPOINT_ROOMS must be true or false")
+        })
+        .unwrap_or(true);
+    let checkpoints = checkpoint_rooms
+        .then(|| CheckpointStore::open(db).expect("Failed to open checkpoint store"));
+    let checkpoint_interval = std::env::var("COSMIC_CHECKPOINT_INTERVAL")
+        .map(|value| {
+            value
+                .parse::<u64>()
+                .ok()
+                .filter(|&seconds| seconds > 0)
+                .expect("COSMIC_CHECKPOINT_INTERVAL must be a positive number of seconds")
+        })
+        .unwrap_or(10);
+
+    let shutdown_countdown = std::env::var("COSMIC_SHUTDOWN_COUNTDOWN")
+        .map(|value| {
+            value
+                .parse::<u64>()
+                .expect("COSMIC_SHUTDOWN_COUNTDOWN must be a number of seconds")
+        })
+        .unwrap_or(10);
+
+    let bandwidth = std::env::var("COSMIC_CLIENT_BANDWIDTH")
+        .map(|value| {
+            value
+                .parse::<u64>()
+                .expect("COSMIC_CLIENT_BANDWIDTH must be a number of bytes per second")
+        })
+        .unwrap_or(outbound::DEFAULT_BANDWIDTH);
+
+    // Every match is recorded unless COSMIC_REPLAY_DIR is set empty
+    let replay_dir = std::env::var("COSMIC_REPLAY_DIR").unwrap_or_else(|_| "replays".to_string());
+    let replays = (!replay_dir.is_empty()).then(|| ReplayStore {
+        dir: replay_dir.into(),
+        max_files: std::env::var("COSMIC_REPLAY_MAX_FILES")
+            .map(|value| {
+                value
+                    .parse::<usize>()
+                    .expect("COSMIC_REPLAY_MAX_FILES must be a number of files")
+            })
+            .unwrap_or(100),
+        max_bytes: std::env::var("COSMIC_REPLAY_MAX_MB")
+            .map(|value| {
+                value
+                    .parse::<u64>()
+                    .expect("COSMIC_REPLAY_MAX_MB must be a number of megabytes")
+            })
+            .unwrap_or(500)
+            * 1024
+            * 1024,
+    });
+    match &replays {
+        Some(store) => info!(
+            "Recording replays to {} (keeping up to {} files, {} MB)",
+            store.dir.display(),
+            store.max_files,
+            store.max_bytes / (1024 * 1024)
+        ),
+        None => info!("Replay recording disabled"),
+    }
+
     let state = AppState {
         rooms: Arc::new(Mutex::new(HashMap::new())),
+        content: Arc::new(Mutex::new(Arc::new(content))),
+        profile_writer: ProfileWriter::spawn(profiles.clone()),
+        profiles,
+        leaderboard,
+        bandwidth,
+        replays,
+        checkpoints,
+        shutting_down: Arc::new(AtomicBool::new(false)),
     };
 
+    // Bring back the rooms that were live when the server last stopped
+    if let Some(store) = state.checkpoints.clone() {
+        restore_rooms(&state, &store);
+
+        let checkpoint_state = state.clone();
+        tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(checkpoint_interval));
+            interval.tick().await; // rooms were just restored, nothing has changed yet
+            loop {
+                interval.tick().await;
+                checkpoint_rooms_task(&checkpoint_state, &store).await;
+            }
+        });
+        info!("Checkpointing rooms every {}s", checkpoint_interval);
+    }
+
     // Start room cleanup task
-    let cleanup_rooms = state.rooms.clone();
+    let cleanup_state = state.clone();
     tokio::spawn(async move {
-        let mut interval = time::interval(Duration::from_secs(60));
+        let mut interval = time::interval(Duration::from_secs(10));
         loop {
             interval.tick().await;
-            cleanup_rooms_task(&cleanup_rooms).await;
+            cleanup_rooms_task(&cleanup_state).await;
         }
     });
 
+    // Start content hot reload task
+    let watch_rooms = state.rooms.clone();
+    let watch_content = state.content.clone();
+    tokio::spawn(async move {
+        watch_content_task(watch_rooms, watch_content, content_dir.into()).await;
+    });
+
     let app = Router::new()
         .route("/", get(|| async { "Cosmic Crunchers Server" }))
         .route("/ws", get(websocket_handler))
         .route("/create-room", post(create_room))
         .route("/rooms", get(list_rooms))
+        .route("/ship-classes", get(list_ship_classes))
+        .route("/leaderboard", get(get_leaderboard))
+        .route("/metrics", get(get_metrics))
         .layer(
             CorsLayer::new()
                 .allow_origin(client_url.parse::<axum::http::HeaderValue>().unwrap())
                 .allow_methods(Any)
                 .allow_headers(Any),
         )
-        .with_state(state);
+        .with_state(state.clone());
 
     let bind_address = format!("{}:{}", server_host, server_port);
     let listener = tokio::net::TcpListener::bind(&bind_address)
@@ -282,114 +630,348 @@ async fn main() {
     info!("WebSocket endpoint: ws://{}/ws", bind_address);
     info!("CORS configured for: {}", client_url);
 
+    // Rooms are drained before the listener closes, so no socket is left open
     axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown(state, shutdown_countdown))
         .await
         .expect("Failed to start server");
+    info!("Server stopped");
+}
+
+/// Resolves on the first Ctrl-C or SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c().await.expect("Failed to listen for Ctrl-C");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Wait for a shutdown signal, count every player down, then close all rooms
+/// and flush everything they still have to write
+async fn shutdown(state: AppState, countdown_secs: u64) {
+    shutdown_signal().await;
+    state.shutting_down.store(true, Ordering::Relaxed);
+    info!(
+        "Shutdown requested, closing rooms in {}s; no longer accepting joins",
+        countdown_secs
+    );
+
+    let mut interval = time::interval(Duration::from_secs(1));
+    for seconds_remaining in (1..=countdown_secs).rev() {
+        interval.tick().await;
+        let shutdown_msg = ServerMessage::ServerShuttingDown { seconds_remaining };
+        let rooms = state.rooms.lock().unwrap();
+        for player in rooms.values().flat_map(|room| room.players.values()) {
+            let _ = player.sender.send(shutdown_msg.clone());
+        }
+    }
+    interval.tick().await;
+
+    let mut pending = Vec::new();
+    let mut checkpoints = Vec::new();
+    {
+        let mut rooms = state.rooms.lock().unwrap();
+        for (room_code, mut room) in rooms.drain() {
+            METRICS.rooms_closed.fetch_add(1, Ordering::Relaxed);
+            for player in room.players.values() {
+                player
+                    .sender
+                    .close(ErrorCode::ServerShuttingDown.close_code());
+            }
+
+            // Checkpointed players keep their results in the saved world
+            let checkpoint = match &state.checkpoints {
+                Some(_) if !room.is_empty() => room
+                    .checkpoint()
+                    .inspect_err(|e| error!("Failed to checkpoint room {}: {}", room_code, e))
+                    .ok(),
+                _ => None,
+            };
+            match checkpoint {
+                Some(checkpoint) => checkpoints.push(checkpoint),
+                None => {
+                    let player_ids: Vec<Uuid> = room
+                        .players
+                        .keys()
+                        .chain(room.disconnected.keys())
+                        .copied()
+                        .collect();
+                    for player_id in player_ids {
+                        if let Some((name, result)) = room.remove_player(player_id) {
+                            pending.extend(record_match_result(&state, name, result));
+                        }
+                    }
+                }
+            }
+
+            pending.extend(save_replay(state.replays.as_ref(), &mut room));
+            info!("Closed room {} for shutdown", room_code);
+        }
+    }
+
+    if let Some(store) = state.checkpoints.clone() {
+        let count = checkpoints.len();
+        match tokio::task::spawn_blocking(move || store.replace_all(&checkpoints)).await {
+            Ok(Ok(())) => info!("Checkpointed {} rooms", count),
+            Ok(Err(e)) => error!("Failed to write {} room checkpoints: {}", count, e),
+            Err(e) => error!("Checkpoint task panicked: {}", e),
+        }
+    }
+
+    // Replays and leaderboard scores first, since new scores don't touch profiles
+    for handle in pending {
+        if let Err(e) = handle.await {
+            error!("Shutdown write task panicked: {}", e);
+        }
+    }
+    state.profile_writer.flush().await;
+    info!("Flushed all pending writes");
 }
 
 async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+    ws.on_upgrade(|socket| {
+        let player_id = Uuid::new_v4();
+        // Everything logged for this connection carries its player and, once joined, room
+        let span = info_span!("connection", %player_id, room = field::Empty);
+        handle_socket(socket, state, player_id).instrument(span)
+    })
 }
 
-async fn handle_socket(socket: WebSocket, state: AppState) {
+async fn handle_socket(socket: WebSocket, state: AppState, player_id: Uuid) {
     let (mut sender, mut receiver) = socket.split();
-    let player_id = Uuid::new_v4();
-    let (tx, mut rx) = broadcast::channel(100);
-
+    let (tx, mut rx) = outbound::channel(state.bandwidth);
     info!("New WebSocket connection: {}", player_id);
+    METRICS.connections_opened.fetch_add(1, Ordering::Relaxed);
 
     // Task to send messages to client
-    let send_task = tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
-            if let Ok(json) = serde_json::to_string(&msg)
-                && sender.send(Message::Text(json.into())).await.is_err()
-            {
-                break;
+    let send = async move {
+        let mut seq: u32 = 0;
+        let mut probe = time::interval(PROBE_INTERVAL);
+        loop {
+            // The handshake queues Welcome right after picking the encoding, so
+            // the first message received once one is set is the Welcome
+            let welcomed = rx.encoding().is_some();
+            let outbound = tokio::select! {
+                outbound = rx.recv() => outbound,
+                // Probes skip the queue so queueing delay doesn't count as latency
+                _ = probe.tick(), if welcomed => Outbound::Message(ServerMessage::Probe { server_time: 0 }),
+            };
+
+            seq = seq.wrapping_add(1);
+            let encoded = match outbound {
+                Outbound::Encoded(message) => message.frame(seq),
+                Outbound::Message(mut msg) => {
+                    // Stamp send times as late as possible
+                    match &mut msg {
+                        ServerMessage::Pong {
+                            server_send_time, ..
+                        } => *server_send_time = server_time_ms(),
+                        ServerMessage::Probe { server_time } => *server_time = server_time_ms(),
+                        _ => {}
+                    }
+                    let encoding = rx.encoding().unwrap_or(Encoding::Json);
+                    encoding.encode(&Frame::new(seq, msg))
+                }
+                Outbound::Close(code) => {
+                    if let Some(code) = code {
+                        let _ = sender.send(close_message(code)).await;
+                    }
+                    break;
+                }
+            };
+            match encoded {
+                Ok(data) => {
+                    rx.record_sent(data.len());
+                    if sender.send(wire_message(data)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(e) => error!("Failed to encode message for {}: {}", player_id, e),
             }
         }
-    });
+
+        let dropped = rx.dropped();
+        if dropped > 0 {
+            info!(
+                "Connection {} dropped {} outbound messages",
+                player_id, dropped
+            );
+        }
+    };
+    let send_task = tokio::spawn(send.in_current_span());
 
     // Task to receive messages from client
     let recv_task = {
         let state = state.clone();
-        tokio::spawn(async move {
+        let recv = async move {
             let mut current_room: Option<String> = None;
+            let mut player_id = player_id; // replaced when resuming an earlier session
+            let mut handshake_complete = false;
+            let mut latency = LatencyEstimate::default();
 
             while let Some(msg) = receiver.next().await {
-                match msg {
-                    Ok(Message::Text(text)) => {
-                        if let Ok(client_msg) = serde_json::from_str::<ClientMessage>(&text) {
-                            match client_msg {
-                                ClientMessage::Join {
-                                    room_code,
-                                    player_name,
-                                } => {
-                                    current_room = handle_join(
-                                        &state,
-                                        &room_code,
-                                        &player_name,
-                                        player_id,
-                                        tx.clone(),
-                                    )
-                                    .await;
-                                }
-                                ClientMessage::Input {
-                                    sequence,
-                                    timestamp,
-                                    data,
-                                } => {
-                                    // Parse input data and add to simulation
-                                    if let Some(room_code) = &current_room {
-                                        handle_input(
-                                            &state, room_code, player_id, sequence, timestamp, data,
-                                        )
-                                        .await;
-                                    }
-                                }
-                                ClientMessage::Ping { timestamp } => {
-                                    let pong = ServerMessage::Pong { timestamp };
-                                    let _ = tx.send(pong);
-
-                                    // Update last seen time
-                                    if let Some(room_code) = &current_room {
-                                        update_player_activity(&state, room_code, player_id).await;
-                                    }
-                                }
-                                ClientMessage::RequestDebugRender { timestamp } => {
-                                    // Handle debug render request
-                                    if let Some(room_code) = &current_room {
-                                        handle_debug_request(
-                                            &state, room_code, player_id, timestamp,
-                                        )
-                                        .await;
-                                    }
-                                }
-                                ClientMessage::Leave => {
-                                    if let Some(room_code) = &current_room {
-                                        leave_room(&state, room_code, player_id).await;
-                                    }
-                                    break;
-                                }
-                            }
-                        }
-                    }
+                let received_at = server_time_ms();
+                let data = match msg {
+                    Ok(Message::Text(text)) => WireData::Text(text.to_string()),
+                    Ok(Message::Binary(bytes)) => WireData::Binary(bytes.to_vec()),
                     Ok(Message::Close(_)) => break,
                     Err(e) => {
                         error!("WebSocket error: {}", e);
                         break;
                     }
-                    _ => {}
+                    _ => continue,
+                };
+
+                let client_msg = match Encoding::decode::<Frame<ClientMessage>>(&data) {
+                    Ok(frame) => frame.message,
+                    Err(e) => {
+                        warn!("Malformed message from {}: {}", player_id, e);
+                        let _ = tx.send(ServerMessage::Error {
+                            code: ErrorCode::MalformedMessage,
+                            message: e,
+                        });
+                        continue;
+                    }
+                };
+
+                // Everything else waits for a successful Hello
+                let handshake = match (&client_msg, handshake_complete) {
+                    (
+                        ClientMessage::Hello {
+                            protocol_version,
+                            encodings,
+                            features,
+                        },
+                        false,
+                    ) => Some(handle_hello(*protocol_version, encodings, features)),
+                    (_, false) => Some(Err((
+                        ErrorCode::HandshakeRequired,
+                        "Send Hello before any other message".to_string(),
+                    ))),
+                    _ => None,
+                };
+
+                match handshake {
+                    Some(Ok(welcome)) => {
+                        if let ServerMessage::Welcome { encoding, .. } = &welcome {
+                            tx.set_encoding(*encoding);
+                        }
+                        let _ = tx.send(welcome);
+                        handshake_complete = true;
+                        continue;
+                    }
+                    Some(Err((code, message))) => {
+                        warn!("Rejected handshake from {}: {}", player_id, message);
+                        let _ = tx.send(ServerMessage::Error { code, message });
+                        tx.close(code.close_code());
+                        break;
+                    }
+                    None => {}
+                }
+
+                match client_msg {
+                    ClientMessage::Hello { .. } => {
+                        warn!("Ignoring repeated Hello from {}", player_id);
+                    }
+                    ClientMessage::Join {
+                        room_code,
+                        player_name,
+                        ship_class,
+                    } => {
+                        current_room = handle_join(
+                            &state,
+                            &room_code,
+                            &player_name,
+                            ship_class,
+                            player_id,
+                            tx.clone(),
+                        )
+                        .await;
+                        if let Some(room_code) = &current_room {
+                            Span::current().record("room", room_code.as_str());
+                        }
+                    }
+                    ClientMessage::Resume {
+                        room_code,
+                        resume_token,
+                    } => {
+                        if let Some(resumed_id) =
+                            handle_resume(&state, &room_code, &resume_token, tx.clone()).await
+                        {
+                            player_id = resumed_id;
+                            Span::current()
+                                .record("player_id", field::display(player_id))
+                                .record("room", room_code.as_str());
+                            current_room = Some(room_code);
+                        }
+                    }
+                    ClientMessage::Input { data } => {
+                        // Add input to simulation
+                        if let Some(room_code) = &current_room {
+                            handle_input(&state, room_code, player_id, &data).await;
+                        }
+                    }
+                    ClientMessage::Ping { timestamp } => {
+                        // Update last seen time
+                        let mut tick = 0;
+                        if let Some(room_code) = &current_room {
+                            update_player_activity(&state, room_code, player_id).await;
+                            tick = room_tick(&state, room_code);
+                        }
+
+                        let pong = ServerMessage::Pong {
+                            timestamp,
+                            server_receive_time: received_at,
+                            server_send_time: 0, // stamped by the send task
+                            tick,
+                        };
+                        let _ = tx.send(pong);
+                    }
+                    ClientMessage::ProbeReply { server_time } => {
+                        latency.record(received_at.saturating_sub(server_time) as f32);
+                        if let Some(room_code) = &current_room {
+                            update_player_latency(&state, room_code, player_id, latency);
+                        }
+                    }
+                    ClientMessage::RequestDebugRender { .. } => {
+                        // Handle debug render request
+                        if let Some(room_code) = &current_room {
+                            handle_debug_request(&state, room_code, player_id).await;
+                        }
+                    }
+                    ClientMessage::Leave => {
+                        if let Some(room_code) = current_room.take() {
+                            leave_room(&state, &room_code, player_id).await;
+                        }
+                        break;
+                    }
                 }
             }
 
-            // Clean up on disconnect
+            // Hold the player's ship for the rejoin grace window
             if let Some(room_code) = current_room {
-                leave_room(&state, &room_code, player_id).await;
+                disconnect_from_room(&state, &room_code, player_id).await;
             }
-        })
+            tx.close(None);
+        };
+        tokio::spawn(recv.in_current_span())
     };
 
     // Wait for either task to complete
@@ -399,22 +981,130 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
     }
 
     info!("WebSocket connection closed: {}", player_id);
+    METRICS.connections_closed.fetch_add(1, Ordering::Relaxed);
+}
+
+fn wire_message(data: WireData) -> Message {
+    match data {
+        WireData::Text(text) => Message::Text(text.into()),
+        WireData::Binary(bytes) => Message::Binary(bytes.into()),
+    }
+}
+
+fn close_message(code: u16) -> Message {
+    Message::Close(Some(CloseFrame {
+        code,
+        reason: "".into(),
+    }))
+}
+
+/// Validate a client's Hello and build the Welcome reply
+fn handle_hello(
+    protocol_version: u32,
+    encodings: &[String],
+    features: &[String],
+) -> Result<ServerMessage, (ErrorCode, String)> {
+    let Some(version) = protocol::negotiate_version(protocol_version) else {
+        return Err((
+            ErrorCode::IncompatibleProtocol,
+            format!(
+                "Client protocol {} is not supported (server speaks {}..={})",
+                protocol_version,
+                protocol::MIN_PROTOCOL_VERSION,
+                PROTOCOL_VERSION
+            ),
+        ));
+    };
+
+    let Some(encoding) = Encoding::negotiate(encodings) else {
+        let supported: Vec<&str> = Encoding::SUPPORTED.iter().map(|e| e.name()).collect();
+        return Err((
+            ErrorCode::UnsupportedEncoding,
+            format!(
+                "None of the offered encodings {:?} are supported (server supports {:?})",
+                encodings, supported
+            ),
+        ));
+    };
+
+    let features = features
+        .iter()
+        .filter(|feature| SUPPORTED_FEATURES.contains(&feature.as_str()))
+        .cloned()
+        .collect();
+
+    Ok(ServerMessage::Welcome {
+        protocol_version: version,
+        encoding,
+        features,
+        tick_rate: SIM_TICK_RATE,
+        snapshot_rate: SNAPSHOT_RATE,
+    })
 }
 
 async fn handle_join(
     state: &AppState,
     room_code: &str,
     player_name: &str,
+    ship_class: Option<String>,
     player_id: Uuid,
-    sender: broadcast::Sender<ServerMessage>,
+    sender: OutboundSender,
 ) -> Option<String> {
+    if state.shutting_down.load(Ordering::Relaxed) {
+        let _ = sender.send(ServerMessage::Error {
+            code: ErrorCode::ServerShuttingDown,
+            message: "Server is shutting down".to_string(),
+        });
+        return None;
+    }
+
+    if let Err(message) = validate_name(player_name) {
+        let _ = sender.send(ServerMessage::Error {
+            code: ErrorCode::JoinFailed,
+            message,
+        });
+        return None;
+    }
+
+    let profiles = state.profiles.clone();
+    let name = player_name.to_string();
+    let loaded = tokio::task::spawn_blocking(move || profiles.load_or_create(&name))
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+    let profile = match loaded {
+        Ok(profile) => profile,
+        Err(e) => {
+            error!("Failed to load profile for {}: {}", player_name, e);
+            let _ = sender.send(ServerMessage::Error {
+                code: ErrorCode::ProfileUnavailable,
+                message: "Failed to load player profile".to_string(),
+            });
+            return None;
+        }
+    };
+
     let mut rooms = state.rooms.lock().unwrap();
 
     if let Some(room) = rooms.get_mut(room_code) {
+        // Explicit choice first, then the profile's last class, then the default
+        let content = room.simulation.content.clone();
+        let ship_class = ship_class
+            .or_else(|| {
+                profile.last_ship_class.clone().filter(|class| {
+                    content.ship(class).is_some_and(|def| {
+                        def.unlocked_by_default || profile.unlocked_ships.contains(class)
+                    })
+                })
+            })
+            .unwrap_or_else(|| content.default_ship_class.clone());
         let player = Player {
             id: player_id,
             name: player_name.to_string(),
+            ship_class,
+            unlocked_ships: profile.unlocked_ships.clone(),
+            resume_token: Uuid::new_v4().to_string(),
             last_seen: Instant::now(),
+            latency: LatencyEstimate::default(),
             sender: sender.clone(),
         };
 
@@ -424,33 +1114,33 @@ async fn handle_join(
                 let entity_id = room
                     .player_entities
                     .get(&player_id)
-                    .map(|entity| entity.id() as u64)
+                    .and_then(|entity| room.simulation.net_id(*entity))
                     .unwrap_or(0);
 
+                let player = room.players.get(&player_id).unwrap();
                 let join_msg = ServerMessage::RoomJoined {
                     room_code: room_code.to_string(),
                     player_id: player_id.to_string(),
                     entity_id,
+                    resume_token: player.resume_token.clone(),
                 };
-                let _ = rooms
-                    .get(room_code)
-                    .unwrap()
-                    .players
-                    .get(&player_id)
-                    .unwrap()
-                    .sender
-                    .send(join_msg);
+                let _ = player.sender.send(join_msg);
+                let _ = sender.send(ServerMessage::ProfileLoaded { profile });
                 info!("Player {} joined room {}", player_name, room_code);
                 Some(room_code.to_string())
             }
             Err(e) => {
-                let error_msg = ServerMessage::Error { message: e };
+                let error_msg = ServerMessage::Error {
+                    code: ErrorCode::JoinFailed,
+                    message: e,
+                };
                 let _ = sender.send(error_msg);
                 None
             }
         }
     } else {
         let error_msg = ServerMessage::Error {
+            code: ErrorCode::JoinFailed,
             message: "Room not found".to_string(),
         };
         let _ = sender.send(error_msg);
@@ -458,21 +1148,155 @@ async fn handle_join(
     }
 }
 
+async fn handle_resume(
+    state: &AppState,
+    room_code: &str,
+    resume_token: &str,
+    sender: OutboundSender,
+) -> Option<Uuid> {
+    if state.shutting_down.load(Ordering::Relaxed) {
+        let _ = sender.send(ServerMessage::Error {
+            code: ErrorCode::ServerShuttingDown,
+            message: "Server is shutting down".to_string(),
+        });
+        return None;
+    }
+
+    let mut rooms = state.rooms.lock().unwrap();
+
+    let resumed = rooms
+        .get_mut(room_code)
+        .ok_or_else(|| "Room not found".to_string())
+        .and_then(|room| {
+            let player_id = room.resume_player(resume_token, sender.clone())?;
+            let entity_id = room
+                .player_entities
+                .get(&player_id)
+                .and_then(|entity| room.simulation.net_id(*entity))
+                .unwrap_or(0);
+            Ok((
+                player_id,
+                entity_id,
+                room.players[&player_id].resume_token.clone(),
+            ))
+        });
+
+    match resumed {
+        Ok((player_id, entity_id, resume_token)) => {
+            let _ = sender.send(ServerMessage::RoomJoined {
+                room_code: room_code.to_string(),
+                player_id: player_id.to_string(),
+                entity_id,
+                resume_token,
+            });
+            info!("Player {} resumed in room {}", player_id, room_code);
+            Some(player_id)
+        }
+        Err(message) => {
+            let _ = sender.send(ServerMessage::Error {
+                code: ErrorCode::ResumeFailed,
+                message,
+            });
+            None
+        }
+    }
+}
+
+async fn disconnect_from_room(state: &AppState, room_code: &str, player_id: Uuid) {
+    let mut rooms = state.rooms.lock().unwrap();
+
+    if let Some(room) = rooms.get_mut(room_code) {
+        room.disconnect_player(player_id);
+        info!(
+            "Player {} disconnected from room {}, holding for {}s",
+            player_id,
+            room_code,
+            REJOIN_GRACE.as_secs()
+        );
+    }
+}
+
 async fn leave_room(state: &AppState, room_code: &str, player_id: Uuid) {
     let mut rooms = state.rooms.lock().unwrap();
 
     if let Some(room) = rooms.get_mut(room_code) {
-        room.remove_player(player_id);
+        if let Some((name, result)) = room.remove_player(player_id) {
+            record_match_result(state, name, result);
+        }
         info!("Player {} left room {}", player_id, room_code);
 
         // Remove empty rooms
-        if room.is_empty() {
-            rooms.remove(room_code);
+        if room.is_empty()
+            && let Some(mut room) = rooms.remove(room_code)
+        {
+            save_replay(state.replays.as_ref(), &mut room);
+            METRICS.rooms_closed.fetch_add(1, Ordering::Relaxed);
             info!("Removed empty room: {}", room_code);
         }
     }
 }
 
+/// Stop a room's recording and save it in the background
+fn save_replay(replays: Option<&ReplayStore>, room: &mut Room) -> Option<JoinHandle<()>> {
+    let (Some(store), Some(recording)) = (replays.cloned(), room.simulation.disable_recording())
+    else {
+        return None;
+    };
+    // Rooms nobody played in aren't worth keeping
+    if recording.roster().is_empty() {
+        return None;
+    }
+
+    let room_code = room.code.as_str().to_string();
+    Some(tokio::task::spawn_blocking(move || {
+        if let Err(e) = store.save(&room_code, recording) {
+            error!("Failed to save replay of room {}: {}", room_code, e);
+        }
+    }))
+}
+
+/// Queue a departed player's results for their profile and the leaderboard
+///
+/// Returns the leaderboard write, if the match scored at all.
+fn record_match_result(
+    state: &AppState,
+    name: String,
+    result: MatchResult,
+) -> Option<JoinHandle<()>> {
+    state.profile_writer.record(&name, result.clone());
+    if result.score == 0 {
+        return None;
+    }
+
+    let leaderboard = state.leaderboard.clone();
+    let rooms = state.rooms.clone();
+    let ship_class = result.ship_class.unwrap_or_default();
+    Some(tokio::task::spawn_blocking(move || {
+        match leaderboard.record(&name, result.score, result.kills, &ship_class) {
+            Ok(true) => {
+                info!("New high score: {} scored {}", name, result.score);
+                let Ok(page) = leaderboard.query(&LeaderboardQuery::default()) else {
+                    return;
+                };
+                let record_msg = ServerMessage::NewHighScore {
+                    player_name: name,
+                    score: result.score,
+                    ship_class,
+                    leaderboard: page,
+                };
+
+                // Announce the record in every room
+                let rooms = rooms.lock().unwrap();
+                for player in rooms.values().flat_map(|room| room.players.values()) {
+                    let _ = player.sender.send(record_msg.clone());
+                }
+            }
+            Ok(false) => {}
+            Err(e) => error!("Failed to record score for {}: {}", name, e),
+        }
+    }))
+}
+
 async fn update_player_activity(state: &AppState, room_code: &str, player_id: Uuid) {
     let mut rooms = state.rooms.lock().unwrap();
 
@@ -483,57 +1307,251 @@ async fn update_player_activity(state: &AppState, room_code: &str, player_id: Uu
     }
 }
 
-async fn create_room(State(state): State<AppState>) -> impl IntoResponse {
+fn update_player_latency(
+    state: &AppState,
+    room_code: &str,
+    player_id: Uuid,
+    latency: LatencyEstimate,
+) {
     let mut rooms = state.rooms.lock().unwrap();
-    let room = Room::new();
+
+    if let Some(room) = rooms.get_mut(room_code)
+        && let Some(player) = room.players.get_mut(&player_id)
+    {
+        player.latency = latency;
+        room.simulation
+            .set_player_latency(player_id, latency.one_way_ms());
+    }
+}
+
+/// Current simulation tick of a room, or 0 if it is gone
+fn room_tick(state: &AppState, room_code: &str) -> u64 {
+    let rooms = state.rooms.lock().unwrap();
+    rooms
+        .get(room_code)
+        .map(|room| room.simulation.tick)
+        .unwrap_or(0)
+}
+
+/// Options for `POST /create-room`
+#[derive(Debug, Deserialize)]
+struct CreateRoomParams {
+    /// Seed for the room's gameplay RNG, for challenge runs and reproducing bugs
+    seed: Option<u64>,
+    /// Let players damage each other
+    #[serde(default)]
+    friendly_fire: bool,
+}
+
+async fn create_room(
+    State(state): State<AppState>,
+    Query(params): Query<CreateRoomParams>,
+) -> impl IntoResponse {
+    if state.shutting_down.load(Ordering::Relaxed) {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Server is shutting down".to_string(),
+        );
+    }
+
+    let mut rooms = state.rooms.lock().unwrap();
+    let seed = params.seed.unwrap_or_else(rand::random);
+    let mut room = Room::new(state.content.lock().unwrap().clone(), seed);
     let room_code = room.code.as_str().to_string();
+    room.simulation.friendly_fire = params.friendly_fire;
+    if state.replays.is_some() {
+        room.simulation.enable_recording();
+    }
 
     rooms.insert(room_code.clone(), room);
-    info!("Created new room: {}", room_code);
+    METRICS.rooms_created.fetch_add(1, Ordering::Relaxed);
+    info!(
+        "Created new room: {} (seed {}, friendly fire {})",
+        room_code, seed, params.friendly_fire
+    );
+    spawn_room_simulation(&state, &room_code);
+
+    (StatusCode::CREATED, room_code)
+}
 
-    // Start simulation loop for this room
+/// Start the simulation loop of a room already in the room map
+fn spawn_room_simulation(state: &AppState, room_code: &str) {
     let simulation_rooms = state.rooms.clone();
-    let simulation_room_code = room_code.clone();
-    tokio::spawn(async move {
-        run_room_simulation(simulation_rooms, simulation_room_code).await;
-    });
+    let simulation_room_code = room_code.to_string();
+    let replays = state.replays.clone();
+    let span = info_span!("room", room = %room_code);
+    tokio::spawn(
+        async move {
+            run_room_simulation(simulation_rooms, simulation_room_code, replays).await;
+        }
+        .instrument(span),
+    );
+}
 
-    (StatusCode::CREATED, room_code)
+/// Restore every checkpointed room under its old code so its players can resume
+///
+/// Rooms checkpointed longer ago than the rejoin grace window aren't brought
+/// back; their players' matches end with the results the checkpoint holds.
+fn restore_rooms(state: &AppState, store: &CheckpointStore) {
+    let checkpoints = match store.all() {
+        Ok(checkpoints) => checkpoints,
+        Err(e) => {
+            error!("Failed to read room checkpoints: {}", e);
+            return;
+        }
+    };
+    let content = state.content.lock().unwrap().clone();
+
+    let mut restored = Vec::new();
+    for checkpoint in checkpoints {
+        let room_code = checkpoint.room_code.clone();
+        let expired = checkpoint.is_expired(REJOIN_GRACE);
+        let age = checkpoint.age();
+        let mut room = match Room::restore(checkpoint, content.clone()) {
+            Ok(room) => room,
+            Err(e) => {
+                error!("Failed to restore room {}: {}", room_code, e);
+                continue;
+            }
+        };
+
+        if expired {
+            let player_ids: Vec<Uuid> = room.disconnected.keys().copied().collect();
+            for player_id in player_ids {
+                if let Some((name, result)) = room.remove_player(player_id) {
+                    record_match_result(state, name, result);
+                }
+            }
+            info!(
+                "Discarded room {}: its checkpoint is {}s old",
+                room_code,
+                age.as_secs()
+            );
+            continue;
+        }
+        restored.push((room_code, room));
+    }
+
+    // Move the clock once, past the room furthest along, so every room's ticks fit behind it
+    let furthest = restored
+        .iter()
+        .map(|(_, room)| room.simulation.elapsed_ms())
+        .max()
+        .unwrap_or(0);
+    advance_server_time(furthest);
+
+    for (room_code, mut room) in restored {
+        room.simulation.resume_clock();
+        if state.replays.is_some() {
+            room.simulation.enable_recording();
+        }
+        info!(
+            "Restored room {} at tick {} with {} players waiting to resume",
+            room_code,
+            room.simulation.tick,
+            room.disconnected.len()
+        );
+        METRICS.rooms_restored.fetch_add(1, Ordering::Relaxed);
+        state.rooms.lock().unwrap().insert(room_code.clone(), room);
+        spawn_room_simulation(state, &room_code);
+    }
 }
 
-async fn run_room_simulation(rooms: SharedRooms, room_code: String) {
-    let mut interval = time::interval(Duration::from_millis(67)); // ~15 Hz
+/// Save every occupied room so it survives a restart or crash
+async fn checkpoint_rooms_task(state: &AppState, store: &CheckpointStore) {
+    let room_codes: Vec<String> = state.rooms.lock().unwrap().keys().cloned().collect();
+    let mut checkpoints: Vec<RoomCheckpoint> = Vec::with_capacity(room_codes.len());
+    for room_code in room_codes {
+        // Serialize one room per lock so other rooms keep ticking in between
+        let checkpoint = {
+            let rooms = state.rooms.lock().unwrap();
+            match rooms.get(&room_code) {
+                Some(room) if !room.is_empty() => room
+                    .checkpoint()
+                    .inspect_err(|e| error!("Failed to checkpoint room {}: {}", room_code, e))
+                    .ok(),
+                _ => None,
+            }
+        };
+        checkpoints.extend(checkpoint);
+        tokio::task::yield_now().await;
+    }
+    // Shutdown writes the final checkpoints itself
+    if state.shutting_down.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let store = store.clone();
+    let count = checkpoints.len();
+    match tokio::task::spawn_blocking(move || store.replace_all(&checkpoints)).await {
+        Ok(Ok(())) => debug!("Checkpointed {} rooms", count),
+        Ok(Err(e)) => error!("Failed to write {} room checkpoints: {}", count, e),
+        Err(e) => error!("Checkpoint task panicked: {}", e),
+    }
+}
+
+async fn run_room_simulation(rooms: SharedRooms, room_code: String, replays: Option<ReplayStore>) {
+    // Tick cadence must match the simulation so tick-derived time tracks the server clock
+    let mut interval = time::interval(SIM_TICK_DURATION);
 
     loop {
         interval.tick().await;
 
         let mut rooms_guard = rooms.lock().unwrap();
         if let Some(room) = rooms_guard.get_mut(&room_code) {
-            // Skip if room is empty
-            if room.is_empty() {
+            // Skip if nobody is connected, holding the clock so tick times stay current
+            if !room.has_connected_players() {
+                room.simulation.resume_clock();
                 continue;
             }
 
             // Step simulation
-            let step_result = room.simulation.step(1.0 / 15.0); // 15 Hz timestep
-
-            // Send snapshot if generated
-            if let Some(snapshot) = step_result.snapshot {
-                let snapshot_data = if let Ok(json) = serde_json::to_vec(&snapshot) {
-                    json
-                } else {
-                    continue;
-                };
-
-                let snapshot_msg = ServerMessage::Snapshot {
-                    sequence: snapshot.sequence,
-                    timestamp: snapshot.timestamp,
-                    data: snapshot_data,
-                };
+            let step_result = room.simulation.step(1.0 / SIM_TICK_RATE);
+            room.metrics
+                .tick_seconds
+                .observe(step_result.step_duration.as_secs_f64());
+            room.metrics.entities = step_result.entity_count;
+
+            // Recordings can't grow without bound; save long matches and stop recording them
+            if room
+                .simulation
+                .input_recorder
+                .as_ref()
+                .is_some_and(|recorder| {
+                    step_result.tick - recorder.start_tick >= MAX_RECORDED_TICKS
+                })
+            {
+                warn!(
+                    "Room {} reached the replay length limit, saving and stopping its recording",
+                    room_code
+                );
+                save_replay(replays.as_ref(), room);
+            }
 
-                // Broadcast to all players in the room
+            // Static data goes to everyone, ahead of the snapshots that use it
+            for info in step_result.entity_info {
                 for player in room.players.values() {
-                    let _ = player.sender.send(snapshot_msg.clone());
+                    let _ = player.sender.send(ServerMessage::EntityInfo(info.clone()));
+                }
+            }
+
+            // Events must not be lost, so they go reliably rather than in the snapshot
+            for (player_id, events) in step_result.events {
+                if let Some(player) = room.players.get(&player_id) {
+                    let _ = player.sender.send(ServerMessage::Events {
+                        tick: step_result.tick,
+                        events,
+                    });
+                }
+            }
+
+            // Send each player the snapshot filtered for their ship
+            for (player_id, snapshot) in step_result.snapshots {
+                if let Some(player) = room.players.get(&player_id) {
+                    room.metrics
+                        .snapshot_bytes
+                        .observe(snapshot.data.len() as f64);
+                    let _ = player.sender.send(ServerMessage::Snapshot(snapshot));
                 }
             }
         } else {
@@ -552,7 +1570,14 @@ async fn list_rooms(State(state): State<AppState>) -> impl IntoResponse {
             serde_json::json!({
                 "code": code,
                 "players": room.players.len(),
-                "created_at": room.created_at.elapsed().as_secs()
+                "created_at": room.created_at.elapsed().as_secs(),
+                "seed": room.simulation.seed,
+                "friendly_fire": room.simulation.friendly_fire,
+                "latency": room
+                    .players
+                    .values()
+                    .map(|player| (player.name.clone(), player.latency))
+                    .collect::<HashMap<_, _>>()
             })
         })
         .collect();
@@ -560,58 +1585,122 @@ async fn list_rooms(State(state): State<AppState>) -> impl IntoResponse {
     serde_json::to_string(&room_list).unwrap_or_else(|_| "[]".to_string())
 }
 
-async fn handle_input(
-    state: &AppState,
-    room_code: &str,
-    player_id: Uuid,
-    sequence: u32,
-    timestamp: u64,
-    data: Vec<u8>,
-) {
-    info!(
-        "🎮 Received input message: player={}, sequence={}, timestamp={}, data_len={}",
-        player_id,
-        sequence,
-        timestamp,
-        data.len()
-    );
+async fn list_ship_classes(State(state): State<AppState>) -> impl IntoResponse {
+    let content = state.content.lock().unwrap().clone();
+    let mut classes: Vec<_> = content.ships.values().collect();
+    classes.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let class_list: Vec<serde_json::Value> = classes
+        .into_iter()
+        .map(|class| {
+            serde_json::json!({
+                "id": class.id,
+                "name": class.name,
+                "default": class.id == content.default_ship_class,
+                "unlocked_by_default": class.unlocked_by_default,
+                "primary_weapon": class.primary_weapon,
+                "thrust_power": class.thrust_power,
+                "turn_rate": class.turn_rate,
+                "max_speed": class.max_speed,
+                "hull": class.hull,
+                "health": class.health,
+            })
+        })
+        .collect();
+
+    serde_json::to_string(&class_list).unwrap_or_else(|_| "[]".to_string())
+}
+
+async fn get_leaderboard(
+    State(state): State<AppState>,
+    Query(query): Query<LeaderboardQuery>,
+) -> impl IntoResponse {
+    let leaderboard = state.leaderboard.clone();
+    match tokio::task::spawn_blocking(move || leaderboard.query(&query)).await {
+        Ok(Ok(page)) => (
+            StatusCode::OK,
+            serde_json::to_string(&page).unwrap_or_else(|_| "{}".to_string()),
+        ),
+        Ok(Err(e)) => {
+            error!("Failed to query leaderboard: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load leaderboard".to_string(),
+            )
+        }
+        Err(e) => {
+            error!("Leaderboard query task failed: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load leaderboard".to_string(),
+            )
+        }
+    }
+}
+
+async fn get_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let rooms = state.rooms.lock().unwrap();
+    let samples: Vec<RoomSample> = rooms
+        .iter()
+        .map(|(code, room)| RoomSample {
+            code,
+            players: room.players.len(),
+            disconnected: room.disconnected.len(),
+            metrics: &room.metrics,
+        })
+        .collect();
+
+    (
+        [(header::CONTENT_TYPE, metrics::CONTENT_TYPE)],
+        metrics::render(&samples),
+    )
+}
+
+async fn handle_input(state: &AppState, room_code: &str, player_id: Uuid, data: &[u8]) {
+    let inputs = match input_codec::decode(data) {
+        Ok(inputs) => inputs,
+        Err(e) => {
+            warn!(
+                "Failed to decode input packet from player {}: {}",
+                player_id, e
+            );
+            METRICS
+                .malformed_input_packets
+                .fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+    };
 
     let mut rooms = state.rooms.lock().unwrap();
     if let Some(room) = rooms.get_mut(room_code) {
-        // Parse input data from client
-        match serde_json::from_slice::<InputData>(&data) {
-            Ok(input_data) => {
-                info!(
-                    "✅ Successfully parsed input: thrust={}, turn={}, primary_fire={}, secondary_fire={}",
-                    input_data.thrust,
-                    input_data.turn,
-                    input_data.primary_fire,
-                    input_data.secondary_fire
-                );
-
-                // Add input to simulation
-                room.simulation.add_player_input(player_id, input_data);
-                info!("📨 Input added to simulation for player {}", player_id);
-            }
-            Err(e) => {
-                warn!(
-                    "❌ Failed to parse input data for player {}: {}",
-                    player_id, e
-                );
-                warn!("Raw data: {:?}", String::from_utf8_lossy(&data));
+        // Oldest first; inputs the server already has are dropped by sequence
+        for input in inputs {
+            trace!(
+                sequence = input.sequence,
+                thrust = input.thrust,
+                turn = input.turn,
+                primary_fire = input.primary_fire,
+                secondary_fire = input.secondary_fire,
+                "Received input"
+            );
+            room.metrics.inputs_received += 1;
+            match room.simulation.add_player_input(player_id, input) {
+                InputOutcome::Buffered => {}
+                InputOutcome::Duplicate => room.metrics.inputs_duplicate += 1,
+                InputOutcome::NoShip => room.metrics.inputs_dropped += 1,
             }
         }
     } else {
         warn!(
-            "❌ Room {} not found for input from player {}",
+            "Room {} not found for input from player {}",
             room_code, player_id
         );
     }
 }
 
-async fn handle_debug_request(state: &AppState, room_code: &str, player_id: Uuid, timestamp: u64) {
-    info!(
-        "🔍 Received debug render request from player {} in room {}",
+async fn handle_debug_request(state: &AppState, room_code: &str, player_id: Uuid) {
+    debug!(
+        "Received debug render request from player {} in room {}",
         player_id, room_code
     );
 
@@ -619,63 +1708,88 @@ async fn handle_debug_request(state: &AppState, room_code: &str, player_id: Uuid
     if let Some(room) = rooms.get_mut(room_code) {
         // Generate debug render data from the simulation
         let debug_data = room.simulation.generate_debug_render_data();
+        let (sequence, bodies, colliders) = (
+            debug_data.sequence,
+            debug_data.rigid_bodies.len(),
+            debug_data.colliders.len(),
+        );
 
-        // Serialize debug data to JSON bytes
-        match serde_json::to_vec(&debug_data) {
-            Ok(debug_bytes) => {
-                let debug_msg = ServerMessage::DebugRender {
-                    sequence: debug_data.sequence,
-                    timestamp,
-                    data: debug_bytes,
-                };
-
-                // Send debug data to the requesting player
-                if let Some(player) = room.players.get(&player_id) {
-                    match player.sender.send(debug_msg) {
-                        Ok(_) => {
-                            info!(
-                                "✅ Sent debug render data to player {} (sequence: {}, bodies: {}, colliders: {})",
-                                player_id,
-                                debug_data.sequence,
-                                debug_data.rigid_bodies.len(),
-                                debug_data.colliders.len()
-                            );
-                        }
-                        Err(e) => {
-                            warn!(
-                                "❌ Failed to send debug render data to player {}: {}",
-                                player_id, e
-                            );
-                        }
-                    }
-                } else {
+        // Send debug data to the requesting player
+        if let Some(player) = room.players.get(&player_id) {
+            match player.sender.send(ServerMessage::DebugRender(debug_data)) {
+                Ok(_) => {
+                    debug!(
+                        "Sent debug render data to player {} (sequence: {}, bodies: {}, colliders: {})",
+                        player_id, sequence, bodies, colliders
+                    );
+                }
+                Err(e) => {
                     warn!(
-                        "❌ Player {} not found in room {} for debug request",
-                        player_id, room_code
+                        "Failed to send debug render data to player {}: {}",
+                        player_id, e
                     );
                 }
             }
-            Err(e) => {
-                warn!(
-                    "❌ Failed to serialize debug render data for player {}: {}",
-                    player_id, e
-                );
-            }
+        } else {
+            warn!(
+                "Player {} not found in room {} for debug request",
+                player_id, room_code
+            );
         }
     } else {
         warn!(
-            "❌ Room {} not found for debug request from player {}",
+            "Room {} not found for debug request from player {}",
             room_code, player_id
         );
     }
 }
 
-async fn cleanup_rooms_task(rooms: &SharedRooms) {
-    let mut rooms_guard = rooms.lock().unwrap();
+/// Poll the content directory and push valid changes into every live room
+async fn watch_content_task(rooms: SharedRooms, content: SharedContent, dir: PathBuf) {
+    let mut last_modified = ContentRegistry::modified(&dir);
+    let mut interval = time::interval(Duration::from_secs(2));
+
+    loop {
+        interval.tick().await;
+
+        let modified = ContentRegistry::modified(&dir);
+        if modified == last_modified {
+            continue;
+        }
+        last_modified = modified;
+
+        let reloaded = match ContentRegistry::load(&dir) {
+            Ok(reloaded) => Arc::new(reloaded),
+            Err(e) => {
+                warn!("Rejected content reload, keeping current content:\n{}", e);
+                continue;
+            }
+        };
+
+        *content.lock().unwrap() = reloaded.clone();
+
+        let mut rooms_guard = rooms.lock().unwrap();
+        for room in rooms_guard.values_mut() {
+            room.simulation.queue_content(reloaded.clone());
+        }
+        info!(
+            "Reloaded content from {}, queued for {} rooms",
+            dir.display(),
+            rooms_guard.len()
+        );
+    }
+}
+
+async fn cleanup_rooms_task(state: &AppState) {
+    let mut rooms_guard = state.rooms.lock().unwrap();
     let mut rooms_to_remove = Vec::new();
 
     for (room_code, room) in rooms_guard.iter_mut() {
-        room.cleanup_inactive_players();
+        let mut departed = room.cleanup_inactive_players();
+        departed.extend(room.expire_disconnected());
+        for (name, result) in departed {
+            record_match_result(state, name, result);
+        }
 
         // Remove rooms that have been empty for more than 5 minutes
         if room.is_empty() && room.last_activity.elapsed() > Duration::from_secs(300) {
@@ -684,7 +1798,10 @@ async fn cleanup_rooms_task(rooms: &SharedRooms) {
     }
 
     for room_code in rooms_to_remove {
-        rooms_guard.remove(&room_code);
+        if let Some(mut room) = rooms_guard.remove(&room_code) {
+            save_replay(state.replays.as_ref(), &mut room);
+            METRICS.rooms_closed.fetch_add(1, Ordering::Relaxed);
+        }
         info!("Cleaned up empty room: {}", room_code);
     }
 }
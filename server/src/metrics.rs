@@ -0,0 +1,456 @@
+//! Prometheus metrics
+//!
+//! Server-wide counters are process-wide atomics, like the outbound queue
+//! counters in [`crate::outbound::METRICS`]. Per-room figures (tick times,
+//! snapshot sizes, entity and input counts) live in each room's
+//! [`RoomMetrics`] and are updated under the room lock by its simulation loop
+//! and input handler. `GET /metrics` renders both in the Prometheus text
+//! exposition format; a room's series disappear when it closes.
+
+use crate::outbound;
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Content type of the text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Tick duration bucket bounds in seconds; at `SIM_TICK_RATE` (15 Hz) a tick
+/// has a 66.7 ms budget, so the bucket above it counts overruns
+const TICK_SECONDS_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.02, 0.033, 0.05, 0.0667, 0.1,
+];
+
+/// Packed snapshot size bucket bounds in bytes
+const SNAPSHOT_BYTES_BUCKETS: &[f64] = &[
+    64.0, 128.0, 256.0, 512.0, 1024.0, 2048.0, 4096.0, 8192.0, 16384.0,
+];
+
+/// Server-wide counters
+#[derive(Debug, Default)]
+pub struct ServerMetrics {
+    pub rooms_created: AtomicU64,
+    pub rooms_restored: AtomicU64,
+    pub rooms_closed: AtomicU64,
+    pub connections_opened: AtomicU64,
+    pub connections_closed: AtomicU64,
+    pub malformed_input_packets: AtomicU64,
+}
+
+impl ServerMetrics {
+    const fn new() -> Self {
+        Self {
+            rooms_created: AtomicU64::new(0),
+            rooms_restored: AtomicU64::new(0),
+            rooms_closed: AtomicU64::new(0),
+            connections_opened: AtomicU64::new(0),
+            connections_closed: AtomicU64::new(0),
+            malformed_input_packets: AtomicU64::new(0),
+        }
+    }
+}
+
+pub static METRICS: ServerMetrics = ServerMetrics::new();
+
+/// Observations counted into fixed buckets
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>, // per bucket, not cumulative
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        if let Some(bucket) = self.bounds.iter().position(|bound| value <= *bound) {
+            self.counts[bucket] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// Figures collected by one room
+#[derive(Debug, Clone)]
+pub struct RoomMetrics {
+    pub tick_seconds: Histogram,
+    pub snapshot_bytes: Histogram,
+    pub entities: u32, // as of the last tick
+    pub inputs_received: u64,
+    pub inputs_duplicate: u64, // resends of inputs already buffered or processed
+    pub inputs_dropped: u64,   // refused, such as for a missing ship
+}
+
+impl Default for RoomMetrics {
+    fn default() -> Self {
+        Self {
+            tick_seconds: Histogram::new(TICK_SECONDS_BUCKETS),
+            snapshot_bytes: Histogram::new(SNAPSHOT_BYTES_BUCKETS),
+            entities: 0,
+            inputs_received: 0,
+            inputs_duplicate: 0,
+            inputs_dropped: 0,
+        }
+    }
+}
+
+/// What `/metrics` reports about one room
+pub struct RoomSample<'a> {
+    pub code: &'a str,
+    pub players: usize,
+    pub disconnected: usize,
+    pub metrics: &'a RoomMetrics,
+}
+
+/// Render every metric in the text exposition format
+pub fn render(rooms: &[RoomSample]) -> String {
+    let mut out = Exposition::default();
+    let counter = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+    out.family("cosmic_rooms", "gauge", "Rooms currently open");
+    out.sample("cosmic_rooms", "", rooms.len());
+    out.family(
+        "cosmic_rooms_created_total",
+        "counter",
+        "Rooms created through /create-room",
+    );
+    out.sample(
+        "cosmic_rooms_created_total",
+        "",
+        counter(&METRICS.rooms_created),
+    );
+    out.family(
+        "cosmic_rooms_restored_total",
+        "counter",
+        "Rooms restored from checkpoints at startup",
+    );
+    out.sample(
+        "cosmic_rooms_restored_total",
+        "",
+        counter(&METRICS.rooms_restored),
+    );
+    out.family("cosmic_rooms_closed_total", "counter", "Rooms closed");
+    out.sample(
+        "cosmic_rooms_closed_total",
+        "",
+        counter(&METRICS.rooms_closed),
+    );
+
+    let opened = counter(&METRICS.connections_opened);
+    let closed = counter(&METRICS.connections_closed);
+    out.family(
+        "cosmic_connections",
+        "gauge",
+        "WebSocket connections currently open",
+    );
+    out.sample("cosmic_connections", "", opened.saturating_sub(closed));
+    out.family(
+        "cosmic_connections_opened_total",
+        "counter",
+        "WebSocket connections accepted",
+    );
+    out.sample("cosmic_connections_opened_total", "", opened);
+
+    out.family(
+        "cosmic_input_packets_malformed_total",
+        "counter",
+        "Input packets that failed to decode",
+    );
+    out.sample(
+        "cosmic_input_packets_malformed_total",
+        "",
+        counter(&METRICS.malformed_input_packets),
+    );
+
+    let outbound = &outbound::METRICS;
+    out.family(
+        "cosmic_outbound_messages_total",
+        "counter",
+        "Messages sent to clients",
+    );
+    out.sample(
+        "cosmic_outbound_messages_total",
+        "",
+        counter(&outbound.messages_sent),
+    );
+    out.family(
+        "cosmic_outbound_bytes_total",
+        "counter",
+        "Bytes sent to clients",
+    );
+    out.sample(
+        "cosmic_outbound_bytes_total",
+        "",
+        counter(&outbound.bytes_sent),
+    );
+    out.family(
+        "cosmic_outbound_dropped_total",
+        "counter",
+        "Queued messages replaced or dropped before sending",
+    );
+    out.sample(
+        "cosmic_outbound_dropped_total",
+        "{priority=\"normal\"}",
+        counter(&outbound.snapshots_coalesced),
+    );
+    out.sample(
+        "cosmic_outbound_dropped_total",
+        "{priority=\"high\"}",
+        counter(&outbound.high_dropped),
+    );
+    out.sample(
+        "cosmic_outbound_dropped_total",
+        "{priority=\"low\"}",
+        counter(&outbound.low_dropped),
+    );
+    out.family(
+        "cosmic_outbound_overflow_closes_total",
+        "counter",
+        "Connections closed because their critical messages backed up",
+    );
+    out.sample(
+        "cosmic_outbound_overflow_closes_total",
+        "",
+        counter(&outbound.overflow_closes),
+    );
+
+    out.family(
+        "cosmic_room_players",
+        "gauge",
+        "Players in a room by connection state",
+    );
+    for room in rooms {
+        out.sample(
+            "cosmic_room_players",
+            &format!("{{room=\"{}\",state=\"connected\"}}", escape(room.code)),
+            room.players,
+        );
+        out.sample(
+            "cosmic_room_players",
+            &format!("{{room=\"{}\",state=\"disconnected\"}}", escape(room.code)),
+            room.disconnected,
+        );
+    }
+    out.family(
+        "cosmic_room_entities",
+        "gauge",
+        "Entities in a room's world",
+    );
+    for room in rooms {
+        out.sample(
+            "cosmic_room_entities",
+            &format!("{{room=\"{}\"}}", escape(room.code)),
+            room.metrics.entities,
+        );
+    }
+    out.family(
+        "cosmic_room_inputs_received_total",
+        "counter",
+        "Inputs received, including resends",
+    );
+    for room in rooms {
+        out.sample(
+            "cosmic_room_inputs_received_total",
+            &format!("{{room=\"{}\"}}", escape(room.code)),
+            room.metrics.inputs_received,
+        );
+    }
+    out.family(
+        "cosmic_room_inputs_duplicate_total",
+        "counter",
+        "Inputs already received, from the resends in every packet",
+    );
+    for room in rooms {
+        out.sample(
+            "cosmic_room_inputs_duplicate_total",
+            &format!("{{room=\"{}\"}}", escape(room.code)),
+            room.metrics.inputs_duplicate,
+        );
+    }
+    out.family(
+        "cosmic_room_inputs_dropped_total",
+        "counter",
+        "Inputs refused, such as for a player without a ship",
+    );
+    for room in rooms {
+        out.sample(
+            "cosmic_room_inputs_dropped_total",
+            &format!("{{room=\"{}\"}}", escape(room.code)),
+            room.metrics.inputs_dropped,
+        );
+    }
+
+    out.family(
+        "cosmic_room_tick_seconds",
+        "histogram",
+        "Time taken by each simulation tick",
+    );
+    for room in rooms {
+        out.histogram(
+            "cosmic_room_tick_seconds",
+            room.code,
+            &room.metrics.tick_seconds,
+        );
+    }
+    out.family(
+        "cosmic_room_snapshot_bytes",
+        "histogram",
+        "Packed entity state per player snapshot",
+    );
+    for room in rooms {
+        out.histogram(
+            "cosmic_room_snapshot_bytes",
+            room.code,
+            &room.metrics.snapshot_bytes,
+        );
+    }
+
+    out.text
+}
+
+/// Escape a label value as the exposition format requires
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[derive(Default)]
+struct Exposition {
+    text: String,
+}
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.text, "# HELP {} {}", name, help);
+        let _ = writeln!(self.text, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &str, value: impl std::fmt::Display) {
+        let _ = writeln!(self.text, "{}{} {}", name, labels, value);
+    }
+
+    fn histogram(&mut self, name: &str, room_code: &str, histogram: &Histogram) {
+        let room_code = escape(room_code);
+        let mut cumulative = 0;
+        for (bound, count) in histogram.bounds.iter().zip(&histogram.counts) {
+            cumulative += count;
+            self.sample(
+                &format!("{}_bucket", name),
+                &format!("{{room=\"{}\",le=\"{}\"}}", room_code, bound),
+                cumulative,
+            );
+        }
+        self.sample(
+            &format!("{}_bucket", name),
+            &format!("{{room=\"{}\",le=\"+Inf\"}}", room_code),
+            histogram.count,
+        );
+        let labels = format!("{{room=\"{}\"}}", room_code);
+        self.sample(&format!("{}_sum", name), &labels, histogram.sum);
+        self.sample(&format!("{}_count", name), &labels, histogram.count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_lines<'a>(text: &'a str, prefix: &str) -> Vec<&'a str> {
+        text.lines()
+            .filter(|line| line.starts_with(prefix))
+            .collect()
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let mut metrics = RoomMetrics::default();
+        for seconds in [0.0004, 0.004, 0.004, 0.07, 0.5] {
+            metrics.tick_seconds.observe(seconds);
+        }
+        let text = render(&[RoomSample {
+            code: "ROOM1234",
+            players: 1,
+            disconnected: 0,
+            metrics: &metrics,
+        }]);
+
+        let buckets = sample_lines(&text, "cosmic_room_tick_seconds_bucket");
+        assert_eq!(buckets.len(), TICK_SECONDS_BUCKETS.len() + 1);
+        assert_eq!(
+            buckets[0],
+            "cosmic_room_tick_seconds_bucket{room=\"ROOM1234\",le=\"0.0005\"} 1"
+        );
+        assert!(
+            buckets.contains(&"cosmic_room_tick_seconds_bucket{room=\"ROOM1234\",le=\"0.005\"} 3")
+        );
+        assert!(
+            buckets.contains(&"cosmic_room_tick_seconds_bucket{room=\"ROOM1234\",le=\"0.0667\"} 3")
+        );
+        assert!(
+            buckets.contains(&"cosmic_room_tick_seconds_bucket{room=\"ROOM1234\",le=\"0.1\"} 4")
+        );
+        assert_eq!(
+            buckets.last().unwrap(),
+            &"cosmic_room_tick_seconds_bucket{room=\"ROOM1234\",le=\"+Inf\"} 5"
+        );
+
+        let counts: Vec<u64> = buckets
+            .iter()
+            .map(|line| line.rsplit(' ').next().unwrap().parse().unwrap())
+            .collect();
+        assert!(counts.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        assert_eq!(
+            sample_lines(&text, "cosmic_room_tick_seconds_count"),
+            ["cosmic_room_tick_seconds_count{room=\"ROOM1234\"} 5"]
+        );
+        let sum = sample_lines(&text, "cosmic_room_tick_seconds_sum")[0];
+        let sum: f64 = sum.rsplit(' ').next().unwrap().parse().unwrap();
+        assert!((sum - 0.5784).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tick_budget_is_a_bucket_edge() {
+        let budget = 1.0 / crate::simulation::SIM_TICK_RATE as f64;
+        assert!(
+            TICK_SECONDS_BUCKETS
+                .iter()
+                .any(|bound| (bound - budget).abs() < 0.0001)
+        );
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        let metrics = RoomMetrics::default();
+        let text = render(&[RoomSample {
+            code: "a\"b\\c\nd",
+            players: 2,
+            disconnected: 1,
+            metrics: &metrics,
+        }]);
+
+        assert!(
+            text.contains("cosmic_room_players{room=\"a\\\"b\\\\c\\nd\",state=\"connected\"} 2")
+        );
+        assert!(text.contains("cosmic_room_entities{room=\"a\\\"b\\\\c\\nd\"} 0"));
+        assert!(text.contains("cosmic_room_snapshot_bytes_count{room=\"a\\\"b\\\\c\\nd\"} 0"));
+        // Every sample stays on its own line
+        assert!(
+            text.lines()
+                .all(|line| line.starts_with('#') || line.starts_with("cosmic_"))
+        );
+    }
+}
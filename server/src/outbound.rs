@@ -0,0 +1,596 @@
+//! Per-connection outbound message scheduling
+//!
+//! Each connection queues its messages by [`MessagePriority`] instead of
+//! pushing them through a fixed-size channel:
+//! - Critical messages (handshake, roster changes, errors) are never dropped;
+//!   a client that lets more than [`CRITICAL_CAPACITY_BYTES`] of them back up
+//!   is disconnected instead
+//! - High priority messages keep their order, dropping the oldest past a bound
+//! - Gameplay events and entity info keep their order and are never dropped;
+//!   more than [`RELIABLE_CAPACITY`] waiting disconnects the client as well
+//! - Snapshots are coalesced, so only the newest one is ever waiting
+//! - Low priority data (debug render, leaderboards) goes out last and is dropped first
+//!
+//! The send task drains the queue through a token bucket, so a client never
+//! receives more than its configured bytes per second. Critical messages are
+//! exempt from the budget but still spend from it. They are encoded as soon
+//! as they are queued, which is also how their backlog is measured, and go
+//! out in the encoding the connection had then.
+
+use crate::protocol::{EncodedMessage, Encoding, ErrorCode, MessagePriority, ServerMessage};
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::Notify;
+
+/// Default per-connection budget in bytes per second
+pub const DEFAULT_BANDWIDTH: u64 = 256 * 1024;
+
+/// Queued high priority messages kept before the oldest is dropped
+const HIGH_CAPACITY: usize = 64;
+
+/// Queued low priority messages kept before the oldest is dropped
+const LOW_CAPACITY: usize = 8;
+
+/// Queued event and entity info messages a connection may build up before it
+/// is closed as too slow
+const RELIABLE_CAPACITY: usize = 256;
+
+/// Queued critical bytes a connection may build up before it is closed as too slow
+const CRITICAL_CAPACITY_BYTES: usize = 1024 * 1024;
+
+/// Server-wide outbound counters
+#[derive(Debug, Default)]
+pub struct OutboundMetrics {
+    pub messages_sent: AtomicU64,
+    pub bytes_sent: AtomicU64,
+    pub snapshots_coalesced: AtomicU64,
+    pub high_dropped: AtomicU64,
+    pub low_dropped: AtomicU64,
+    pub overflow_closes: AtomicU64,
+}
+
+impl OutboundMetrics {
+    const fn new() -> Self {
+        Self {
+            messages_sent: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            snapshots_coalesced: AtomicU64::new(0),
+            high_dropped: AtomicU64::new(0),
+            low_dropped: AtomicU64::new(0),
+            overflow_closes: AtomicU64::new(0),
+        }
+    }
+}
+
+pub static METRICS: OutboundMetrics = OutboundMetrics::new();
+
+/// What the send task should do next
+#[derive(Debug)]
+pub enum Outbound {
+    Message(ServerMessage),
+    /// A critical message, encoded when it was queued
+    Encoded(EncodedMessage),
+    /// Nothing more will be sent; close the socket, with a close code if given
+    Close(Option<u16>),
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    encoding: Option<Encoding>, // JSON until the handshake settles on something else
+    critical: VecDeque<EncodedMessage>,
+    critical_bytes: usize,
+    high: VecDeque<ServerMessage>,
+    reliable: VecDeque<ServerMessage>,
+    snapshot: Option<ServerMessage>, // newest normal priority message
+    low: VecDeque<ServerMessage>,
+    closing: Option<Option<u16>>,
+    dropped: u64, // messages this connection never received
+}
+
+impl QueueState {
+    fn encoding(&self) -> Encoding {
+        self.encoding.unwrap_or(Encoding::Json)
+    }
+
+    fn pop_critical(&mut self) -> Option<EncodedMessage> {
+        let message = self.critical.pop_front()?;
+        self.critical_bytes -= message.len();
+        Some(message)
+    }
+
+    /// Throw away everything queued and close with an error the client can read
+    fn overflow(&mut self) {
+        let discarded = self.critical.len()
+            + self.high.len()
+            + self.reliable.len()
+            + self.snapshot.is_some() as usize
+            + self.low.len();
+        self.dropped += discarded as u64;
+        *self = QueueState {
+            encoding: self.encoding,
+            dropped: self.dropped,
+            ..QueueState::default()
+        };
+
+        let error = ServerMessage::Error {
+            code: ErrorCode::SendQueueOverflow,
+            message: "Too many messages waiting to be sent; reconnect to resume".to_string(),
+        };
+        if let Ok(error) = self.encoding().encode_message(&error) {
+            self.critical_bytes = error.len();
+            self.critical.push_back(error);
+        }
+        self.closing = Some(ErrorCode::SendQueueOverflow.close_code());
+        METRICS.overflow_closes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn has_budgeted(&self) -> bool {
+        !self.high.is_empty()
+            || !self.reliable.is_empty()
+            || self.snapshot.is_some()
+            || !self.low.is_empty()
+    }
+
+    fn pop_budgeted(&mut self) -> Option<ServerMessage> {
+        self.high
+            .pop_front()
+            .or_else(|| self.reliable.pop_front())
+            .or_else(|| self.snapshot.take())
+            .or_else(|| self.low.pop_front())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<QueueState>,
+    notify: Notify,
+}
+
+/// Create the queue for one connection
+pub fn channel(bytes_per_sec: u64) -> (OutboundSender, OutboundReceiver) {
+    let shared = Arc::new(Shared::default());
+    (
+        OutboundSender {
+            shared: shared.clone(),
+        },
+        OutboundReceiver {
+            shared,
+            bucket: TokenBucket::new(bytes_per_sec),
+        },
+    )
+}
+
+/// Handle for queueing messages to a connection
+#[derive(Debug, Clone)]
+pub struct OutboundSender {
+    shared: Arc<Shared>,
+}
+
+impl OutboundSender {
+    /// Queue a message; fails once the connection has closed
+    pub fn send(&self, message: ServerMessage) -> Result<(), String> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.closing.is_some() {
+            return Err("connection closed".to_string());
+        }
+
+        match message.priority() {
+            MessagePriority::Critical => {
+                // Welcome announces the encoding, so it always goes out as JSON
+                let encoding = match message {
+                    ServerMessage::Welcome { .. } => Encoding::Json,
+                    _ => state.encoding(),
+                };
+                let message = encoding.encode_message(&message)?;
+                if state.critical_bytes + message.len() > CRITICAL_CAPACITY_BYTES {
+                    return self.overflow(state);
+                }
+                state.critical_bytes += message.len();
+                state.critical.push_back(message);
+            }
+            MessagePriority::High => {
+                if state.high.len() >= HIGH_CAPACITY {
+                    state.high.pop_front();
+                    state.dropped += 1;
+                    METRICS.high_dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                state.high.push_back(message);
+            }
+            MessagePriority::Reliable => {
+                if state.reliable.len() >= RELIABLE_CAPACITY {
+                    return self.overflow(state);
+                }
+                state.reliable.push_back(message);
+            }
+            MessagePriority::Normal => {
+                if state.snapshot.replace(message).is_some() {
+                    state.dropped += 1;
+                    METRICS.snapshots_coalesced.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            MessagePriority::Low => {
+                if state.low.len() >= LOW_CAPACITY {
+                    state.low.pop_front();
+                    state.dropped += 1;
+                    METRICS.low_dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                state.low.push_back(message);
+            }
+        }
+        drop(state);
+
+        self.shared.notify.notify_one();
+        Ok(())
+    }
+
+    /// Encode messages queued from now on in `encoding`
+    pub fn set_encoding(&self, encoding: Encoding) {
+        self.shared.state.lock().unwrap().encoding = Some(encoding);
+    }
+
+    fn overflow(&self, mut state: MutexGuard<QueueState>) -> Result<(), String> {
+        state.overflow();
+        drop(state);
+        self.shared.notify.notify_one();
+        Err("send queue overflowed".to_string())
+    }
+
+    /// Stop accepting messages; the receiver drains critical messages, then closes
+    pub fn close(&self, code: Option<u16>) {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.closing.is_none() {
+            state.closing = Some(code);
+        }
+        drop(state);
+
+        self.shared.notify.notify_one();
+    }
+}
+
+/// The send task's end of a connection queue
+pub struct OutboundReceiver {
+    shared: Arc<Shared>,
+    bucket: TokenBucket,
+}
+
+impl OutboundReceiver {
+    /// Wait for the next message the budget allows, or for the queue to close
+    pub async fn recv(&mut self) -> Outbound {
+        loop {
+            let wait = {
+                let mut state = self.shared.state.lock().unwrap();
+                if let Some(message) = state.pop_critical() {
+                    return Outbound::Encoded(message);
+                }
+                if let Some(code) = state.closing {
+                    return Outbound::Close(code);
+                }
+                if state.has_budgeted() {
+                    let wait = self.bucket.wait_time();
+                    if wait.is_zero()
+                        && let Some(message) = state.pop_budgeted()
+                    {
+                        return Outbound::Message(message);
+                    }
+                    Some(wait)
+                } else {
+                    None
+                }
+            };
+
+            match wait {
+                // Over budget: wake when tokens refill, or early for critical messages
+                Some(wait) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep(wait) => {}
+                        _ = self.shared.notify.notified() => {}
+                    }
+                }
+                None => self.shared.notify.notified().await,
+            }
+        }
+    }
+
+    /// Charge a sent message against the budget
+    pub fn record_sent(&mut self, bytes: usize) {
+        self.bucket.spend(bytes as f64);
+        METRICS.messages_sent.fetch_add(1, Ordering::Relaxed);
+        METRICS
+            .bytes_sent
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Encoding for the messages returned as [`Outbound::Message`], or `None`
+    /// until the handshake picked one
+    pub fn encoding(&self) -> Option<Encoding> {
+        self.shared.state.lock().unwrap().encoding
+    }
+
+    /// Messages this connection has had dropped or coalesced away
+    pub fn dropped(&self) -> u64 {
+        self.shared.state.lock().unwrap().dropped
+    }
+}
+
+/// Bytes-per-second limiter; a rate of 0 means unlimited
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    tokens: f64, // may go negative after a large message
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            rate: bytes_per_sec as f64,
+            tokens: bytes_per_sec as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Refill, capped at one second of budget
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last_refill = now;
+    }
+
+    fn wait_time(&mut self) -> Duration {
+        if self.rate == 0.0 {
+            return Duration::ZERO;
+        }
+        self.refill();
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+
+    fn spend(&mut self, bytes: f64) {
+        if self.rate > 0.0 {
+            self.refill();
+            self.tokens -= bytes;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{DebugRenderData, Frame, WireData};
+    use crate::snapshot_codec::{Arena, PackedSnapshot};
+
+    fn critical(player_id: &str) -> ServerMessage {
+        ServerMessage::PlayerLeft {
+            player_id: player_id.to_string(),
+        }
+    }
+
+    fn high() -> ServerMessage {
+        ServerMessage::Probe { server_time: 1 }
+    }
+
+    fn snapshot(tick: u64) -> ServerMessage {
+        ServerMessage::Snapshot(PackedSnapshot {
+            sequence: tick as u32,
+            tick,
+            timestamp: 0,
+            arena: Arena {
+                min: [0.0, 0.0],
+                max: [1.0, 1.0],
+            },
+            data: Vec::new(),
+            deferred: Vec::new(),
+        })
+    }
+
+    fn low() -> ServerMessage {
+        ServerMessage::DebugRender(DebugRenderData {
+            sequence: 0,
+            rigid_bodies: Vec::new(),
+            colliders: Vec::new(),
+            forces: Vec::new(),
+            velocities: Vec::new(),
+            joints: Vec::new(),
+        })
+    }
+
+    fn events(tick: u64) -> ServerMessage {
+        ServerMessage::Events {
+            tick,
+            events: Vec::new(),
+        }
+    }
+
+    async fn next(rx: &mut OutboundReceiver) -> ServerMessage {
+        match rx.recv().await {
+            Outbound::Message(message) => message,
+            Outbound::Encoded(message) => {
+                let frame: Frame<ServerMessage> =
+                    Encoding::decode(&message.frame(1).unwrap()).unwrap();
+                frame.message
+            }
+            Outbound::Close(code) => panic!("queue closed with {:?}", code),
+        }
+    }
+
+    #[tokio::test]
+    async fn drains_by_priority() {
+        let (tx, mut rx) = channel(0);
+        tx.send(low()).unwrap();
+        tx.send(snapshot(1)).unwrap();
+        tx.send(events(1)).unwrap();
+        tx.send(high()).unwrap();
+        tx.send(critical("a")).unwrap();
+
+        assert!(matches!(
+            next(&mut rx).await,
+            ServerMessage::PlayerLeft { .. }
+        ));
+        assert!(matches!(next(&mut rx).await, ServerMessage::Probe { .. }));
+        assert!(matches!(next(&mut rx).await, ServerMessage::Events { .. }));
+        assert!(matches!(next(&mut rx).await, ServerMessage::Snapshot(_)));
+        assert!(matches!(next(&mut rx).await, ServerMessage::DebugRender(_)));
+        assert_eq!(rx.dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn coalesces_snapshots() {
+        let (tx, mut rx) = channel(0);
+        for tick in 1..=3 {
+            tx.send(snapshot(tick)).unwrap();
+        }
+
+        match next(&mut rx).await {
+            ServerMessage::Snapshot(snapshot) => assert_eq!(snapshot.tick, 3),
+            other => panic!("expected a snapshot, got {:?}", other),
+        }
+        assert_eq!(rx.dropped(), 2);
+    }
+
+    #[tokio::test]
+    async fn drains_critical_messages_before_closing() {
+        let (tx, mut rx) = channel(0);
+        tx.send(critical("a")).unwrap();
+        tx.send(snapshot(1)).unwrap();
+        tx.close(Some(1001));
+
+        assert!(matches!(
+            next(&mut rx).await,
+            ServerMessage::PlayerLeft { .. }
+        ));
+        assert!(matches!(rx.recv().await, Outbound::Close(Some(1001))));
+        assert!(tx.send(critical("b")).is_err());
+    }
+
+    #[tokio::test]
+    async fn closes_when_critical_messages_back_up() {
+        let (tx, mut rx) = channel(0);
+        let message = critical(&"x".repeat(1024));
+        let size = Encoding::Json.encode_message(&message).unwrap().len();
+        let fits = CRITICAL_CAPACITY_BYTES / size;
+        for _ in 0..fits {
+            tx.send(message.clone()).unwrap();
+        }
+        tx.send(snapshot(1)).unwrap();
+        assert!(tx.send(message.clone()).is_err());
+        assert!(tx.send(critical("late")).is_err());
+
+        // The backlog is discarded in favour of a single error
+        match next(&mut rx).await {
+            ServerMessage::Error { code, .. } => assert_eq!(code, ErrorCode::SendQueueOverflow),
+            other => panic!("expected an error, got {:?}", other),
+        }
+        assert!(matches!(rx.recv().await, Outbound::Close(Some(4003))));
+        assert_eq!(rx.dropped(), fits as u64 + 1);
+    }
+
+    #[tokio::test]
+    async fn events_wait_for_the_budget() {
+        let (tx, mut rx) = channel(1000);
+        rx.record_sent(1500);
+        for tick in 1..=3 {
+            tx.send(events(tick)).unwrap();
+        }
+        tx.send(critical("a")).unwrap();
+
+        // Critical messages skip the budget; events wait for it, in order
+        assert!(matches!(
+            next(&mut rx).await,
+            ServerMessage::PlayerLeft { .. }
+        ));
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), rx.recv())
+                .await
+                .is_err()
+        );
+        for tick in 1..=3 {
+            rx.bucket.tokens = 1000.0;
+            match next(&mut rx).await {
+                ServerMessage::Events { tick: got, .. } => assert_eq!(got, tick),
+                other => panic!("expected events, got {:?}", other),
+            }
+        }
+        assert_eq!(rx.dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn closes_when_events_back_up() {
+        let (tx, mut rx) = channel(1000);
+        rx.record_sent(1500);
+        for tick in 0..RELIABLE_CAPACITY as u64 {
+            tx.send(events(tick)).unwrap();
+        }
+        assert!(tx.send(events(0)).is_err());
+
+        match next(&mut rx).await {
+            ServerMessage::Error { code, .. } => assert_eq!(code, ErrorCode::SendQueueOverflow),
+            other => panic!("expected an error, got {:?}", other),
+        }
+        assert!(matches!(rx.recv().await, Outbound::Close(Some(4003))));
+        assert_eq!(rx.dropped(), RELIABLE_CAPACITY as u64);
+    }
+
+    #[tokio::test]
+    async fn critical_messages_use_the_negotiated_encoding() {
+        let (tx, mut rx) = channel(0);
+        tx.send(critical("a")).unwrap();
+        tx.set_encoding(Encoding::MessagePack);
+        tx.send(ServerMessage::Welcome {
+            protocol_version: 1,
+            encoding: Encoding::MessagePack,
+            features: Vec::new(),
+            tick_rate: 15.0,
+            snapshot_rate: 15.0,
+        })
+        .unwrap();
+        tx.send(critical("b")).unwrap();
+
+        let mut frames = Vec::new();
+        for _ in 0..3 {
+            match rx.recv().await {
+                Outbound::Encoded(message) => frames.push(message.frame(1).unwrap()),
+                other => panic!("expected an encoded message, got {:?}", other),
+            }
+        }
+        assert!(matches!(frames[0], WireData::Text(_)));
+        // Welcome announces the encoding, so the client must be able to read it as JSON
+        assert!(matches!(frames[1], WireData::Text(_)));
+        assert!(matches!(frames[2], WireData::Binary(_)));
+        assert_eq!(rx.encoding(), Some(Encoding::MessagePack));
+    }
+
+    #[test]
+    fn token_bucket_waits_out_overspending() {
+        let mut bucket = TokenBucket::new(1000);
+        assert!(bucket.wait_time().is_zero());
+
+        bucket.spend(1500.0);
+        let wait = bucket.wait_time();
+        assert!(wait > Duration::from_millis(490) && wait <= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn token_bucket_refills_at_most_one_second() {
+        let mut bucket = TokenBucket::new(1000);
+        bucket.last_refill -= Duration::from_secs(10);
+        bucket.refill();
+        assert_eq!(bucket.tokens, 1000.0);
+    }
+
+    #[test]
+    fn unlimited_bucket_never_waits() {
+        let mut bucket = TokenBucket::new(0);
+        bucket.spend(1_000_000.0);
+        assert!(bucket.wait_time().is_zero());
+    }
+}
@@ -0,0 +1,426 @@
+//! Persistent player profiles
+//!
+//! Profiles are created automatically the first time a name joins and are
+//! keyed by the normalized player name, so "BoneCrusher" and "bonecrusher"
+//! share one profile. Reads happen synchronously on join; writes are queued
+//! to a background task that merges per-player match results and flushes them
+//! to redb at most once per [`FLUSH_INTERVAL`].
+
+use redb::{Database, ReadableTable, TableDefinition};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, hash_map::Entry},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time,
+};
+use tracing::{debug, error, info};
+
+/// Profiles table: normalized player name -> JSON encoded PlayerProfile
+const PROFILES: TableDefinition<&str, &[u8]> = TableDefinition::new("profiles");
+
+/// Minimum time between batched profile writes
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Everything that persists about a player between matches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct PlayerProfile {
+    pub name: String,
+    pub created_at: u64, // unix seconds
+    pub last_seen: u64,  // unix seconds
+    pub matches_played: u32,
+    pub lifetime_kills: u32,
+    pub lifetime_deaths: u32,
+    pub lifetime_score: u64,
+    pub best_score: u32,
+    pub credits: u32,
+    pub last_ship_class: Option<String>,
+    pub unlocked_ships: BTreeSet<String>,
+    pub unlocked_weapons: BTreeSet<String>,
+    pub upgrades: BTreeMap<String, u32>, // upgrade id -> level
+}
+
+impl PlayerProfile {
+    pub fn new(name: &str) -> Self {
+        let now = unix_now();
+        Self {
+            name: name.to_string(),
+            created_at: now,
+            last_seen: now,
+            matches_played: 0,
+            lifetime_kills: 0,
+            lifetime_deaths: 0,
+            lifetime_score: 0,
+            best_score: 0,
+            credits: 0,
+            last_ship_class: None,
+            unlocked_ships: BTreeSet::new(),
+            unlocked_weapons: BTreeSet::new(),
+            upgrades: BTreeMap::new(),
+        }
+    }
+
+    /// Fold the results of one match into the lifetime totals
+    pub fn apply(&mut self, result: &MatchResult) {
+        self.last_seen = unix_now();
+        self.matches_played += result.matches;
+        self.lifetime_kills += result.kills;
+        self.lifetime_deaths += result.deaths;
+        self.lifetime_score += result.score as u64;
+        self.best_score = self.best_score.max(result.best_score);
+        self.credits += result.credits;
+        if let Some(ship_class) = &result.ship_class {
+            self.last_ship_class = Some(ship_class.clone());
+        }
+    }
+}
+
+/// A player's results from a finished (or abandoned) match
+#[derive(Debug, Clone, Default)]
+pub struct MatchResult {
+    pub matches: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub score: u32,
+    pub best_score: u32,
+    pub credits: u32,
+    pub ship_class: Option<String>,
+}
+
+impl MatchResult {
+    /// Results of a single match
+    pub fn single(kills: u32, deaths: u32, score: u32, credits: u32, ship_class: &str) -> Self {
+        Self {
+            matches: 1,
+            kills,
+            deaths,
+            score,
+            best_score: score,
+            credits,
+            ship_class: Some(ship_class.to_string()),
+        }
+    }
+
+    /// Combine two pending results for the same player
+    fn merge(&mut self, other: MatchResult) {
+        self.matches += other.matches;
+        self.kills += other.kills;
+        self.deaths += other.deaths;
+        self.score += other.score;
+        self.best_score = self.best_score.max(other.best_score);
+        self.credits += other.credits;
+        if other.ship_class.is_some() {
+            self.ship_class = other.ship_class;
+        }
+    }
+}
+
+/// Reject names that would all share the profile stored under the empty key
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if profile_key(name).is_empty() {
+        return Err("Player name must not be empty".to_string());
+    }
+    Ok(())
+}
+
+/// Profile key for a player name
+pub fn profile_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Synchronous access to the profiles table
+#[derive(Clone)]
+pub struct ProfileStore {
+    db: Arc<Database>,
+}
+
+impl ProfileStore {
+    /// Wrap a database, creating the profiles table if needed
+    pub fn open(db: Arc<Database>) -> Result<Self, String> {
+        let txn = db.begin_write().map_err(|e| e.to_string())?;
+        txn.open_table(PROFILES).map_err(|e| e.to_string())?;
+        txn.commit().map_err(|e| e.to_string())?;
+        Ok(Self { db })
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<PlayerProfile>, String> {
+        let txn = self.db.begin_read().map_err(|e| e.to_string())?;
+        let table = txn.open_table(PROFILES).map_err(|e| e.to_string())?;
+        let Some(value) = table
+            .get(profile_key(name).as_str())
+            .map_err(|e| e.to_string())?
+        else {
+            return Ok(None);
+        };
+        serde_json::from_slice(value.value())
+            .map(Some)
+            .map_err(|e| format!("Corrupt profile for {}: {}", name, e))
+    }
+
+    /// Fetch a profile, creating and storing a fresh one on first join
+    pub fn load_or_create(&self, name: &str) -> Result<PlayerProfile, String> {
+        validate_name(name)?;
+        if let Some(profile) = self.get(name)? {
+            return Ok(profile);
+        }
+
+        let profile = PlayerProfile::new(name.trim());
+        self.put(&profile)?;
+        info!("Created new profile for {}", profile.name);
+        Ok(profile)
+    }
+
+    pub fn put(&self, profile: &PlayerProfile) -> Result<(), String> {
+        let bytes = serde_json::to_vec(profile).map_err(|e| e.to_string())?;
+        let txn = self.db.begin_write().map_err(|e| e.to_string())?;
+        {
+            let mut table = txn.open_table(PROFILES).map_err(|e| e.to_string())?;
+            table
+                .insert(profile_key(&profile.name).as_str(), bytes.as_slice())
+                .map_err(|e| e.to_string())?;
+        }
+        txn.commit().map_err(|e| e.to_string())
+    }
+
+    /// Apply a batch of match results in a single write transaction, returning
+    /// the keys of corrupt profiles that were left untouched
+    fn apply_results(
+        &self,
+        results: &HashMap<String, (String, MatchResult)>,
+    ) -> Result<Vec<String>, String> {
+        let mut skipped = Vec::new();
+        let txn = self.db.begin_write().map_err(|e| e.to_string())?;
+        {
+            let mut table = txn.open_table(PROFILES).map_err(|e| e.to_string())?;
+            for (key, (name, result)) in results {
+                let existing = match table.get(key.as_str()).map_err(|e| e.to_string())? {
+                    Some(value) => match serde_json::from_slice(value.value()) {
+                        Ok(profile) => Some(profile),
+                        Err(e) => {
+                            error!("Corrupt profile for {}: {}", name, e);
+                            skipped.push(key.clone());
+                            continue;
+                        }
+                    },
+                    None => None,
+                };
+                let mut profile = existing.unwrap_or_else(|| PlayerProfile::new(name));
+                profile.apply(result);
+
+                let bytes = serde_json::to_vec(&profile).map_err(|e| e.to_string())?;
+                table
+                    .insert(key.as_str(), bytes.as_slice())
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        txn.commit().map_err(|e| e.to_string())?;
+        Ok(skipped)
+    }
+}
+
+enum WriterCommand {
+    Record { name: String, result: MatchResult },
+    Flush(oneshot::Sender<()>),
+}
+
+/// Handle for queueing profile writes to the background writer task
+#[derive(Clone)]
+pub struct ProfileWriter {
+    tx: mpsc::UnboundedSender<WriterCommand>,
+}
+
+impl ProfileWriter {
+    /// Start the writer task for `store`
+    pub fn spawn(store: ProfileStore) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(run_writer(store, rx));
+        Self { tx }
+    }
+
+    /// Queue a player's match results; written on the next flush
+    pub fn record(&self, name: &str, result: MatchResult) {
+        let _ = self.tx.send(WriterCommand::Record {
+            name: name.to_string(),
+            result,
+        });
+    }
+
+    /// Write everything queued so far and wait for it to reach disk
+    pub async fn flush(&self) {
+        let (done_tx, done_rx) = oneshot::channel();
+        if self.tx.send(WriterCommand::Flush(done_tx)).is_ok() {
+            let _ = done_rx.await;
+        }
+    }
+}
+
+async fn run_writer(store: ProfileStore, mut rx: mpsc::UnboundedReceiver<WriterCommand>) {
+    let mut pending: HashMap<String, (String, MatchResult)> = HashMap::new();
+    let mut interval = time::interval(FLUSH_INTERVAL);
+
+    loop {
+        tokio::select! {
+            command = rx.recv() => match command {
+                Some(WriterCommand::Record { name, result }) => {
+                    queue_result(&mut pending, profile_key(&name), name, result);
+                }
+                Some(WriterCommand::Flush(done)) => {
+                    flush_pending(&store, &mut pending).await;
+                    let _ = done.send(());
+                }
+                None => {
+                    flush_pending(&store, &mut pending).await;
+                    break;
+                }
+            },
+            _ = interval.tick() => flush_pending(&store, &mut pending).await,
+        }
+    }
+}
+
+fn queue_result(
+    pending: &mut HashMap<String, (String, MatchResult)>,
+    key: String,
+    name: String,
+    result: MatchResult,
+) {
+    match pending.entry(key) {
+        Entry::Occupied(mut entry) => {
+            entry.get_mut().1.merge(result);
+        }
+        Entry::Vacant(entry) => {
+            entry.insert((name, result));
+        }
+    }
+}
+
+/// Write pending results; anything that could not be written stays pending
+/// and is retried on the next flush
+async fn flush_pending(store: &ProfileStore, pending: &mut HashMap<String, (String, MatchResult)>) {
+    if pending.is_empty() {
+        return;
+    }
+
+    let batch = std::mem::take(pending);
+    let count = batch.len();
+    let store = store.clone();
+    let (mut batch, outcome) = tokio::task::spawn_blocking(move || {
+        let outcome = store.apply_results(&batch);
+        (batch, outcome)
+    })
+    .await
+    .unwrap_or_else(|e| {
+        (
+            HashMap::new(),
+            Err(format!("profile writer panicked: {}", e)),
+        )
+    });
+
+    let retry: Vec<String> = match outcome {
+        Ok(skipped) => {
+            debug!("Flushed {} profile updates", count - skipped.len());
+            skipped
+        }
+        Err(e) => {
+            error!(
+                "Failed to write {} profile updates, retrying on the next flush: {}",
+                count, e
+            );
+            batch.keys().cloned().collect()
+        }
+    };
+
+    for key in retry {
+        if let Some((name, result)) = batch.remove(&key) {
+            queue_result(pending, key, name, result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// A store backed by a fresh database file that is unlinked once opened
+    fn temp_store() -> ProfileStore {
+        let path = std::env::temp_dir().join(format!("profiles-{}.redb", uuid::Uuid::new_v4()));
+        let db = Database::create(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        ProfileStore::open(Arc::new(db)).unwrap()
+    }
+
+    fn write_raw(store: &ProfileStore, key: &str, bytes: &[u8]) {
+        let txn = store.db.begin_write().unwrap();
+        txn.open_table(PROFILES)
+            .unwrap()
+            .insert(key, bytes)
+            .unwrap();
+        txn.commit().unwrap();
+    }
+
+    #[test]
+    fn blank_names_are_rejected() {
+        let store = temp_store();
+        for name in ["", "   ", "\t\n"] {
+            assert!(validate_name(name).is_err(), "{:?} was accepted", name);
+            assert!(store.load_or_create(name).is_err());
+        }
+        assert!(store.get("").unwrap().is_none());
+
+        let profile = store.load_or_create("  BoneCrusher ").unwrap();
+        assert_eq!(profile.name, "BoneCrusher");
+        assert_eq!(
+            store.get("bonecrusher").unwrap().unwrap().name,
+            "BoneCrusher"
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_flush_is_retried() {
+        let store = temp_store();
+        write_raw(&store, "bonecrusher", b"not json");
+
+        let mut pending = HashMap::new();
+        for result in [
+            MatchResult::single(3, 1, 300, 10, "standard"),
+            MatchResult::single(1, 2, 500, 5, "gunship"),
+        ] {
+            queue_result(
+                &mut pending,
+                profile_key("BoneCrusher"),
+                "BoneCrusher".to_string(),
+                result,
+            );
+        }
+
+        // The corrupt profile can't be updated, so its results stay queued
+        flush_pending(&store, &mut pending).await;
+        assert!(store.get("BoneCrusher").is_err());
+        assert_eq!(pending["bonecrusher"].1.kills, 4);
+
+        store.put(&PlayerProfile::new("BoneCrusher")).unwrap();
+        flush_pending(&store, &mut pending).await;
+        assert!(pending.is_empty());
+
+        let profile = store.get("BoneCrusher").unwrap().unwrap();
+        assert_eq!(profile.matches_played, 2);
+        assert_eq!(profile.lifetime_kills, 4);
+        assert_eq!(profile.lifetime_deaths, 3);
+        assert_eq!(profile.lifetime_score, 800);
+        assert_eq!(profile.best_score, 500);
+        assert_eq!(profile.credits, 15);
+        assert_eq!(profile.last_ship_class.as_deref(), Some("gunship"));
+    }
+}
@@ -0,0 +1,554 @@
+//! Network protocol
+//!
+//! This module owns every message exchanged between client and server, the
+//! framing that wraps them on the wire, their delivery priority, and the
+//! encodings a connection can negotiate during the Hello/Welcome handshake.
+//! Both directions are defined here so the server and client cannot drift
+//! apart on message shapes: the client's `src/protocol.ts` is generated from
+//! these types, and a test fails when the checked-in copy is stale.
+
+#![allow(dead_code)] // Client priorities are informational; only clients schedule them
+
+use crate::leaderboard::LeaderboardPage;
+use crate::profiles::PlayerProfile;
+use crate::simulation::GameEvent;
+use crate::snapshot_codec::{EntityInfo, PackedSnapshot};
+use crate::timesync::server_time_ms;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+/// Protocol version for compatibility checking
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Oldest client protocol version the server still speaks
+pub const MIN_PROTOCOL_VERSION: u32 = 2;
+
+/// Optional protocol features the server can enable for a connection
+pub const SUPPORTED_FEATURES: &[&str] = &["resume", "debug_render", "leaderboard"];
+
+/// Maximum message size in bytes (1MB)
+pub const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+/// Messages sent from client to server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(tag = "type")]
+pub enum ClientMessage {
+    /// First message on every connection
+    Hello {
+        protocol_version: u32,
+        encodings: Vec<String>,
+        #[serde(default)]
+        features: Vec<String>,
+    },
+    Join {
+        room_code: String,
+        player_name: String,
+        #[serde(default)]
+        #[cfg_attr(test, ts(optional))]
+        ship_class: Option<String>, // None picks the content default class
+    },
+    /// Bit-packed inputs, see [`crate::input_codec`]
+    Input {
+        #[serde(with = "serde_bytes")]
+        #[cfg_attr(test, ts(type = "Uint8Array | number[]"))] // number[] in JSON
+        data: Vec<u8>,
+    },
+    Resume {
+        room_code: String,
+        resume_token: String,
+    },
+    Ping {
+        timestamp: u64,
+    },
+    /// Echo of a server Probe
+    ProbeReply {
+        server_time: u64,
+    },
+    RequestDebugRender {
+        timestamp: u64,
+    },
+    Leave,
+}
+
+/// Messages sent from server to client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(tag = "type")]
+pub enum ServerMessage {
+    /// Reply to Hello with the settings chosen for this connection
+    Welcome {
+        protocol_version: u32,
+        encoding: Encoding,
+        features: Vec<String>,
+        tick_rate: f32,
+        snapshot_rate: f32,
+    },
+    RoomJoined {
+        room_code: String,
+        player_id: String,
+        entity_id: u64,       // Network ID of the player's ship
+        resume_token: String, // Present with Resume to reclaim this player after a disconnect
+    },
+    RoomCreated {
+        room_code: String,
+    },
+    PlayerJoined {
+        player_id: String,
+        player_name: String,
+    },
+    PlayerLeft {
+        player_id: String,
+    },
+    PlayerDisconnected {
+        player_id: String,
+        grace_secs: u64,
+    },
+    PlayerReconnected {
+        player_id: String,
+    },
+    ProfileLoaded {
+        profile: PlayerProfile,
+    },
+    NewHighScore {
+        player_name: String,
+        score: u32,
+        ship_class: String,
+        leaderboard: LeaderboardPage,
+    },
+    Snapshot(PackedSnapshot),
+    /// Names and ship configuration, sent when an entity appears or changes
+    EntityInfo(EntityInfo),
+    /// Spawns, despawns, hits and kills from one tick, sent ahead of its snapshot
+    Events {
+        tick: u64,
+        events: Vec<GameEvent>,
+    },
+    /// Clock sync reply; server times are on the monotonic server clock
+    Pong {
+        timestamp: u64, // client time from the Ping
+        server_receive_time: u64,
+        server_send_time: u64,
+        tick: u64, // current simulation tick of the player's room
+    },
+    /// Round trip measurement; answered with ProbeReply
+    Probe {
+        server_time: u64,
+    },
+    DebugRender(DebugRenderData),
+    /// The server is stopping; sent every second until it closes the connection
+    ServerShuttingDown {
+        seconds_remaining: u64,
+    },
+    Error {
+        code: ErrorCode,
+        message: String,
+    },
+}
+
+/// Machine-readable reason attached to every Error message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    HandshakeRequired,
+    IncompatibleProtocol,
+    UnsupportedEncoding,
+    MalformedMessage,
+    JoinFailed,
+    ResumeFailed,
+    ProfileUnavailable,
+    ServerShuttingDown,
+    SendQueueOverflow,
+}
+
+impl ErrorCode {
+    /// WebSocket close code sent after errors that end the connection
+    pub fn close_code(&self) -> Option<u16> {
+        match self {
+            ErrorCode::HandshakeRequired => Some(4000),
+            ErrorCode::IncompatibleProtocol => Some(4001),
+            ErrorCode::UnsupportedEncoding => Some(4002),
+            ErrorCode::SendQueueOverflow => Some(4003),
+            ErrorCode::ServerShuttingDown => Some(1001), // going away
+            _ => None,
+        }
+    }
+}
+
+/// Envelope around every message on the wire
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Frame<M> {
+    /// Per-connection, per-direction sequence number
+    #[serde(default)]
+    pub seq: u32,
+    /// Sender clock in milliseconds when the frame was written (server frames use
+    /// the monotonic server clock)
+    #[serde(default)]
+    pub sent_at: u64,
+    pub message: M,
+}
+
+impl<M> Frame<M> {
+    pub fn new(seq: u32, message: M) -> Self {
+        Self {
+            seq,
+            sent_at: server_time_ms(),
+            message,
+        }
+    }
+}
+
+/// Message priority levels for bandwidth management
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MessagePriority {
+    /// Critical messages (connection, errors)
+    Critical,
+    /// High priority (input, acks)
+    High,
+    /// Gameplay events and entity info, which must arrive in order
+    Reliable,
+    /// Normal priority (snapshots)
+    Normal,
+    /// Low priority (optional data)
+    Low,
+}
+
+impl ServerMessage {
+    pub fn priority(&self) -> MessagePriority {
+        match self {
+            ServerMessage::Welcome { .. }
+            | ServerMessage::RoomJoined { .. }
+            | ServerMessage::RoomCreated { .. }
+            | ServerMessage::PlayerJoined { .. }
+            | ServerMessage::PlayerLeft { .. }
+            | ServerMessage::PlayerDisconnected { .. }
+            | ServerMessage::PlayerReconnected { .. }
+            | ServerMessage::ServerShuttingDown { .. }
+            | ServerMessage::Error { .. } => MessagePriority::Critical,
+            ServerMessage::ProfileLoaded { .. }
+            | ServerMessage::Pong { .. }
+            | ServerMessage::Probe { .. } => MessagePriority::High,
+            ServerMessage::EntityInfo(_) | ServerMessage::Events { .. } => {
+                MessagePriority::Reliable
+            }
+            ServerMessage::Snapshot(_) => MessagePriority::Normal,
+            ServerMessage::NewHighScore { .. } => MessagePriority::Low,
+            ServerMessage::DebugRender(_) => MessagePriority::Low, // Debug data is low priority
+        }
+    }
+}
+
+/// Payload encodings the server can speak, in order of preference
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// MessagePack in WebSocket binary frames
+    #[serde(rename = "msgpack")]
+    MessagePack,
+    /// JSON in WebSocket text frames
+    Json,
+}
+
+/// An encoded frame, ready to be wrapped in a WebSocket message
+#[derive(Debug, Clone)]
+pub enum WireData {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl WireData {
+    pub fn len(&self) -> usize {
+        match self {
+            WireData::Text(text) => text.len(),
+            WireData::Binary(bytes) => bytes.len(),
+        }
+    }
+}
+
+/// A message encoded ahead of time, so its size is known while it waits to
+/// be sent; it is wrapped in its [`Frame`] only when it goes out
+#[derive(Debug, Clone)]
+pub struct EncodedMessage(WireData);
+
+impl EncodedMessage {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Wrap the message in a frame stamped now
+    pub fn frame(&self, seq: u32) -> Result<WireData, String> {
+        self.frame_at(seq, server_time_ms())
+    }
+
+    /// Splice the message into a frame, giving the same bytes as encoding the
+    /// whole [`Frame`] would
+    fn frame_at(&self, seq: u32, sent_at: u64) -> Result<WireData, String> {
+        match &self.0 {
+            WireData::Text(message) => Ok(WireData::Text(format!(
+                r#"{{"seq":{},"sent_at":{},"message":{}}}"#,
+                seq, sent_at, message
+            ))),
+            WireData::Binary(message) => {
+                let mut frame = Vec::with_capacity(message.len() + 32);
+                frame.push(0x83); // map of the frame's three named fields
+                for (field, value) in [("seq", seq as u64), ("sent_at", sent_at)] {
+                    rmp_serde::encode::write(&mut frame, field)
+                        .and_then(|_| rmp_serde::encode::write(&mut frame, &value))
+                        .map_err(|e| format!("Serialization error: {}", e))?;
+                }
+                rmp_serde::encode::write(&mut frame, "message")
+                    .map_err(|e| format!("Serialization error: {}", e))?;
+                frame.extend_from_slice(message);
+                Ok(WireData::Binary(frame))
+            }
+        }
+    }
+}
+
+impl Encoding {
+    pub const SUPPORTED: &[Encoding] = &[Encoding::MessagePack, Encoding::Json];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::MessagePack => "msgpack",
+            Encoding::Json => "json",
+        }
+    }
+
+    /// Pick the most preferred server encoding the client also offered
+    pub fn negotiate(offered: &[String]) -> Option<Encoding> {
+        Self::SUPPORTED
+            .iter()
+            .copied()
+            .find(|encoding| offered.iter().any(|name| name == encoding.name()))
+    }
+
+    /// Encoding implied by the kind of WebSocket frame data arrived in
+    pub fn of(data: &WireData) -> Encoding {
+        match data {
+            WireData::Text(_) => Encoding::Json,
+            WireData::Binary(_) => Encoding::MessagePack,
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<WireData, String> {
+        match self {
+            Encoding::Json => serde_json::to_string(value)
+                .map(WireData::Text)
+                .map_err(|e| format!("Serialization error: {}", e)),
+            // Named fields keep internally tagged enums decodable
+            Encoding::MessagePack => rmp_serde::to_vec_named(value)
+                .map(WireData::Binary)
+                .map_err(|e| format!("Serialization error: {}", e)),
+        }
+    }
+
+    /// Encode a message to be framed later by [`EncodedMessage::frame`]
+    pub fn encode_message(&self, message: &ServerMessage) -> Result<EncodedMessage, String> {
+        self.encode(message).map(EncodedMessage)
+    }
+
+    /// Decode a frame; the frame type (text or binary) selects the encoding
+    pub fn decode<T: DeserializeOwned>(data: &WireData) -> Result<T, String> {
+        if data.len() > MAX_MESSAGE_SIZE {
+            return Err(format!("Message of {} bytes exceeds limit", data.len()));
+        }
+        match data {
+            WireData::Text(text) => {
+                serde_json::from_str(text).map_err(|e| format!("Deserialization error: {}", e))
+            }
+            WireData::Binary(bytes) => {
+                rmp_serde::from_slice(bytes).map_err(|e| format!("Deserialization error: {}", e))
+            }
+        }
+    }
+}
+
+/// Pick the protocol version to speak with a client, if the two are compatible
+pub fn negotiate_version(client_version: u32) -> Option<u32> {
+    let version = client_version.min(PROTOCOL_VERSION);
+    (version >= MIN_PROTOCOL_VERSION).then_some(version)
+}
+
+/// Debug rendering data for physics visualization
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct DebugRenderData {
+    pub sequence: u32,
+    pub rigid_bodies: Vec<DebugRigidBody>,
+    pub colliders: Vec<DebugCollider>,
+    pub forces: Vec<DebugForce>,
+    pub velocities: Vec<DebugVelocity>,
+    pub joints: Vec<DebugJoint>,
+}
+
+/// Debug rigid body visualization
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct DebugRigidBody {
+    pub handle: u32, // Simplified handle for network
+    pub position: [f32; 2],
+    pub rotation: f32,
+    pub body_type: DebugBodyType,
+    pub mass: f32,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+}
+
+/// Debug collider visualization
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct DebugCollider {
+    pub handle: u32,
+    pub parent_body: u32,
+    pub shape: DebugShape,
+    pub position: [f32; 2],
+    pub rotation: f32,
+}
+
+/// Debug force vector visualization
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct DebugForce {
+    pub body_handle: u32,
+    pub force: [f32; 2],
+    pub torque: f32,
+    pub application_point: [f32; 2],
+}
+
+/// Debug velocity vector visualization
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct DebugVelocity {
+    pub body_handle: u32,
+    pub linear_velocity: [f32; 2],
+    pub angular_velocity: f32,
+}
+
+/// Debug joint visualization
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct DebugJoint {
+    pub handle: u32,
+    pub body1: u32,
+    pub body2: u32,
+    pub anchor1: [f32; 2],
+    pub anchor2: [f32; 2],
+    pub joint_type: DebugJointType,
+}
+
+/// Simplified body type for debug rendering
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub enum DebugBodyType {
+    Dynamic,
+    Kinematic,
+    Static,
+}
+
+/// Simplified shape for debug rendering
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub enum DebugShape {
+    Ball { radius: f32 },
+    Cuboid { half_extents: [f32; 2] },
+    Triangle { vertices: [[f32; 2]; 3] },
+    Polygon { vertices: Vec<[f32; 2]> },
+}
+
+/// Simplified joint type for debug rendering
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub enum DebugJointType {
+    Fixed,
+    Revolute,
+    Prismatic,
+    Distance,
+    Spring,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::BTreeMap, fs, path::Path};
+    use ts_rs::{TS, TypeVisitor};
+
+    /// Generated TypeScript mirror of this module, imported by the client
+    const CLIENT_TYPES: &str = "../client/src/protocol.ts";
+
+    /// Collects the declaration of every exportable type reachable from the visited ones
+    #[derive(Default)]
+    struct Declarations(BTreeMap<String, String>);
+
+    impl TypeVisitor for Declarations {
+        fn visit<T: TS + 'static + ?Sized>(&mut self) {
+            if T::output_path().is_none() || self.0.contains_key(&T::ident()) {
+                return;
+            }
+            self.0.insert(T::ident(), T::decl());
+            T::visit_dependencies(self);
+        }
+    }
+
+    fn client_types() -> String {
+        let mut declarations = Declarations::default();
+        declarations.visit::<Frame<ts_rs::Dummy>>();
+        declarations.visit::<ClientMessage>();
+        declarations.visit::<ServerMessage>();
+
+        let mut out = String::from(
+            "// Generated from server/src/protocol.rs; do not edit.\n\
+             // Regenerate with `UPDATE_CLIENT_TYPES=1 cargo test client_types` in server/.\n",
+        );
+        for declaration in declarations.0.values() {
+            // u64 values (ticks, millisecond times, network IDs) stay below 2^53
+            // and arrive as plain numbers from both encodings
+            out.push_str(&format!(
+                "\nexport {}\n",
+                declaration.replace("bigint", "number")
+            ));
+        }
+        out
+    }
+
+    #[test]
+    fn encoded_message_frames_like_a_whole_frame() {
+        let message = ServerMessage::PlayerLeft {
+            player_id: "pilot \"one\"".to_string(),
+        };
+        for encoding in Encoding::SUPPORTED {
+            for (seq, sent_at) in [(1, 0), (300, 70_000), (u32::MAX, u64::MAX)] {
+                let whole = encoding
+                    .encode(&Frame {
+                        seq,
+                        sent_at,
+                        message: message.clone(),
+                    })
+                    .unwrap();
+                let spliced = encoding
+                    .encode_message(&message)
+                    .unwrap()
+                    .frame_at(seq, sent_at)
+                    .unwrap();
+                assert_eq!(format!("{:?}", spliced), format!("{:?}", whole));
+            }
+        }
+    }
+
+    #[test]
+    fn client_types_are_current() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(CLIENT_TYPES);
+        let generated = client_types();
+        if std::env::var_os("UPDATE_CLIENT_TYPES").is_some() {
+            fs::write(&path, &generated).unwrap();
+        }
+        let checked_in = fs::read_to_string(&path).unwrap_or_default();
+        assert!(
+            checked_in == generated,
+            "{} is stale; regenerate it with `UPDATE_CLIENT_TYPES=1 cargo test client_types`",
+            CLIENT_TYPES
+        );
+    }
+}
@@ -0,0 +1,563 @@
+//! Deterministic replay of recorded sessions
+//!
+//! While recording, a simulation keeps every input and session change (joins,
+//! leaves, disconnects, latency updates, content reloads) with the tick it
+//! arrived before, plus a hash of its state after every tick. Replaying builds
+//! a fresh simulation with the same configuration, feeds the recording back at
+//! the same ticks and hashes each resulting state, so the first tick where the
+//! two runs disagree pinpoints a divergence.
+//!
+//! A recording started in a world that already has entities carries a world
+//! save ([`crate::world_save`]) of that moment, and replays restore it before
+//! feeding anything back. Otherwise replays start from an empty world.
+//!
+//! Each room's recording is saved when the room closes by a [`ReplayStore`].
+//! A replay file is a 4-byte magic and a little-endian format version,
+//! followed by a MessagePack [`ReplayFile`]: a header describing the match
+//! and then the recording itself, including the content it was played with.
+
+#![allow(dead_code)] // The server records and saves; running and reading replays is for tooling
+
+use crate::content::ContentRegistry;
+use crate::protocol::PROTOCOL_VERSION;
+use crate::simulation::{
+    GameBounds, GameSimulation, RecordedInput, RecordedSessionEvent, SIM_TICK_RATE, SessionEvent,
+};
+use rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// First bytes of every replay file
+const MAGIC: &[u8; 4] = b"CCRP";
+
+/// Bumped whenever the layout of [`ReplayFile`] changes
+pub const FORMAT_VERSION: u16 = 5;
+
+/// Extension of replay files in the replay directory
+const EXTENSION: &str = "replay";
+
+/// Longest a single recording may run before it is saved and stopped (one hour)
+pub const MAX_RECORDED_TICKS: u64 = 60 * 60 * SIM_TICK_RATE as u64;
+
+/// State hash after one tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TickHash {
+    pub tick: u64,
+    pub hash: u64,
+}
+
+/// A recorded session, complete enough to re-run it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    /// Seed of the simulation's RNG
+    pub seed: u64,
+    /// Tick the recording started at
+    pub start_tick: u64,
+    pub tick_epoch_ms: u64,
+    pub bounds: GameBounds,
+    pub friendly_fire: bool,
+    /// Content in effect when recording started; reloads are session events
+    pub content: Arc<ContentRegistry>,
+    /// World save at `start_tick`, if the world wasn't empty when recording began
+    #[serde(with = "serde_bytes")]
+    pub checkpoint: Option<Vec<u8>>,
+    /// Players already in the world at `start_tick`
+    pub initial_roster: Vec<RosterEntry>,
+    pub inputs: Vec<RecordedInput>,
+    pub session_events: Vec<RecordedSessionEvent>,
+    /// State hashes of the original run, one per tick
+    pub hashes: Vec<TickHash>,
+}
+
+impl Recording {
+    /// Last tick the original run reached
+    pub fn end_tick(&self) -> u64 {
+        self.hashes
+            .last()
+            .map(|hash| hash.tick)
+            .unwrap_or(self.start_tick)
+    }
+
+    /// Every player who joined during the recording
+    pub fn roster(&self) -> Vec<RosterEntry> {
+        let joined = self
+            .session_events
+            .iter()
+            .filter_map(|recorded| match &recorded.event {
+                SessionEvent::Joined {
+                    player_id,
+                    name,
+                    ship_class,
+                    ..
+                } => Some(RosterEntry {
+                    player_id: *player_id,
+                    name: name.clone(),
+                    ship_class: ship_class.clone(),
+                }),
+                _ => None,
+            });
+        self.initial_roster.iter().cloned().chain(joined).collect()
+    }
+}
+
+/// Summary of a replay, readable without replaying it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    pub protocol_version: u32,
+    /// [`ContentRegistry::fingerprint`] of the content at the start of the match
+    pub content_version: u64,
+    pub room_code: String,
+    pub seed: u64,
+    pub friendly_fire: bool,
+    /// Unix seconds when the replay was saved
+    pub recorded_at: u64,
+    pub tick_rate: f32,
+    pub start_tick: u64,
+    pub end_tick: u64,
+    pub roster: Vec<RosterEntry>,
+}
+
+/// A player who took part in a recorded match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RosterEntry {
+    pub player_id: Uuid,
+    pub name: String,
+    pub ship_class: String,
+}
+
+/// Contents of a replay file after the magic and format version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayFile {
+    pub header: ReplayHeader,
+    pub recording: Recording,
+}
+
+impl ReplayFile {
+    pub fn new(room_code: &str, recording: Recording) -> Self {
+        let recorded_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        Self {
+            header: ReplayHeader {
+                protocol_version: PROTOCOL_VERSION,
+                content_version: recording.content.fingerprint(),
+                room_code: room_code.to_string(),
+                seed: recording.seed,
+                friendly_fire: recording.friendly_fire,
+                recorded_at,
+                tick_rate: SIM_TICK_RATE,
+                start_tick: recording.start_tick,
+                end_tick: recording.end_tick(),
+                roster: recording.roster(),
+            },
+            recording,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let body =
+            rmp_serde::to_vec_named(self).map_err(|e| format!("Failed to encode replay: {}", e))?;
+        let mut bytes = Vec::with_capacity(MAGIC.len() + 2 + body.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&body);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < MAGIC.len() + 2 || &bytes[..MAGIC.len()] != MAGIC {
+            return Err("Not a replay file".to_string());
+        }
+        let version = u16::from_le_bytes([bytes[MAGIC.len()], bytes[MAGIC.len() + 1]]);
+        if version != FORMAT_VERSION {
+            return Err(format!(
+                "Unsupported replay format version {} (expected {})",
+                version, FORMAT_VERSION
+            ));
+        }
+        rmp_serde::from_slice(&bytes[MAGIC.len() + 2..])
+            .map_err(|e| format!("Failed to decode replay: {}", e))
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let bytes =
+            fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// Directory of saved replays with retention limits
+#[derive(Debug, Clone)]
+pub struct ReplayStore {
+    pub dir: PathBuf,
+    /// Most replay files kept; the oldest are deleted first
+    pub max_files: usize,
+    /// Most bytes all replay files may take up together
+    pub max_bytes: u64,
+}
+
+impl ReplayStore {
+    /// Write a room's recording, then prune old replays beyond the limits
+    ///
+    /// Does blocking file IO; call from a blocking task.
+    pub fn save(&self, room_code: &str, recording: Recording) -> Result<PathBuf, String> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create {}: {}", self.dir.display(), e))?;
+
+        let file = ReplayFile::new(room_code, recording);
+        let path = self.dir.join(format!(
+            "{}-{}-{}.{}",
+            file.header.recorded_at, room_code, file.header.start_tick, EXTENSION
+        ));
+        fs::write(&path, file.to_bytes()?)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        info!(
+            "Saved replay of room {} ({} ticks, {} players) to {}",
+            room_code,
+            file.header.end_tick - file.header.start_tick,
+            file.header.roster.len(),
+            path.display()
+        );
+
+        self.enforce_retention()?;
+        Ok(path)
+    }
+
+    /// Delete the oldest replays until the directory is within its limits
+    pub fn enforce_retention(&self) -> Result<(), String> {
+        let entries = fs::read_dir(&self.dir)
+            .map_err(|e| format!("Failed to list {}: {}", self.dir.display(), e))?;
+        let mut replays: Vec<(SystemTime, u64, PathBuf)> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == EXTENSION))
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+            })
+            .collect();
+        replays.sort_by_key(|(modified, ..)| std::cmp::Reverse(*modified)); // newest first
+
+        let mut kept_bytes = 0;
+        for (index, (_, len, path)) in replays.into_iter().enumerate() {
+            kept_bytes += len;
+            if index >= self.max_files || kept_bytes > self.max_bytes {
+                match fs::remove_file(&path) {
+                    Ok(()) => info!("Deleted old replay {}", path.display()),
+                    Err(e) => warn!("Failed to delete old replay {}: {}", path.display(), e),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Re-runs a [`Recording`] one tick at a time
+pub struct ReplayRunner<'a> {
+    recording: &'a Recording,
+    simulation: GameSimulation,
+    next_input: usize,
+    next_event: usize,
+}
+
+impl<'a> ReplayRunner<'a> {
+    pub fn new(recording: &'a Recording) -> Result<Self, String> {
+        let simulation = match &recording.checkpoint {
+            Some(checkpoint) => {
+                GameSimulation::restore_state(checkpoint, recording.content.clone())?
+            }
+            None => {
+                let mut simulation =
+                    GameSimulation::with_seed(recording.content.clone(), recording.seed);
+                simulation.tick = recording.start_tick;
+                simulation.tick_epoch_ms = recording.tick_epoch_ms;
+                simulation.bounds = recording.bounds.clone();
+                simulation.friendly_fire = recording.friendly_fire;
+                simulation
+            }
+        };
+
+        Ok(Self {
+            recording,
+            simulation,
+            next_input: 0,
+            next_event: 0,
+        })
+    }
+
+    /// The simulation being replayed
+    pub fn simulation(&self) -> &GameSimulation {
+        &self.simulation
+    }
+
+    /// Whether the replay has caught up with the end of the recording
+    pub fn is_finished(&self) -> bool {
+        self.simulation.tick >= self.recording.end_tick()
+    }
+
+    /// Feed everything recorded for the current tick, step, and hash the result
+    pub fn step(&mut self) -> TickHash {
+        let tick = self.simulation.tick;
+
+        // Interleave session changes and inputs exactly as they originally arrived
+        loop {
+            let event = self
+                .recording
+                .session_events
+                .get(self.next_event)
+                .filter(|recorded| recorded.tick <= tick);
+            let input = self
+                .recording
+                .inputs
+                .get(self.next_input)
+                .filter(|recorded| recorded.tick <= tick);
+
+            match (event, input) {
+                (Some(event), _) if event.input_index <= self.next_input => {
+                    self.apply_session_event(&event.event);
+                    self.next_event += 1;
+                }
+                (_, Some(input)) => {
+                    self.simulation
+                        .add_player_input(input.player_id, input.input.clone());
+                    self.next_input += 1;
+                }
+                _ => break,
+            }
+        }
+
+        self.simulation.step(1.0 / SIM_TICK_RATE);
+        TickHash {
+            tick: self.simulation.tick,
+            hash: self.simulation.state_hash(),
+        }
+    }
+
+    /// Replay to the end of the recording, returning every tick's hash
+    pub fn run(mut self) -> Vec<TickHash> {
+        let mut hashes = Vec::new();
+        while !self.is_finished() {
+            hashes.push(self.step());
+        }
+        hashes
+    }
+
+    fn apply_session_event(&mut self, event: &SessionEvent) {
+        match event {
+            SessionEvent::Joined {
+                player_id,
+                name,
+                ship_class,
+                position,
+            } => {
+                self.simulation.spawn_player_ship(
+                    *player_id,
+                    name.clone(),
+                    ship_class,
+                    Vector::new(position[0], position[1]),
+                );
+            }
+            SessionEvent::Left { player_id } => {
+                if let Some(entity) = self.simulation.player_entity(*player_id) {
+                    self.simulation
+                        .despawn_entity(entity, crate::simulation::DespawnReason::Left);
+                }
+            }
+            SessionEvent::Disconnected { player_id } => {
+                if let Some(entity) = self.simulation.player_entity(*player_id) {
+                    self.simulation.freeze_entity(entity);
+                }
+            }
+            SessionEvent::Reconnected { player_id } => {
+                if let Some(entity) = self.simulation.player_entity(*player_id) {
+                    self.simulation.thaw_entity(entity);
+                }
+            }
+            SessionEvent::Latency {
+                player_id,
+                one_way_ms,
+            } => {
+                self.simulation
+                    .set_player_latency(*player_id, *one_way_ms as f32);
+            }
+            SessionEvent::ContentReloaded(content) => {
+                self.simulation.queue_content(content.clone());
+            }
+        }
+    }
+}
+
+/// Replay a recording and compare it against the original run's hashes
+///
+/// Returns the first tick whose state differs, or `None` if every tick matched.
+pub fn verify(recording: &Recording) -> Result<Option<u64>, String> {
+    let replayed = ReplayRunner::new(recording)?.run();
+    let divergence = first_divergence(&recording.hashes, &replayed);
+    match divergence {
+        Some(tick) => warn!("Replay diverged from the recording at tick {}", tick),
+        None => info!("Replay matched the recording for {} ticks", replayed.len()),
+    }
+    Ok(divergence)
+}
+
+/// First tick at which two runs' hashes differ, including one run ending early
+pub fn first_divergence(a: &[TickHash], b: &[TickHash]) -> Option<u64> {
+    a.iter()
+        .zip(b)
+        .find(|(a, b)| a != b)
+        .map(|(a, _)| a.tick)
+        .or_else(|| match a.len().cmp(&b.len()) {
+            std::cmp::Ordering::Greater => Some(a[b.len()].tick),
+            std::cmp::Ordering::Less => Some(b[a.len()].tick),
+            std::cmp::Ordering::Equal => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::InputData;
+    use crate::simulation::DespawnReason;
+
+    /// Two players flying, turning and shooting at each other, one of whom drops and leaves
+    fn play(simulation: &mut GameSimulation, players: [Uuid; 2]) {
+        simulation.friendly_fire = true;
+        simulation.spawn_player_ship(
+            players[0],
+            "a".to_string(),
+            "interceptor",
+            Vector::new(-200.0, 0.0),
+        );
+        simulation.step(1.0 / SIM_TICK_RATE);
+        let second = simulation.spawn_player_ship(
+            players[1],
+            "b".to_string(),
+            "interceptor",
+            Vector::new(0.0, 0.0),
+        );
+        simulation.set_player_latency(players[0], 40.0);
+
+        for sequence in 1..80u32 {
+            for (i, player_id) in players.iter().enumerate() {
+                simulation.add_player_input(
+                    *player_id,
+                    InputData {
+                        sequence,
+                        timestamp: sequence as u64 * 66,
+                        thrust: 0.5 + i as f32 * 0.5,
+                        turn: if sequence % 10 < 5 { 0.3 } else { -0.3 },
+                        primary_fire: (sequence + i as u32).is_multiple_of(2),
+                        secondary_fire: false,
+                    },
+                );
+            }
+            match sequence {
+                40 => simulation.freeze_entity(second),
+                50 => simulation.thaw_entity(second),
+                _ => {}
+            }
+            simulation.step(1.0 / SIM_TICK_RATE);
+        }
+
+        simulation.despawn_entity(second, DespawnReason::Left);
+        for _ in 0..10 {
+            simulation.step(1.0 / SIM_TICK_RATE);
+        }
+    }
+
+    /// The first player keeps flying and shooting for a few more ticks
+    fn fly_on(simulation: &mut GameSimulation, player_id: Uuid) {
+        for sequence in 80..110u32 {
+            simulation.add_player_input(
+                player_id,
+                InputData {
+                    sequence,
+                    timestamp: sequence as u64 * 66,
+                    thrust: 1.0,
+                    turn: -0.5,
+                    primary_fire: true,
+                    secondary_fire: false,
+                },
+            );
+            simulation.step(1.0 / SIM_TICK_RATE);
+        }
+    }
+
+    fn record() -> Recording {
+        let mut simulation = GameSimulation::new();
+        simulation.enable_recording();
+        play(&mut simulation, [Uuid::new_v4(), Uuid::new_v4()]);
+        simulation.disable_recording().unwrap()
+    }
+
+    #[test]
+    fn identical_runs_hash_identically() {
+        let players = [Uuid::new_v4(), Uuid::new_v4()];
+        let content = Arc::new(ContentRegistry::default());
+        let mut first = GameSimulation::with_seed(content.clone(), 7);
+        let mut second = GameSimulation::with_seed(content, 7);
+        second.tick_epoch_ms = first.tick_epoch_ms;
+        play(&mut first, players);
+        play(&mut second, players);
+
+        assert_eq!(first.tick, second.tick);
+        assert_eq!(first.state_hash(), second.state_hash());
+    }
+
+    #[test]
+    fn replay_matches_recording() {
+        let recording = record();
+        assert!(recording.hashes.len() > 80);
+        assert_eq!(verify(&recording), Ok(None));
+    }
+
+    #[test]
+    fn replay_reports_first_divergent_tick() {
+        let mut recording = record();
+        let dropped = recording.inputs.remove(recording.inputs.len() / 2);
+
+        let divergence = verify(&recording).unwrap().expect("replay should diverge");
+        assert!(divergence > dropped.tick);
+    }
+
+    #[test]
+    fn replay_file_round_trips() {
+        let recording = record();
+        let file = ReplayFile::new("TESTROOM", recording);
+        assert_eq!(file.header.roster.len(), 2);
+
+        let decoded = ReplayFile::from_bytes(&file.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.header.room_code, "TESTROOM");
+        assert_eq!(decoded.recording.hashes, file.recording.hashes);
+        assert_eq!(verify(&decoded.recording), Ok(None));
+    }
+
+    #[test]
+    fn replay_starts_from_checkpoint() {
+        let players = [Uuid::new_v4(), Uuid::new_v4()];
+        let mut simulation = GameSimulation::new();
+        play(&mut simulation, players);
+        simulation.enable_recording();
+        fly_on(&mut simulation, players[0]);
+        let recording = simulation.disable_recording().unwrap();
+
+        assert!(recording.checkpoint.is_some());
+        assert_eq!(recording.roster().len(), 1);
+        assert_eq!(verify(&recording), Ok(None));
+    }
+
+    #[test]
+    fn rejects_other_formats() {
+        let mut bytes = ReplayFile::new("TESTROOM", record()).to_bytes().unwrap();
+        bytes[MAGIC.len()] += 1;
+        assert!(ReplayFile::from_bytes(&bytes).is_err());
+        assert!(ReplayFile::from_bytes(b"not a replay").is_err());
+    }
+}
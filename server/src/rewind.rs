@@ -0,0 +1,229 @@
+//! Lag compensation for hit detection
+//!
+//! Clients render other ships in the past: a round trip behind the server plus
+//! their interpolation delay. To make shots land where the shooter saw the
+//! target, the simulation records every target's hitbox each tick and tests
+//! hitscan weapons and fast projectiles against the hitboxes as they were at
+//! the shooter's view time, bounded by [`MAX_REWIND_MS`] so a laggy client
+//! can't reach arbitrarily far back.
+
+use hecs::Entity;
+use rapier2d::parry::query::Ray;
+use rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use uuid::Uuid;
+
+/// Furthest back a shot may be resolved
+pub const MAX_REWIND_MS: u64 = 250;
+
+/// How far behind the latest snapshot clients render (client INTERPOLATION_DELAY)
+pub const CLIENT_VIEW_DELAY_MS: u64 = 120;
+
+/// Projectiles at least this fast are resolved against rewound hitboxes
+pub const FAST_PROJECTILE_SPEED: f32 = 250.0;
+
+/// Hitbox history kept, a little more than the rewind window
+const HISTORY_MS: u64 = MAX_REWIND_MS + 100;
+
+/// A target's collider at one tick
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Hitbox {
+    pub entity: Entity,
+    pub player_id: Option<Uuid>,
+    pub position: Vector<f32>,
+    pub rotation: f32,
+    pub shape: SharedShape,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct TickHitboxes {
+    time_ms: u64,
+    hitboxes: Vec<Hitbox>,
+}
+
+/// Recent hitboxes of every target, oldest first
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct RewindHistory {
+    ticks: VecDeque<TickHitboxes>,
+}
+
+impl RewindHistory {
+    /// Record the hitboxes at `time_ms`, dropping ticks older than the history window
+    pub fn record(&mut self, time_ms: u64, hitboxes: Vec<Hitbox>) {
+        self.ticks.push_back(TickHitboxes { time_ms, hitboxes });
+        while self
+            .ticks
+            .front()
+            .is_some_and(|tick| tick.time_ms + HISTORY_MS < time_ms)
+        {
+            self.ticks.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.ticks.clear();
+    }
+
+    /// Drop every recorded hitbox of `entity`, so shots can't hit where it used to be
+    pub fn forget(&mut self, entity: Entity) {
+        for tick in &mut self.ticks {
+            tick.hitboxes.retain(|hitbox| hitbox.entity != entity);
+        }
+    }
+
+    /// Move every recorded tick by `delta_ms`, following a change of clock
+    pub fn shift(&mut self, delta_ms: i64) {
+        for tick in &mut self.ticks {
+            tick.time_ms = tick.time_ms.saturating_add_signed(delta_ms);
+        }
+    }
+
+    /// Time of the newest recorded tick
+    pub fn latest_ms(&self) -> Option<u64> {
+        self.ticks.back().map(|tick| tick.time_ms)
+    }
+
+    /// Hitboxes as they were at `time_ms`, interpolated between recorded ticks
+    ///
+    /// Times outside the history clamp to the oldest or newest tick.
+    pub fn at(&self, time_ms: u64) -> Vec<Hitbox> {
+        let Some(after) = self.ticks.iter().position(|tick| tick.time_ms >= time_ms) else {
+            return self
+                .ticks
+                .back()
+                .map(|tick| tick.hitboxes.clone())
+                .unwrap_or_default();
+        };
+        if after == 0 {
+            return self.ticks[0].hitboxes.clone();
+        }
+
+        let (from, to) = (&self.ticks[after - 1], &self.ticks[after]);
+        let t = (time_ms - from.time_ms) as f32 / (to.time_ms - from.time_ms).max(1) as f32;
+        from.hitboxes
+            .iter()
+            .map(|hitbox| {
+                let mut hitbox = hitbox.clone();
+                if let Some(next) = to.hitboxes.iter().find(|next| next.entity == hitbox.entity) {
+                    hitbox.position += (next.position - hitbox.position) * t;
+                    if t >= 0.5 {
+                        hitbox.rotation = next.rotation;
+                    }
+                }
+                hitbox
+            })
+            .collect()
+    }
+
+    /// First hitbox accepted by `filter` that the segment from `origin` along
+    /// `direction` (unit length) crosses within `max_distance`, with the distance to it
+    pub fn raycast(
+        &self,
+        time_ms: u64,
+        origin: Vector<f32>,
+        direction: Vector<f32>,
+        max_distance: f32,
+        filter: impl Fn(&Hitbox) -> bool,
+    ) -> Option<(Entity, f32)> {
+        let ray = Ray::new(Point::from(origin), direction);
+        self.at(time_ms)
+            .iter()
+            .filter(|hitbox| filter(hitbox))
+            .filter_map(|hitbox| {
+                let pose = Isometry::new(hitbox.position, hitbox.rotation);
+                hitbox
+                    .shape
+                    .cast_ray(&pose, &ray, max_distance, true)
+                    .map(|distance| (hitbox.entity, distance))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK_MS: u64 = 33;
+
+    fn target_at(x: f32, y: f32) -> Vec<Hitbox> {
+        let mut world = hecs::World::new();
+        vec![Hitbox {
+            entity: world.spawn(()),
+            player_id: None,
+            position: Vector::new(x, y),
+            rotation: 0.0,
+            shape: SharedShape::ball(10.0),
+        }]
+    }
+
+    /// A target that sat at (300, 0) and then moved to (300, 300) at `moved_ms`
+    fn history(moved_ms: u64, latest_ms: u64) -> RewindHistory {
+        let mut history = RewindHistory::default();
+        let stay = target_at(300.0, 0.0);
+        let mut moved = stay.clone();
+        moved[0].position = Vector::new(300.0, 300.0);
+        for time_ms in (0..=latest_ms).step_by(TICK_MS as usize) {
+            let hitboxes = if time_ms < moved_ms { &stay } else { &moved };
+            history.record(time_ms, hitboxes.clone());
+        }
+        history
+    }
+
+    fn shoot_along_x(history: &RewindHistory, time_ms: u64) -> Option<(Entity, f32)> {
+        history.raycast(time_ms, Vector::zeros(), Vector::x(), 1000.0, |_| true)
+    }
+
+    #[test]
+    fn hit_lands_at_rewound_position() {
+        let history = history(990, 1089);
+        let (_, distance) = shoot_along_x(&history, 900).expect("rewound target is hit");
+        assert!((distance - 290.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn same_shot_misses_present_position() {
+        let history = history(990, 1089);
+        assert!(shoot_along_x(&history, 1089).is_none());
+
+        let toward = Vector::new(1.0, 1.0).normalize();
+        assert!(
+            history
+                .raycast(1089, Vector::zeros(), toward, 1000.0, |_| true)
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn position_interpolates_between_ticks() {
+        let mut history = RewindHistory::default();
+        history.record(0, target_at(0.0, 0.0));
+        let mut later = history.at(0);
+        later[0].position = Vector::new(100.0, 0.0);
+        history.record(100, later);
+
+        assert_eq!(history.at(25)[0].position, Vector::new(25.0, 0.0));
+    }
+
+    #[test]
+    fn history_is_limited_to_window() {
+        let history = history(0, 2000);
+        let oldest = history.ticks.front().unwrap().time_ms;
+        assert!(oldest + HISTORY_MS >= 2000);
+        assert!(oldest >= 2000 - HISTORY_MS - TICK_MS);
+
+        // Earlier times clamp to the oldest tick kept
+        assert_eq!(history.at(0)[0].position, history.at(oldest)[0].position);
+    }
+
+    #[test]
+    fn filter_excludes_targets() {
+        let history = history(990, 1089);
+        assert!(
+            history
+                .raycast(900, Vector::zeros(), Vector::x(), 1000.0, |_| false)
+                .is_none()
+        );
+    }
+}
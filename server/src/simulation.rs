@@ -9,28 +9,44 @@
 #![allow(dead_code)] // Allow unused code during Phase 2 infrastructure development
 
 use crate::components::*;
-use crate::wire_format::{
+use crate::content::{ContentRegistry, ProjectileDef};
+use crate::interest::{self, InterestRadii, InterestState, Viewer};
+use crate::protocol::{
     DebugBodyType, DebugCollider, DebugJoint, DebugJointType, DebugRenderData, DebugRigidBody,
     DebugShape, DebugVelocity,
 };
+use crate::replay::{Recording, RosterEntry, TickHash};
+use crate::rewind::{
+    CLIENT_VIEW_DELAY_MS, FAST_PROJECTILE_SPEED, Hitbox, MAX_REWIND_MS, RewindHistory,
+};
+use crate::snapshot_codec::{self, Arena, EntityInfo, EntityKind, PackedSnapshot, PlayerInfo};
+use crate::state_hash::StateHasher;
+use crate::timesync::server_time_ms;
 use hecs::{Entity, World};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use rapier2d::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
     time::{Duration, Instant},
 };
-use tracing::{debug, info, warn};
+use tracing::{debug, info, trace, warn};
 use uuid::Uuid;
 
 /// Target simulation frequency (15 Hz) - matches snapshot frequency for simplicity
-const SIM_TICK_RATE: f32 = 15.0;
-const SIM_TICK_DURATION: Duration = Duration::from_nanos((1_000_000_000.0 / SIM_TICK_RATE) as u64);
+pub const SIM_TICK_RATE: f32 = 15.0;
+pub const SIM_TICK_DURATION: Duration =
+    Duration::from_nanos((1_000_000_000.0 / SIM_TICK_RATE) as u64);
 
 /// Snapshot frequency matches simulation frequency (15 Hz)
-const SNAPSHOT_RATE: f32 = 15.0;
+pub const SNAPSHOT_RATE: f32 = 15.0;
 // No need for SNAPSHOTS_PER_TICK - every simulation tick generates a snapshot
 
+/// Ticks between state hash log lines (every 10 seconds)
+const STATE_HASH_LOG_INTERVAL: u64 = 150;
+
 /// Game simulation state for a single room
 pub struct GameSimulation {
     /// ECS World containing all entities and components
@@ -62,6 +78,45 @@ pub struct GameSimulation {
 
     /// Room bounds for containment
     pub bounds: GameBounds,
+
+    /// Ship, weapon and enemy definitions used when spawning entities
+    pub content: Arc<ContentRegistry>,
+
+    /// Reloaded content waiting to be applied at the next tick boundary
+    pub pending_content: Option<Arc<ContentRegistry>>,
+
+    /// Static entity data as last sent to clients, to detect changes
+    pub sent_entity_info: HashMap<u64, EntityInfo>,
+
+    /// Entities each player's client has been told about
+    pub interest: InterestState,
+
+    /// Server clock time of tick 0; simulation time advances with the tick counter
+    pub tick_epoch_ms: u64,
+
+    /// Next network ID to hand out; IDs start at 1 so 0 can mean "none"
+    pub next_net_id: u64,
+
+    /// Lookup from network ID back to the entity carrying it
+    pub net_ids: HashMap<u64, Entity>,
+
+    /// Recent target hitboxes for lag-compensated hit detection
+    pub rewind: RewindHistory,
+
+    /// Estimated one-way latency per player in ms, used to rewind their shots
+    pub player_latency: HashMap<Uuid, u64>,
+
+    /// Whether players can damage each other
+    pub friendly_fire: bool,
+
+    /// Events raised since the last step result, delivered with the next one
+    pub events: Vec<GameEvent>,
+
+    /// Seed the RNG started from, recorded so runs can be reproduced
+    pub seed: u64,
+
+    /// Source of all gameplay randomness; never use the thread RNG in game logic
+    pub rng: ChaCha8Rng,
 }
 
 /// Physics world wrapper
@@ -102,7 +157,7 @@ impl Default for PhysicsWorld {
 }
 
 /// Game world boundaries
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameBounds {
     pub width: f32,
     pub height: f32,
@@ -155,15 +210,55 @@ impl EventHandler for CollisionEventCollector {
 pub struct InputRecorder {
     pub recorded_inputs: Vec<RecordedInput>,
     pub is_recording: bool,
+    pub start_tick: u64,
+    pub content: Arc<ContentRegistry>,
+    pub checkpoint: Option<Vec<u8>>,
+    pub initial_roster: Vec<RosterEntry>,
+    pub session_events: Vec<RecordedSessionEvent>,
+    pub hashes: Vec<TickHash>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RecordedInput {
     pub tick: u64,
     pub player_id: Uuid,
     pub input: InputData,
 }
 
+/// A session change applied before `tick` was stepped
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedSessionEvent {
+    pub tick: u64,
+    /// Inputs recorded before this event, so a replay keeps their original order
+    pub input_index: usize,
+    pub event: SessionEvent,
+}
+
+/// Everything besides inputs that changes how a recorded session plays out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SessionEvent {
+    Joined {
+        player_id: Uuid,
+        name: String,
+        ship_class: String,
+        position: [f32; 2],
+    },
+    Left {
+        player_id: Uuid,
+    },
+    Disconnected {
+        player_id: Uuid,
+    },
+    Reconnected {
+        player_id: Uuid,
+    },
+    Latency {
+        player_id: Uuid,
+        one_way_ms: u64,
+    },
+    ContentReloaded(Arc<ContentRegistry>),
+}
+
 impl Default for GameSimulation {
     fn default() -> Self {
         Self::new()
@@ -172,6 +267,14 @@ impl Default for GameSimulation {
 
 impl GameSimulation {
     pub fn new() -> Self {
+        Self::with_content(Arc::new(ContentRegistry::default()))
+    }
+
+    pub fn with_content(content: Arc<ContentRegistry>) -> Self {
+        Self::with_seed(content, rand::random())
+    }
+
+    pub fn with_seed(content: Arc<ContentRegistry>, seed: u64) -> Self {
         let world = World::new();
         let mut physics = PhysicsWorld::default();
 
@@ -195,50 +298,394 @@ impl GameSimulation {
             snapshot_sequence: 0,
             input_recorder: None,
             bounds: GameBounds::default(),
+            content,
+            pending_content: None,
+            sent_entity_info: HashMap::new(),
+            interest: InterestState::default(),
+            tick_epoch_ms: server_time_ms(),
+            next_net_id: 1,
+            net_ids: HashMap::new(),
+            rewind: RewindHistory::default(),
+            player_latency: HashMap::new(),
+            friendly_fire: false,
+            events: Vec::new(),
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// Server clock time of the current tick
+    pub fn tick_time_ms(&self) -> u64 {
+        self.time_of_tick(self.tick)
+    }
+
+    fn time_of_tick(&self, tick: u64) -> u64 {
+        self.tick_epoch_ms + (tick as f64 * 1000.0 / SIM_TICK_RATE as f64) as u64
+    }
+
+    /// Update a player's one-way latency estimate for lag compensation
+    pub fn set_player_latency(&mut self, player_id: Uuid, one_way_ms: f32) {
+        let one_way_ms = one_way_ms.max(0.0) as u64;
+        self.record_session_event(SessionEvent::Latency {
+            player_id,
+            one_way_ms,
+        });
+        self.player_latency.insert(player_id, one_way_ms);
+    }
+
+    /// Time on the server clock that a shooter was seeing when the state at `now_ms` reached them
+    fn view_time_ms(&self, shooter: Uuid, now_ms: u64) -> u64 {
+        let latency = self.player_latency.get(&shooter).copied().unwrap_or(0);
+        now_ms.saturating_sub((latency + CLIENT_VIEW_DELAY_MS).min(MAX_REWIND_MS))
+    }
+
+    /// Queue reloaded content; it takes effect at the start of the next step
+    pub fn queue_content(&mut self, content: Arc<ContentRegistry>) {
+        self.record_session_event(SessionEvent::ContentReloaded(content.clone()));
+        self.pending_content = Some(content);
+    }
+
+    /// Swap in queued content and retune live ships and weapons to match it
+    fn apply_pending_content(&mut self) {
+        let Some(content) = self.pending_content.take() else {
+            return;
+        };
+
+        let mut updated = 0;
+        for (entity, (ship, health, collider)) in
+            self.world
+                .query_mut::<(&mut Ship, &mut Health, &crate::components::Collider)>()
+        {
+            let Some(class) = content.ship(&ship.class) else {
+                warn!(
+                    "Ship class '{}' no longer exists, keeping old tuning for {:?}",
+                    ship.class, entity
+                );
+                continue;
+            };
+
+            ship.thrust_power = class.thrust_power;
+            ship.turn_rate = class.turn_rate;
+            ship.max_speed = class.max_speed;
+
+            health.max = class.health.max;
+            health.current = health.current.min(class.health.max);
+            health.armor = class.health.armor;
+            health.shield_max = class.health.shield_max;
+            health.shield = health.shield.min(class.health.shield_max);
+            health.shield_recharge_rate = class.health.shield_recharge_rate;
+            health.shield_recharge_delay = class.health.shield_recharge_delay;
+
+            // Hull shape and mass live on the rapier collider; swap them in and
+            // let the body recompute its mass from it
+            if let Some(hull) = class.hull.collider().map(|builder| builder.build())
+                && let Some(live) = self.physics.collider_set.get_mut(collider.handle)
+            {
+                live.set_shape(hull.shared_shape().clone());
+                live.set_mass(class.hull.mass);
+                live.set_friction(class.hull.friction);
+                live.set_restitution(class.hull.restitution);
+                ship.size = class.hull.radius();
+            }
+
+            if let Some(body_handle) = self.entity_to_body.get(&entity)
+                && let Some(body) = self.physics.rigid_body_set.get_mut(*body_handle)
+            {
+                body.set_linear_damping(class.hull.linear_damping);
+                body.set_angular_damping(class.hull.angular_damping);
+                body.recompute_mass_properties_from_colliders(&self.physics.collider_set);
+                ship.mass = body.mass();
+            }
+            updated += 1;
+        }
+
+        for (_, weapon) in self.world.query_mut::<&mut Weapon>() {
+            if let Some(def) = content.weapon(&weapon.id) {
+                weapon.weapon_type = def.kind.clone();
+                weapon.cooldown = def.cooldown;
+            }
         }
+
+        self.content = content;
+        info!(
+            "Applied reloaded content to {} ships at tick {}",
+            updated, self.tick
+        );
     }
 
+    /// Start recording; a world that already has entities is saved into the
+    /// recording so replays can start from it
     pub fn enable_recording(&mut self) {
+        let checkpoint = if self.world.is_empty() {
+            None
+        } else {
+            match self.save_state() {
+                Ok(bytes) => Some(bytes),
+                Err(e) => {
+                    warn!("{}; replays of this recording will diverge", e);
+                    None
+                }
+            }
+        };
+        let initial_roster = self
+            .world
+            .query::<(&Player, &Ship)>()
+            .iter()
+            .map(|(_, (player, ship))| RosterEntry {
+                player_id: player.id,
+                name: player.name.clone(),
+                ship_class: ship.class.clone(),
+            })
+            .collect();
         self.input_recorder = Some(InputRecorder {
             recorded_inputs: Vec::new(),
             is_recording: true,
+            start_tick: self.tick,
+            content: self.content.clone(),
+            checkpoint,
+            initial_roster,
+            session_events: Vec::new(),
+            hashes: Vec::new(),
         });
         info!("Input recording enabled for simulation");
     }
 
-    pub fn disable_recording(&mut self) -> Option<Vec<RecordedInput>> {
+    pub fn disable_recording(&mut self) -> Option<Recording> {
         if let Some(recorder) = self.input_recorder.take() {
             info!(
-                "Input recording disabled, collected {} inputs",
-                recorder.recorded_inputs.len()
+                "Input recording disabled, collected {} inputs over {} ticks",
+                recorder.recorded_inputs.len(),
+                recorder.hashes.len()
             );
-            Some(recorder.recorded_inputs)
+            Some(Recording {
+                seed: self.seed,
+                start_tick: recorder.start_tick,
+                tick_epoch_ms: self.tick_epoch_ms,
+                bounds: self.bounds.clone(),
+                friendly_fire: self.friendly_fire,
+                content: recorder.content,
+                checkpoint: recorder.checkpoint,
+                initial_roster: recorder.initial_roster,
+                inputs: recorder.recorded_inputs,
+                session_events: recorder.session_events,
+                hashes: recorder.hashes,
+            })
         } else {
             None
         }
     }
 
+    fn record_session_event(&mut self, event: SessionEvent) {
+        if let Some(recorder) = &mut self.input_recorder
+            && recorder.is_recording
+        {
+            recorder.session_events.push(RecordedSessionEvent {
+                tick: self.tick,
+                input_index: recorder.recorded_inputs.len(),
+                event,
+            });
+        }
+    }
+
+    /// Hash of the whole simulation state, for determinism checks
+    ///
+    /// Covers every networked entity's gameplay components and its rapier
+    /// body, visited in network ID order so the result doesn't depend on
+    /// ECS or physics storage layout. Two simulations fed the same inputs
+    /// must produce the same hash every tick.
+    pub fn state_hash(&self) -> u64 {
+        let mut entities: Vec<(u64, Entity)> = self
+            .world
+            .query::<&NetId>()
+            .iter()
+            .map(|(entity, net_id)| (net_id.0, entity))
+            .collect();
+        entities.sort_unstable_by_key(|(net_id, _)| *net_id);
+
+        let mut hasher = StateHasher::default();
+        hasher.write_u64(self.tick);
+        hasher.write_u64(self.next_net_id);
+        hasher.write_u128(self.rng.get_word_pos());
+        for (net_id, entity) in entities {
+            hasher.write_u64(net_id);
+            self.hash_components(entity, &mut hasher);
+
+            let body = self
+                .entity_to_body
+                .get(&entity)
+                .and_then(|handle| self.physics.rigid_body_set.get(*handle));
+            hasher.write_bool(body.is_some());
+            if let Some(body) = body {
+                hasher.write_u64(body.body_type() as u64);
+                hasher.write_f32(body.translation().x);
+                hasher.write_f32(body.translation().y);
+                hasher.write_f32(body.rotation().angle());
+                hasher.write_f32(body.linvel().x);
+                hasher.write_f32(body.linvel().y);
+                hasher.write_f32(body.angvel());
+                hasher.write_f32(body.user_force().x);
+                hasher.write_f32(body.user_force().y);
+                hasher.write_f32(body.user_torque());
+            }
+        }
+        hasher.finish()
+    }
+
+    /// Feed an entity's gameplay components to a state hash, marking absent ones
+    fn hash_components(&self, entity: Entity, hasher: &mut StateHasher) {
+        let Ok(entity) = self.world.entity(entity) else {
+            return;
+        };
+
+        let transform = entity.get::<&Transform>();
+        hasher.write_bool(transform.is_some());
+        if let Some(transform) = transform {
+            hasher.write_f32(transform.position[0]);
+            hasher.write_f32(transform.position[1]);
+            hasher.write_f32(transform.rotation);
+        }
+
+        let velocity = entity.get::<&Velocity>();
+        hasher.write_bool(velocity.is_some());
+        if let Some(velocity) = velocity {
+            hasher.write_f32(velocity.linear[0]);
+            hasher.write_f32(velocity.linear[1]);
+            hasher.write_f32(velocity.angular);
+        }
+
+        let health = entity.get::<&Health>();
+        hasher.write_bool(health.is_some());
+        if let Some(health) = health {
+            hasher.write_f32(health.current);
+            hasher.write_f32(health.max);
+            hasher.write_f32(health.shield);
+            hasher.write_f64(health.last_damage_time);
+        }
+
+        let player = entity.get::<&Player>();
+        hasher.write_bool(player.is_some());
+        if let Some(player) = player {
+            hasher.write_bytes(player.id.as_bytes());
+            hasher.write_u64(player.score as u64);
+            hasher.write_u64(player.kills as u64);
+            hasher.write_u64(player.deaths as u64);
+            hasher.write_u64(player.credits as u64);
+        }
+
+        // Tuning changes with content reloads, and movement depends on it
+        let ship = entity.get::<&Ship>();
+        hasher.write_bool(ship.is_some());
+        if let Some(ship) = ship {
+            hasher.write_bytes(ship.class.as_bytes());
+            hasher.write_f32(ship.thrust_power);
+            hasher.write_f32(ship.turn_rate);
+            hasher.write_f32(ship.max_speed);
+            hasher.write_f32(ship.mass);
+            hasher.write_f32(ship.size);
+        }
+
+        let weapon = entity.get::<&Weapon>();
+        hasher.write_bool(weapon.is_some());
+        if let Some(weapon) = weapon {
+            hasher.write_f64(weapon.last_fire_time);
+            hasher.write_u64(weapon.ammo.map_or(u64::MAX, |ammo| ammo as u64));
+        }
+
+        let projectile = entity.get::<&Projectile>();
+        hasher.write_bool(projectile.is_some());
+        if let Some(projectile) = projectile {
+            hasher.write_f32(projectile.damage);
+            hasher.write_bytes(projectile.owner_id.as_bytes());
+        }
+
+        let lifetime = entity.get::<&Lifetime>();
+        hasher.write_bool(lifetime.is_some());
+        if let Some(lifetime) = lifetime {
+            hasher.write_f32(lifetime.remaining);
+        }
+
+        let input_buffer = entity.get::<&InputBuffer>();
+        hasher.write_bool(input_buffer.is_some());
+        if let Some(input_buffer) = input_buffer {
+            hasher.write_u64(input_buffer.last_processed_sequence as u64);
+            hasher.write_u64(input_buffer.buffer.len() as u64);
+        }
+
+        hasher.write_bool(entity.has::<Frozen>());
+    }
+
+    /// The ship entity of a player
+    pub fn player_entity(&self, player_id: Uuid) -> Option<Entity> {
+        self.world
+            .query::<&Player>()
+            .iter()
+            .find(|(_, player)| player.id == player_id)
+            .map(|(entity, _)| entity)
+    }
+
+    /// Reserve the next network ID; register it with [`Self::net_ids`] once the entity is spawned
+    fn allocate_net_id(&mut self) -> NetId {
+        let id = NetId(self.next_net_id);
+        self.next_net_id += 1;
+        id
+    }
+
+    /// Network ID of an entity
+    pub fn net_id(&self, entity: Entity) -> Option<u64> {
+        self.world.get::<&NetId>(entity).map(|id| id.0).ok()
+    }
+
+    /// Entity carrying a network ID
+    pub fn entity_by_net_id(&self, net_id: u64) -> Option<Entity> {
+        self.net_ids.get(&net_id).copied()
+    }
+
     /// Create a player ship entity
     pub fn spawn_player_ship(
         &mut self,
         player_id: Uuid,
         name: String,
+        ship_class: &str,
         spawn_position: Vector<f32>,
     ) -> Entity {
+        let class = self
+            .content
+            .ship(ship_class)
+            .unwrap_or_else(|| {
+                warn!(
+                    "Unknown ship class '{}' for player {}, using default",
+                    ship_class, player_id
+                );
+                self.content.default_ship()
+            })
+            .clone();
+        self.record_session_event(SessionEvent::Joined {
+            player_id,
+            name: name.clone(),
+            ship_class: ship_class.to_string(),
+            position: [spawn_position.x, spawn_position.y],
+        });
+
+        let weapon = self
+            .content
+            .weapon(&class.primary_weapon)
+            .map(|w| w.to_component())
+            .expect("Ship primary weapon is validated when content loads");
+
         // Create physics body
         let rigid_body = RigidBodyBuilder::dynamic()
             .translation(spawn_position)
-            .linear_damping(0.4) // Realistic damping for smooth gameplay
-            .angular_damping(1.0) // Realistic damping for smooth gameplay
+            .linear_damping(class.hull.linear_damping)
+            .angular_damping(class.hull.angular_damping)
             .build();
 
         let body_handle = self.physics.rigid_body_set.insert(rigid_body);
 
         // Create collider
-        let collider = ColliderBuilder::ball(8.0) // Ship radius
-            .density(1.0)
-            .friction(0.0)
-            .restitution(0.8)
+        let collider = class
+            .hull
+            .collider()
+            .expect("Ship hulls are validated when content loads")
             .build();
 
         let collider_handle = self.physics.collider_set.insert_with_parent(
@@ -248,10 +695,12 @@ impl GameSimulation {
         );
 
         // Create ECS entity with components
+        let net_id = self.allocate_net_id();
         let entity = self.world.spawn((
+            net_id,
             Transform::from_vector(spawn_position, 0.0),
             Velocity::default(),
-            Health::default(),
+            class.health.to_component(),
             Player {
                 id: player_id,
                 name: name.clone(),
@@ -261,17 +710,8 @@ impl GameSimulation {
                 credits: 0,
             },
             InputBuffer::default(),
-            Ship::default(),
-            Weapon {
-                weapon_type: WeaponType::RapidFire {
-                    rate: 5.0, // 5 shots per second
-                    damage: 25.0,
-                    speed: 300.0, // pixels per second
-                },
-                last_fire_time: 0.0,
-                ammo: None,    // Unlimited ammo for now
-                cooldown: 0.2, // 200ms cooldown
-            },
+            class.to_component(),
+            weapon,
             crate::components::RigidBody {
                 handle: body_handle,
             },
@@ -280,9 +720,15 @@ impl GameSimulation {
             },
         ));
 
-        // Map entity to physics body
+        // Map entity to physics body and network ID
         self.entity_to_body.insert(entity, body_handle);
         self.body_to_entity.insert(body_handle, entity);
+        self.net_ids.insert(net_id.0, entity);
+        self.events.push(GameEvent::Spawned {
+            entity_id: net_id.0,
+            kind: EntityKind::Player,
+            position: [spawn_position.x, spawn_position.y],
+        });
 
         // Update Ship component with physics-calculated values for consistency
         if let Ok(mut ship) = self.world.get::<&mut Ship>(entity)
@@ -291,23 +737,20 @@ impl GameSimulation {
             // Update mass with physics-calculated value
             ship.mass = body.mass();
 
-            // Update size with collider radius (we know it's a ball collider with radius 8.0)
-            ship.size = 8.0;
-
             // Log the final ship configuration that will be sent to client
-            info!("🚀 Ship config for Player [{}]:", name);
-            info!("  Mass: {:.1}kg | Size: {:.1}px", ship.mass, ship.size);
-            info!("  Thrust Power: {:.0}N", ship.thrust_power);
-            info!("  Turn Rate: {:.1} rad/s", ship.turn_rate);
-            info!("  Max Speed: {:.1} px/s", ship.max_speed);
-
-            info!(
-                "Created new ship for player[{}] with physics mass[{:.1}], linear_damp[{}], angular_damp[{}]. and spawn[{}]",
-                player_id,
-                body.mass(),
-                body.linear_damping(),
-                body.angular_damping(),
-                body.position()
+            debug!(
+                player = %name,
+                class = %class.name,
+                mass = ship.mass,
+                size = ship.size,
+                thrust_power = ship.thrust_power,
+                turn_rate = ship.turn_rate,
+                max_speed = ship.max_speed,
+                linear_damping = body.linear_damping(),
+                angular_damping = body.angular_damping(),
+                spawn_x = body.translation().x,
+                spawn_y = body.translation().y,
+                "Ship config"
             );
         }
 
@@ -315,8 +758,19 @@ impl GameSimulation {
         entity
     }
 
-    /// Remove a player ship entity
-    pub fn despawn_entity(&mut self, entity: Entity) {
+    /// Remove an entity, telling clients why it went away
+    pub fn despawn_entity(&mut self, entity: Entity, reason: DespawnReason) {
+        let departing = self
+            .world
+            .get::<&Player>(entity)
+            .map(|player| player.id)
+            .ok();
+        if let Some(player_id) = departing
+            && reason == DespawnReason::Left
+        {
+            self.record_session_event(SessionEvent::Left { player_id });
+        }
+
         // Remove physics body if it exists
         if let Ok(rigid_body) = self.world.get::<&crate::components::RigidBody>(entity) {
             let body_handle = rigid_body.handle;
@@ -336,6 +790,14 @@ impl GameSimulation {
             self.body_to_entity.remove(&body_handle);
         }
 
+        if let Ok(net_id) = self.world.get::<&NetId>(entity) {
+            self.net_ids.remove(&net_id.0);
+            self.events.push(GameEvent::Despawned {
+                entity_id: net_id.0,
+                reason,
+            });
+        }
+
         // Remove ECS entity
         if let Err(e) = self.world.despawn(entity) {
             warn!("Failed to despawn entity {:?}: {}", entity, e);
@@ -344,13 +806,50 @@ impl GameSimulation {
         }
     }
 
-    /// Add input for a specific player
-    pub fn add_player_input(&mut self, player_id: Uuid, input: InputData) {
-        info!(
-            "🔧 Adding input to simulation: player={}, thrust={}, turn={}, seq={}",
-            player_id, input.thrust, input.turn, input.sequence
-        );
+    /// Freeze a ship in place while its player is disconnected
+    pub fn freeze_entity(&mut self, entity: Entity) {
+        if let Ok(player_id) = self.world.get::<&Player>(entity).map(|player| player.id) {
+            self.record_session_event(SessionEvent::Disconnected { player_id });
+        }
+
+        if let Some(body_handle) = self.entity_to_body.get(&entity)
+            && let Some(body) = self.physics.rigid_body_set.get_mut(*body_handle)
+        {
+            body.set_linvel(Vector::zeros(), false);
+            body.set_angvel(0.0, false);
+            body.set_body_type(RigidBodyType::KinematicPositionBased, true);
+        }
+
+        if let Ok(mut input_buffer) = self.world.get::<&mut InputBuffer>(entity) {
+            input_buffer.buffer.clear();
+        }
+        let _ = self.world.insert_one(entity, Frozen);
+        debug!("Froze entity: {:?}", entity);
+    }
+
+    /// Return a frozen ship to normal physics control
+    pub fn thaw_entity(&mut self, entity: Entity) {
+        if let Ok(player_id) = self.world.get::<&Player>(entity).map(|player| player.id) {
+            self.record_session_event(SessionEvent::Reconnected { player_id });
+        }
 
+        if let Some(body_handle) = self.entity_to_body.get(&entity)
+            && let Some(body) = self.physics.rigid_body_set.get_mut(*body_handle)
+        {
+            body.set_body_type(RigidBodyType::Dynamic, true);
+        }
+
+        // The resumed connection numbers its inputs afresh, so forget the old sequence
+        if let Ok(mut input_buffer) = self.world.get::<&mut InputBuffer>(entity) {
+            input_buffer.buffer.clear();
+            input_buffer.last_processed_sequence = 0;
+        }
+        let _ = self.world.remove_one::<Frozen>(entity);
+        debug!("Thawed entity: {:?}", entity);
+    }
+
+    /// Add input for a specific player
+    pub fn add_player_input(&mut self, player_id: Uuid, input: InputData) -> InputOutcome {
         // Record input if recording is enabled
         if let Some(recorder) = &mut self.input_recorder
             && recorder.is_recording
@@ -363,36 +862,28 @@ impl GameSimulation {
         }
 
         // Find the player's entity and add input to their buffer
-        let mut found_player = false;
         for (_, (player, input_buffer)) in self.world.query_mut::<(&Player, &mut InputBuffer)>() {
             if player.id == player_id {
-                info!("✅ Found matching player in ECS world: {}", player.name);
-                input_buffer.add_input(input);
-                info!(
-                    "📝 Input added to buffer. Buffer size: {}",
-                    input_buffer.buffer.len()
-                );
-                found_player = true;
-                break;
+                if !input_buffer.add_input(input) {
+                    trace!("Dropped duplicate input from {}", player.name);
+                    return InputOutcome::Duplicate;
+                }
+                trace!(buffered = input_buffer.buffer.len(), "Buffered input");
+                return InputOutcome::Buffered;
             }
         }
 
-        if !found_player {
-            warn!(
-                "❌ No matching player found in ECS world for player_id: {}",
-                player_id
-            );
-            info!("Available players in ECS:");
-            for (_, player) in self.world.query::<&Player>().iter() {
-                info!("  - Player: {} (id: {})", player.name, player.id);
-            }
-        }
+        debug!("No ship found for input from player {}", player_id);
+        InputOutcome::NoShip
     }
 
     /// Step the simulation forward by one tick
     pub fn step(&mut self, dt: f32) -> SimulationStepResult {
         let step_start = Instant::now();
 
+        // Apply any hot-reloaded balance data before this tick runs
+        self.apply_pending_content();
+
         // Prepare inputs for processing
         self.prepare_inputs(dt);
 
@@ -407,6 +898,11 @@ impl GameSimulation {
         // Sync physics back to ECS
         self.sync_physics_to_ecs();
 
+        // Record where every target is this tick, then resolve projectile hits against that history
+        let now_ms = self.time_of_tick(self.tick + 1);
+        self.record_hitboxes(now_ms);
+        self.resolve_projectile_hits(dt, now_ms);
+
         // Update game logic systems
         self.update_lifetime_system(dt);
         self.update_health_system(dt);
@@ -417,9 +913,41 @@ impl GameSimulation {
         // Advance tick
         self.tick += 1;
 
-        // Generate snapshot every tick (15 Hz simulation = 15 Hz snapshots)
+        // Hash every recorded tick so replays can be checked against this run,
+        // and log one now and then so live rooms can be compared too
+        let recording = self
+            .input_recorder
+            .as_ref()
+            .is_some_and(|recorder| recorder.is_recording);
+        let log_hash = self.tick.is_multiple_of(STATE_HASH_LOG_INTERVAL);
+        if recording || log_hash {
+            let hash = self.state_hash();
+            if log_hash {
+                debug!("State hash at tick {}: {:016x}", self.tick, hash);
+            }
+            if let Some(recorder) = &mut self.input_recorder
+                && recorder.is_recording
+            {
+                recorder.hashes.push(TickHash {
+                    tick: self.tick,
+                    hash,
+                });
+            }
+        }
+
+        // Generate snapshots every tick (15 Hz simulation = 15 Hz snapshots)
         self.snapshot_sequence += 1;
-        let snapshot = Some(self.generate_snapshot());
+        let (viewers, always_relevant) = self.viewers();
+        let radii = InterestRadii::for_bounds(&self.bounds);
+        let events = interest::events_for(
+            &std::mem::take(&mut self.events),
+            &viewers,
+            &always_relevant,
+            radii,
+            &self.interest,
+        );
+        let snapshots = self.generate_player_snapshots(&viewers, &always_relevant, radii);
+        let entity_info = self.changed_entity_info();
 
         let step_duration = step_start.elapsed();
 
@@ -427,7 +955,9 @@ impl GameSimulation {
             tick: self.tick,
             step_duration,
             entity_count: self.world.len(),
-            snapshot,
+            snapshots,
+            entity_info,
+            events,
         }
     }
 
@@ -450,7 +980,7 @@ impl GameSimulation {
             // Log only when discarding inputs (indicates network issues or processing lag)
             if discarded_count > 0 {
                 debug!(
-                    "⚠️  Player {} discarded {} older inputs, kept latest",
+                    "Player {} discarded {} older inputs, kept latest",
                     player.name, discarded_count
                 );
             }
@@ -514,8 +1044,8 @@ impl GameSimulation {
                 // Log actual physics actions taken
                 if let Some(player) = player {
                     if thrust_value != 0.0 {
-                        debug!(
-                            "⚡ Applying thrust to {}: force=[{:.1}, {:.1}]N, magnitude={:.1}",
+                        trace!(
+                            "Applying thrust to {}: force=[{:.1}, {:.1}]N, magnitude={:.1}",
                             player.name,
                             thrust_force.x,
                             thrust_force.y,
@@ -524,13 +1054,13 @@ impl GameSimulation {
                     }
 
                     if turn_value != 0.0 {
-                        debug!("🌀 Applying torque to {}: {:.1}N⋅m", player.name, torque);
+                        trace!("Applying torque to {}: {:.1}N⋅m", player.name, torque);
                     }
 
                     // Log zero force application for damping (less frequently to avoid spam)
                     if thrust_value == 0.0 || turn_value == 0.0 {
-                        debug!(
-                            "🛑 Applying zero forces to {} for damping integration",
+                        trace!(
+                            "Applying zero forces to {} for damping integration",
                             player.name
                         );
                     }
@@ -544,7 +1074,9 @@ impl GameSimulation {
 
     /// Process weapon firing for all players
     fn process_weapon_firing(&mut self, current_time: f64) {
+        let content = self.content.clone();
         let mut projectiles_to_spawn = Vec::new();
+        let mut beams_to_fire = Vec::new();
 
         // Check all players for weapon firing
         for (_entity, (transform, player, input_buffer, weapon)) in
@@ -555,30 +1087,73 @@ impl GameSimulation {
                 // Check primary fire
                 if latest_input.primary_fire
                     && current_time - weapon.last_fire_time >= weapon.cooldown as f64
-                    && let WeaponType::RapidFire { damage, speed, .. } = weapon.weapon_type
+                    && let Some(weapon_def) = content.weapon(&weapon.id)
                 {
-                    // Calculate spawn position (front of ship)
-                    let spawn_offset = 15.0; // Spawn projectile in front of ship
-                    let spawn_position = Vector::new(
-                        transform.position[0] + transform.rotation.cos() * spawn_offset,
-                        transform.position[1] + transform.rotation.sin() * spawn_offset,
-                    );
-
-                    // Calculate projectile velocity
-                    let projectile_velocity = Vector::new(
-                        transform.rotation.cos() * speed,
-                        transform.rotation.sin() * speed,
-                    );
+                    // Direction offsets (radians) of each projectile fired this shot
+                    let (shots, damage, speed): (Vec<f32>, f32, f32) = match weapon.weapon_type {
+                        WeaponType::RapidFire { damage, speed, .. } => (vec![0.0], damage, speed),
+                        WeaponType::Spread {
+                            count,
+                            spread_angle,
+                            damage,
+                            speed,
+                        } => {
+                            let shots = if count > 1 {
+                                let step = spread_angle / (count - 1) as f32;
+                                (0..count)
+                                    .map(|i| i as f32 * step - spread_angle / 2.0)
+                                    .collect()
+                            } else {
+                                vec![0.0; count as usize]
+                            };
+                            (shots, damage, speed)
+                        }
+                        // Beams are hitscan: one pulse per cooldown, resolved after this pass
+                        WeaponType::Beam {
+                            damage_per_second,
+                            range,
+                        } => {
+                            let pulse = weapon.cooldown.max(1.0 / SIM_TICK_RATE);
+                            beams_to_fire.push((
+                                player.id,
+                                Vector::new(transform.position[0], transform.position[1]),
+                                transform.rotation,
+                                range,
+                                damage_per_second * pulse,
+                            ));
+                            weapon.last_fire_time = current_time;
+                            (Vec::new(), 0.0, 0.0)
+                        }
+                        // Other archetypes don't fire yet
+                        _ => (Vec::new(), 0.0, 0.0),
+                    };
+
+                    if !shots.is_empty() {
+                        // Calculate spawn position (front of ship)
+                        let spawn_offset = weapon_def.projectile.spawn_offset;
+                        let spawn_position = Vector::new(
+                            transform.position[0] + transform.rotation.cos() * spawn_offset,
+                            transform.position[1] + transform.rotation.sin() * spawn_offset,
+                        );
 
-                    projectiles_to_spawn.push((
-                        spawn_position,
-                        projectile_velocity,
-                        damage,
-                        player.id,
-                    ));
+                        for offset in shots {
+                            // Calculate projectile velocity
+                            let angle = transform.rotation + offset;
+                            let projectile_velocity =
+                                Vector::new(angle.cos() * speed, angle.sin() * speed);
+
+                            projectiles_to_spawn.push((
+                                spawn_position,
+                                projectile_velocity,
+                                damage,
+                                player.id,
+                                weapon_def.projectile.clone(),
+                            ));
+                        }
 
-                    weapon.last_fire_time = current_time;
-                    debug!("Player {} fired primary weapon", player.name);
+                        weapon.last_fire_time = current_time;
+                        debug!("Player {} fired primary weapon", player.name);
+                    }
                 }
 
                 // TODO: Add secondary fire processing here when needed
@@ -592,8 +1167,38 @@ impl GameSimulation {
         }
 
         // Spawn all projectiles
-        for (position, velocity, damage, owner_id) in projectiles_to_spawn {
-            self.spawn_projectile(position, velocity, damage, owner_id);
+        for (position, velocity, damage, owner_id, projectile) in projectiles_to_spawn {
+            self.spawn_projectile(position, velocity, damage, owner_id, &projectile);
+        }
+
+        for (shooter, origin, rotation, range, damage) in beams_to_fire {
+            self.fire_beam(shooter, origin, rotation, range, damage);
+        }
+    }
+
+    /// Resolve a hitscan shot against targets as the shooter saw them
+    fn fire_beam(
+        &mut self,
+        shooter: Uuid,
+        origin: Vector<f32>,
+        rotation: f32,
+        range: f32,
+        damage: f32,
+    ) {
+        let view_time = self.view_time_ms(shooter, self.tick_time_ms());
+        let direction = Vector::new(rotation.cos(), rotation.sin());
+        let hit = self
+            .rewind
+            .raycast(view_time, origin, direction, range, |hitbox| {
+                self.can_hit(shooter, hitbox)
+            });
+
+        if let Some((target, distance)) = hit {
+            debug!(
+                "Beam from {} hit {:?} at {:.0}px (rewound to {}ms)",
+                shooter, target, distance, view_time
+            );
+            self.apply_hit(target, shooter, damage);
         }
     }
 
@@ -604,6 +1209,7 @@ impl GameSimulation {
         velocity: Vector<f32>,
         damage: f32,
         owner_id: Uuid,
+        projectile: &ProjectileDef,
     ) {
         // Create physics body for projectile
         let rigid_body = RigidBodyBuilder::kinematic_velocity_based()
@@ -614,8 +1220,10 @@ impl GameSimulation {
         let body_handle = self.physics.rigid_body_set.insert(rigid_body);
 
         // Create collider for projectile
-        let collider = ColliderBuilder::ball(2.0) // Small projectile radius
-            .density(0.1)
+        // Projectiles are sensors: hits are resolved against the rewind history, not by contact
+        let collider = ColliderBuilder::ball(projectile.radius)
+            .sensor(true)
+            .density(projectile.density)
             .friction(0.0)
             .restitution(0.0)
             .build();
@@ -627,17 +1235,19 @@ impl GameSimulation {
         );
 
         // Create ECS entity for projectile
+        let net_id = self.allocate_net_id();
         let entity = self.world.spawn((
+            net_id,
             Transform::from_vector(position, velocity.y.atan2(velocity.x)),
             Velocity::from_vector(velocity, 0.0),
             Projectile {
                 damage,
-                lifetime: 3.0, // 3 seconds
+                lifetime: projectile.lifetime,
                 speed: velocity.magnitude(),
                 owner_id,
             },
             Lifetime {
-                remaining: 3.0, // 3 seconds
+                remaining: projectile.lifetime,
             },
             crate::components::RigidBody {
                 handle: body_handle,
@@ -647,9 +1257,15 @@ impl GameSimulation {
             },
         ));
 
-        // Map entity to physics body
+        // Map entity to physics body and network ID
         self.entity_to_body.insert(entity, body_handle);
         self.body_to_entity.insert(body_handle, entity);
+        self.net_ids.insert(net_id.0, entity);
+        self.events.push(GameEvent::Spawned {
+            entity_id: net_id.0,
+            kind: EntityKind::Projectile,
+            position: [position.x, position.y],
+        });
 
         debug!(
             "Spawned projectile: entity={:?}, owner={}",
@@ -687,7 +1303,7 @@ impl GameSimulation {
 
         // PHYSICS DIAGNOSTICS: Compare velocities after physics step
         if !velocity_before.is_empty() && self.tick.is_multiple_of(15) {
-            debug!("🔬 PHYSICS STEP DIAGNOSTICS (dt={:.4}s):", dt);
+            trace!("Physics step diagnostics (dt={:.4}s):", dt);
             for (body_handle, vel_before) in velocity_before {
                 if let Some(body) = self.physics.rigid_body_set.get(body_handle) {
                     let vel_after = body.linvel().magnitude();
@@ -695,7 +1311,7 @@ impl GameSimulation {
                     let change = vel_after - vel_before;
                     let expected_change = -vel_before * damping * dt;
 
-                    debug!(
+                    trace!(
                         "  Body {:?}: vel {:.2}→{:.2} px/s (Δ{:+.2}) | damping={:.1} | expected Δ{:+.2}",
                         body_handle.into_raw_parts(),
                         vel_before,
@@ -735,16 +1351,16 @@ impl GameSimulation {
                     if self.tick.is_multiple_of(15)
                         && (linear_vel.magnitude() > 0.1 || angular_vel.abs() > 0.001)
                     {
-                        debug!(
-                            "🚀 Player {} velocity: linear={:.2} px/s [{:.1}, {:.1}], angular={:.3} rad/s",
+                        trace!(
+                            "Player {} velocity: linear={:.2} px/s [{:.1}, {:.1}], angular={:.3} rad/s",
                             player.name,
                             linear_vel.magnitude(),
                             linear_vel.x,
                             linear_vel.y,
                             angular_vel
                         );
-                        debug!(
-                            "🔧 Body damping: linear={:.3}, angular={:.3}",
+                        trace!(
+                            "Body damping: linear={:.3}, angular={:.3}",
                             body.linear_damping(),
                             body.angular_damping()
                         );
@@ -752,8 +1368,8 @@ impl GameSimulation {
 
                     // Additional debug: Log when velocity should be decaying but isn't
                     if self.tick.is_multiple_of(5) && linear_vel.magnitude() > 10.0 {
-                        debug!(
-                            "⚠️  Player {} velocity NOT decaying: {:.2} px/s (tick {}) - NO THRESHOLDING",
+                        trace!(
+                            "Player {} velocity NOT decaying: {:.2} px/s (tick {}) - NO THRESHOLDING",
                             player.name,
                             linear_vel.magnitude(),
                             self.tick
@@ -767,6 +1383,178 @@ impl GameSimulation {
         }
     }
 
+    /// Record the hitbox of every damageable entity at `time_ms`
+    fn record_hitboxes(&mut self, time_ms: u64) {
+        let hitboxes = self
+            .world
+            .query::<(&crate::components::Collider, &Health, Option<&Player>)>()
+            .without::<&Projectile>()
+            .without::<&Frozen>()
+            .iter()
+            .filter_map(|(entity, (collider, _, player))| {
+                let collider = self.physics.collider_set.get(collider.handle)?;
+                Some(Hitbox {
+                    entity,
+                    player_id: player.map(|player| player.id),
+                    position: collider.position().translation.vector,
+                    rotation: collider.position().rotation.angle(),
+                    shape: collider.shared_shape().clone(),
+                })
+            })
+            .collect();
+        self.rewind.record(time_ms, hitboxes);
+    }
+
+    /// Whether a shot from `shooter` can damage `hitbox`
+    fn can_hit(&self, shooter: Uuid, hitbox: &Hitbox) -> bool {
+        match hitbox.player_id {
+            Some(player_id) => player_id != shooter && self.friendly_fire,
+            None => true,
+        }
+    }
+
+    /// Test each projectile's path this tick against targets
+    ///
+    /// Fast projectiles are tested against targets where the shooter saw them;
+    /// slow ones are visibly in flight on every client, so they use the present.
+    fn resolve_projectile_hits(&mut self, dt: f32, now_ms: u64) {
+        let projectiles: Vec<_> = self
+            .world
+            .query::<(&Projectile, &Transform, &Velocity)>()
+            .iter()
+            .map(|(entity, (projectile, transform, velocity))| {
+                (
+                    entity,
+                    Vector::new(transform.position[0], transform.position[1]),
+                    Vector::new(velocity.linear[0], velocity.linear[1]),
+                    projectile.damage,
+                    projectile.owner_id,
+                )
+            })
+            .collect();
+
+        let mut hits = Vec::new();
+        for (entity, position, velocity, damage, owner_id) in projectiles {
+            let travel = velocity * dt;
+            let distance = travel.magnitude();
+            if distance <= f32::EPSILON {
+                continue;
+            }
+            let time = if velocity.magnitude() >= FAST_PROJECTILE_SPEED {
+                self.view_time_ms(owner_id, now_ms)
+            } else {
+                now_ms
+            };
+            let hit = self.rewind.raycast(
+                time,
+                position - travel,
+                travel / distance,
+                distance,
+                |hitbox| self.can_hit(owner_id, hitbox),
+            );
+            if let Some((target, _)) = hit {
+                hits.push((entity, target, owner_id, damage));
+            }
+        }
+
+        for (projectile, target, owner_id, damage) in hits {
+            self.despawn_entity(projectile, DespawnReason::Hit);
+            self.apply_hit(target, owner_id, damage);
+        }
+    }
+
+    /// Damage a target, crediting the shooter if it is destroyed
+    fn apply_hit(&mut self, target: Entity, shooter: Uuid, damage: f32) {
+        let current_time = self.tick as f64 * (1.0 / SIM_TICK_RATE as f64);
+        let lethal = match self.world.get::<&mut Health>(target) {
+            Ok(mut health) => health.take_damage(damage, current_time),
+            Err(_) => return,
+        };
+        let entity_id = self.net_id(target).unwrap_or(0);
+        let position = self
+            .world
+            .get::<&Transform>(target)
+            .map(|transform| transform.position)
+            .unwrap_or_default();
+        self.events.push(GameEvent::Hit {
+            entity_id,
+            shooter_id: shooter,
+            damage,
+            position,
+        });
+        if !lethal {
+            return;
+        }
+
+        let radius = self
+            .world
+            .get::<&crate::components::Collider>(target)
+            .ok()
+            .and_then(|collider| self.physics.collider_set.get(collider.handle))
+            .map(|collider| collider.shape().compute_local_bounding_sphere().radius)
+            .unwrap_or(0.0);
+        self.events.push(GameEvent::Killed {
+            entity_id,
+            killer_id: shooter,
+        });
+        self.events.push(GameEvent::Explosion { position, radius });
+
+        // Destroyed ships are worth their class's score; other targets don't score yet
+        let score = self
+            .world
+            .get::<&Ship>(target)
+            .ok()
+            .and_then(|ship| self.content.ship(&ship.class).map(|class| class.score))
+            .unwrap_or(0);
+        if let Some(shooter_entity) = self.player_entity(shooter)
+            && let Ok(mut player) = self.world.get::<&mut Player>(shooter_entity)
+        {
+            player.kills += 1;
+            player.score += score;
+        }
+
+        let victim = self
+            .world
+            .get::<&mut Player>(target)
+            .map(|mut player| {
+                player.deaths += 1;
+                player.name.clone()
+            })
+            .ok();
+        match victim {
+            Some(name) => {
+                info!("Player {} was destroyed by {}", name, shooter);
+                self.respawn_ship(target);
+            }
+            None => self.despawn_entity(target, DespawnReason::Destroyed),
+        }
+    }
+
+    /// Restore a destroyed ship somewhere in the middle half of the arena
+    fn respawn_ship(&mut self, entity: Entity) {
+        let position = self.bounds.center
+            + Vector::new(
+                self.rng.random_range(-0.25..0.25) * self.bounds.width,
+                self.rng.random_range(-0.25..0.25) * self.bounds.height,
+            );
+        if let Ok(mut health) = self.world.get::<&mut Health>(entity) {
+            health.current = health.max;
+            health.shield = health.shield_max;
+        }
+        if let Ok(mut transform) = self.world.get::<&mut Transform>(entity) {
+            transform.position = [position.x, position.y];
+        }
+        if let Some(body_handle) = self.entity_to_body.get(&entity)
+            && let Some(body) = self.physics.rigid_body_set.get_mut(*body_handle)
+        {
+            body.set_translation(position, true);
+            body.set_linvel(Vector::zeros(), true);
+            body.set_angvel(0.0, true);
+        }
+        // The ship's past hitboxes are where it died, not where it is now
+        self.rewind.forget(entity);
+    }
+
     /// Update lifetime system for temporary entities
     fn update_lifetime_system(&mut self, dt: f32) {
         let mut entities_to_remove = Vec::new();
@@ -779,7 +1567,7 @@ impl GameSimulation {
         }
 
         for entity in entities_to_remove {
-            self.despawn_entity(entity);
+            self.despawn_entity(entity, DespawnReason::Expired);
         }
     }
 
@@ -977,12 +1765,100 @@ impl GameSimulation {
         debug_data
     }
 
+    /// Static data for every entity that has any, as sent to joining players
+    pub fn entity_infos(&self) -> Vec<EntityInfo> {
+        self.world
+            .query::<(&NetId, &Player, Option<&Ship>)>()
+            .iter()
+            .map(|(_, (net_id, player, ship))| EntityInfo {
+                entity_id: net_id.0,
+                player: Some(PlayerInfo {
+                    id: player.id,
+                    name: player.name.clone(),
+                }),
+                ship: ship.cloned(),
+            })
+            .collect()
+    }
+
+    /// Static data that is new or changed since the last tick
+    fn changed_entity_info(&mut self) -> Vec<EntityInfo> {
+        let current: HashMap<u64, EntityInfo> = self
+            .entity_infos()
+            .into_iter()
+            .map(|info| (info.entity_id, info))
+            .collect();
+
+        let changed = current
+            .values()
+            .filter(|info| self.sent_entity_info.get(&info.entity_id) != Some(*info))
+            .cloned()
+            .collect();
+        self.sent_entity_info = current;
+        changed
+    }
+
+    /// Connected players' ships, and the entities sent to them at any range
+    fn viewers(&self) -> (Vec<Viewer>, HashSet<u64>) {
+        let viewers: Vec<Viewer> = self
+            .world
+            .query::<(&NetId, &Player, &Transform)>()
+            .without::<&Frozen>()
+            .iter()
+            .map(|(_, (net_id, player, transform))| Viewer {
+                player_id: player.id,
+                entity_id: net_id.0,
+                position: transform.position,
+            })
+            .collect();
+
+        // Teammates and marked entities (such as bosses) are sent at any range
+        let mut always_relevant: HashSet<u64> = self
+            .world
+            .query::<&NetId>()
+            .with::<&Player>()
+            .iter()
+            .map(|(_, net_id)| net_id.0)
+            .collect();
+        always_relevant.extend(
+            self.world
+                .query::<&NetId>()
+                .with::<&AlwaysRelevant>()
+                .iter()
+                .map(|(_, net_id)| net_id.0),
+        );
+        (viewers, always_relevant)
+    }
+
+    /// Generate each connected player's snapshot, filtered to what is near their ship
+    fn generate_player_snapshots(
+        &mut self,
+        viewers: &[Viewer],
+        always_relevant: &HashSet<u64>,
+        radii: InterestRadii,
+    ) -> HashMap<Uuid, PackedSnapshot> {
+        let snapshot = self.generate_snapshot();
+        let arena = Arena::from_bounds(&self.bounds);
+        interest::partition(
+            &snapshot,
+            viewers,
+            always_relevant,
+            radii,
+            &mut self.interest,
+        )
+        .into_iter()
+        .map(|(player_id, snapshot)| (player_id, snapshot_codec::encode(&snapshot, arena)))
+        .collect()
+    }
+
     /// Generate a snapshot of the current game state
     fn generate_snapshot(&self) -> GameSnapshot {
         let mut entities = Vec::new();
 
         // Include all players in snapshot
-        for (entity, (transform, player)) in self.world.query::<(&Transform, &Player)>().iter() {
+        for (entity, (net_id, transform, player)) in
+            self.world.query::<(&NetId, &Transform, &Player)>().iter()
+        {
             let health = self
                 .world
                 .get::<&Health>(entity)
@@ -996,7 +1872,7 @@ impl GameSimulation {
             let ship = self.world.get::<&Ship>(entity).map(|s| (*s).clone()).ok();
 
             entities.push(EntitySnapshot {
-                entity_id: entity.id() as u64, // Convert hecs::Entity to u64
+                entity_id: net_id.0,
                 entity_type: EntityType::Player(player.clone()),
                 transform: transform.clone(),
                 velocity,
@@ -1006,8 +1882,10 @@ impl GameSimulation {
         }
 
         // Include all projectiles in snapshot
-        for (entity, (transform, projectile)) in
-            self.world.query::<(&Transform, &Projectile)>().iter()
+        for (entity, (net_id, transform, projectile)) in self
+            .world
+            .query::<(&NetId, &Transform, &Projectile)>()
+            .iter()
         {
             let velocity = self
                 .world
@@ -1017,7 +1895,7 @@ impl GameSimulation {
             let health = Health::default(); // Projectiles don't have health but we need it for the snapshot
 
             entities.push(EntitySnapshot {
-                entity_id: entity.id() as u64,
+                entity_id: net_id.0,
                 entity_type: EntityType::Projectile(projectile.clone()),
                 transform: transform.clone(),
                 velocity,
@@ -1029,11 +1907,9 @@ impl GameSimulation {
         GameSnapshot {
             sequence: self.snapshot_sequence,
             tick: self.tick,
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_millis() as u64,
+            timestamp: self.tick_time_ms(),
             entities,
+            deferred: Vec::new(),
         }
     }
 }
@@ -1044,7 +1920,65 @@ pub struct SimulationStepResult {
     pub tick: u64,
     pub step_duration: Duration,
     pub entity_count: u32,
-    pub snapshot: Option<GameSnapshot>,
+    pub snapshots: HashMap<Uuid, PackedSnapshot>, // one per connected player
+    pub entity_info: Vec<EntityInfo>,             // static data to broadcast
+    pub events: HashMap<Uuid, Vec<GameEvent>>,    // what each connected player should hear about
+}
+
+/// What became of an input handed to [`GameSimulation::add_player_input`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputOutcome {
+    Buffered,
+    /// Already buffered or processed; clients resend recent inputs in every packet
+    Duplicate,
+    NoShip,
+}
+
+/// Why an entity left the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum DespawnReason {
+    Expired,   // lifetime ran out
+    Hit,       // projectile struck a target
+    Destroyed, // health reached zero
+    Left,      // owning player left the room
+}
+
+/// Something that happened during a tick, for client effects and kill feeds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(tag = "type")]
+pub enum GameEvent {
+    Spawned {
+        entity_id: u64,
+        kind: EntityKind,
+        position: [f32; 2],
+    },
+    Despawned {
+        entity_id: u64,
+        reason: DespawnReason,
+    },
+    Hit {
+        entity_id: u64,
+        shooter_id: Uuid,
+        damage: f32,
+        position: [f32; 2],
+    },
+    Killed {
+        entity_id: u64,
+        killer_id: Uuid,
+    },
+    // Nothing drops pickups yet
+    Pickup {
+        entity_id: u64,
+        player_id: Uuid,
+        item: String,
+    },
+    Explosion {
+        position: [f32; 2],
+        radius: f32,
+    },
 }
 
 /// Network-serializable snapshot of game state
@@ -1054,6 +1988,9 @@ pub struct GameSnapshot {
     pub tick: u64,
     pub timestamp: u64,
     pub entities: Vec<EntitySnapshot>,
+    /// Entities still in range but skipped this snapshot; clients keep their last state
+    #[serde(default)]
+    pub deferred: Vec<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1072,3 +2009,194 @@ pub enum EntityType {
     Projectile(Projectile),
     Enemy, // Will be expanded in Phase 4
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHOOTER: Uuid = Uuid::from_u128(1);
+    const TARGET: Uuid = Uuid::from_u128(2);
+
+    /// A target that holds at (300, 0) for a while, then jumps to (300, 300)
+    /// and stays there for `ticks_since_move` ticks
+    fn setup(friendly_fire: bool, ticks_since_move: u32) -> (GameSimulation, Entity) {
+        let mut simulation = GameSimulation::new();
+        simulation.friendly_fire = friendly_fire;
+        simulation.spawn_player_ship(SHOOTER, "a".to_string(), "interceptor", Vector::zeros());
+        let target = simulation.spawn_player_ship(
+            TARGET,
+            "b".to_string(),
+            "interceptor",
+            Vector::new(300.0, 0.0),
+        );
+        for _ in 0..15 {
+            simulation.step(1.0 / SIM_TICK_RATE);
+        }
+
+        let handle = simulation.entity_to_body[&target];
+        simulation.physics.rigid_body_set[handle].set_translation(Vector::new(300.0, 300.0), true);
+        for _ in 0..ticks_since_move {
+            simulation.step(1.0 / SIM_TICK_RATE);
+        }
+        simulation.events.clear();
+        (simulation, target)
+    }
+
+    fn shield(simulation: &GameSimulation, entity: Entity) -> f32 {
+        simulation.world.get::<&Health>(entity).unwrap().shield
+    }
+
+    fn fire_at(simulation: &mut GameSimulation, aim: Vector<f32>) {
+        simulation.fire_beam(SHOOTER, Vector::zeros(), aim.y.atan2(aim.x), 1000.0, 10.0);
+    }
+
+    #[test]
+    fn beam_hits_target_where_shooter_saw_it() {
+        let (mut simulation, target) = setup(true, 2);
+        simulation.set_player_latency(SHOOTER, 50.0);
+        let before = shield(&simulation, target);
+
+        fire_at(&mut simulation, Vector::new(300.0, 0.0));
+
+        assert_eq!(shield(&simulation, target), before - 10.0);
+        assert!(simulation.events.iter().any(
+            |event| matches!(event, GameEvent::Hit { shooter_id, .. } if *shooter_id == SHOOTER)
+        ));
+    }
+
+    #[test]
+    fn beam_misses_target_at_present_position() {
+        let (mut simulation, target) = setup(true, 2);
+        simulation.set_player_latency(SHOOTER, 50.0);
+        let before = shield(&simulation, target);
+
+        fire_at(&mut simulation, Vector::new(300.0, 300.0));
+
+        assert_eq!(shield(&simulation, target), before);
+        assert!(simulation.events.is_empty());
+    }
+
+    #[test]
+    fn rewind_is_clamped_to_window() {
+        // Moved 9 ticks (300 ms) ago: inside the history, outside the rewind window
+        let (mut simulation, target) = setup(true, 9);
+        simulation.set_player_latency(SHOOTER, 5_000.0);
+        let now = simulation.tick_time_ms();
+        assert_eq!(simulation.view_time_ms(SHOOTER, now), now - MAX_REWIND_MS);
+        let before = shield(&simulation, target);
+
+        fire_at(&mut simulation, Vector::new(300.0, 0.0));
+        assert_eq!(shield(&simulation, target), before);
+
+        fire_at(&mut simulation, Vector::new(300.0, 300.0));
+        assert_eq!(shield(&simulation, target), before - 10.0);
+    }
+
+    #[test]
+    fn kill_scores_victim_class() {
+        let (mut simulation, target) = setup(true, 2);
+        simulation.set_player_latency(SHOOTER, 50.0);
+        simulation.world.get::<&mut Health>(target).unwrap().current = 1.0;
+        simulation.world.get::<&mut Health>(target).unwrap().shield = 0.0;
+
+        fire_at(&mut simulation, Vector::new(300.0, 0.0));
+
+        let score = simulation.content.ship("interceptor").unwrap().score;
+        let shooter = simulation.player_entity(SHOOTER).unwrap();
+        let player = simulation.world.get::<&Player>(shooter).unwrap();
+        assert_eq!((player.kills, player.score), (1, score));
+        assert_eq!(simulation.world.get::<&Player>(target).unwrap().deaths, 1);
+        let health = simulation.world.get::<&Health>(target).unwrap();
+        assert_eq!(health.current, health.max);
+    }
+
+    #[test]
+    fn respawned_ship_is_not_hit_where_it_died() {
+        let (mut simulation, target) = setup(true, 2);
+        simulation.set_player_latency(SHOOTER, 50.0);
+        simulation.world.get::<&mut Health>(target).unwrap().current = 1.0;
+        simulation.world.get::<&mut Health>(target).unwrap().shield = 0.0;
+
+        fire_at(&mut simulation, Vector::new(300.0, 0.0));
+        assert_eq!(simulation.world.get::<&Player>(target).unwrap().deaths, 1);
+        let health = Health::clone(&simulation.world.get::<&Health>(target).unwrap());
+
+        fire_at(&mut simulation, Vector::new(300.0, 0.0));
+        assert_eq!(
+            simulation.world.get::<&Health>(target).unwrap().current,
+            health.current
+        );
+        assert_eq!(
+            simulation.world.get::<&Health>(target).unwrap().shield,
+            health.shield
+        );
+    }
+
+    #[test]
+    fn players_are_safe_without_friendly_fire() {
+        let (mut simulation, target) = setup(false, 2);
+        let before = shield(&simulation, target);
+
+        fire_at(&mut simulation, Vector::new(300.0, 0.0));
+        fire_at(&mut simulation, Vector::new(300.0, 300.0));
+
+        assert_eq!(shield(&simulation, target), before);
+    }
+
+    #[test]
+    fn reloaded_hull_retunes_live_ships() {
+        let mut simulation = GameSimulation::new();
+        let entity = simulation.spawn_player_ship(
+            Uuid::new_v4(),
+            "a".to_string(),
+            "standard",
+            Vector::new(0.0, 0.0),
+        );
+        let before = simulation.world.get::<&Ship>(entity).unwrap().mass;
+
+        let mut content = ContentRegistry::default();
+        let standard = content.ships.get_mut("standard").unwrap();
+        standard.hull.mass *= 2.0;
+        standard.hull.shape = crate::content::HullShape::Ball { radius: 12.0 };
+        simulation.queue_content(Arc::new(content));
+        simulation.step(1.0 / SIM_TICK_RATE);
+
+        let ship = simulation.world.get::<&Ship>(entity).unwrap();
+        assert!((ship.mass - before * 2.0).abs() < 0.01);
+        assert_eq!(ship.size, 12.0);
+    }
+
+    #[test]
+    fn resumed_ship_accepts_restarted_sequence() {
+        let player_id = Uuid::new_v4();
+        let mut simulation = GameSimulation::new();
+        let entity = simulation.spawn_player_ship(
+            player_id,
+            "a".to_string(),
+            "interceptor",
+            Vector::new(0.0, 0.0),
+        );
+        let input = |sequence| InputData {
+            sequence,
+            timestamp: sequence as u64 * 66,
+            thrust: 1.0,
+            turn: 0.0,
+            primary_fire: false,
+            secondary_fire: false,
+        };
+
+        assert_eq!(
+            simulation.add_player_input(player_id, input(500)),
+            InputOutcome::Buffered
+        );
+        simulation.step(1.0 / SIM_TICK_RATE);
+        simulation.freeze_entity(entity);
+        simulation.thaw_entity(entity);
+
+        // A fresh connection numbers its inputs from 1 again
+        assert_eq!(
+            simulation.add_player_input(player_id, input(1)),
+            InputOutcome::Buffered
+        );
+    }
+}
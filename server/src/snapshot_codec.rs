@@ -0,0 +1,433 @@
+//! Compact snapshot encoding
+//!
+//! Per-tick entity state is quantized and bit-packed:
+//! - Network IDs are varints, so small IDs take a byte and none are truncated
+//! - Positions use 16 bits per axis across the arena bounds
+//! - Rotation uses 16 bits across a full turn
+//! - Velocities use 16 bits per axis (12 for angular) across fixed ranges
+//! - Health and shield are sent as 8-bit fractions of their maximum
+//!
+//! Data that rarely changes (player names, ship configuration) is not part of
+//! the snapshot. It is sent as an [`EntityInfo`] message when an entity
+//! appears or changes, and to each player when they join.
+
+#![allow(dead_code)] // The server only encodes; decoding serves tests and tools
+
+use crate::bitpack::{BitReader, BitWriter, dequantize, quantize};
+use crate::components::Ship;
+use crate::simulation::{EntitySnapshot, EntityType, GameBounds, GameSnapshot};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use uuid::Uuid;
+
+pub const POSITION_BITS: u32 = 16;
+pub const ROTATION_BITS: u32 = 16;
+pub const VELOCITY_BITS: u32 = 16;
+pub const MAX_VELOCITY: f32 = 1024.0; // units per second, per axis
+pub const ANGULAR_VELOCITY_BITS: u32 = 12;
+pub const MAX_ANGULAR_VELOCITY: f32 = 32.0; // radians per second
+pub const FRACTION_BITS: u32 = 8;
+
+const COUNT_BITS: u32 = 16;
+const KIND_BITS: u32 = 2;
+
+/// Arena rectangle positions are quantized across
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Arena {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl Arena {
+    pub fn from_bounds(bounds: &GameBounds) -> Self {
+        let half = [bounds.width / 2.0, bounds.height / 2.0];
+        Self {
+            min: [bounds.center.x - half[0], bounds.center.y - half[1]],
+            max: [bounds.center.x + half[0], bounds.center.y + half[1]],
+        }
+    }
+}
+
+/// Snapshot as sent on the wire
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct PackedSnapshot {
+    pub sequence: u32,
+    pub tick: u64,
+    pub timestamp: u64,
+    pub arena: Arena,
+    #[serde(with = "serde_bytes")]
+    #[cfg_attr(test, ts(type = "Uint8Array | number[]"))] // number[] in JSON
+    pub data: Vec<u8>, // bit-packed entity states
+    /// Entities still in range but skipped this snapshot; clients keep their last state
+    #[serde(default)]
+    pub deferred: Vec<u64>,
+}
+
+/// Entity kind tag carried in every packed entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Player = 0,
+    Projectile = 1,
+    Enemy = 2,
+}
+
+impl EntityKind {
+    fn of(entity_type: &EntityType) -> Self {
+        match entity_type {
+            EntityType::Player(_) => EntityKind::Player,
+            EntityType::Projectile(_) => EntityKind::Projectile,
+            EntityType::Enemy => EntityKind::Enemy,
+        }
+    }
+
+    fn from_bits(bits: u32) -> Result<Self, String> {
+        match bits {
+            0 => Ok(EntityKind::Player),
+            1 => Ok(EntityKind::Projectile),
+            2 => Ok(EntityKind::Enemy),
+            other => Err(format!("Unknown entity kind {}", other)),
+        }
+    }
+
+    /// Projectiles carry no health
+    fn has_health(&self) -> bool {
+        !matches!(self, EntityKind::Projectile)
+    }
+}
+
+/// One entity's per-tick state after unpacking
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityState {
+    pub entity_id: u64,
+    pub kind: EntityKind,
+    pub position: [f32; 2],
+    pub rotation: f32,
+    pub linear_velocity: [f32; 2],
+    pub angular_velocity: f32,
+    pub health: Option<f32>, // fraction of max
+    pub shield: Option<f32>, // fraction of max
+}
+
+/// Static data sent when an entity appears or changes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct EntityInfo {
+    pub entity_id: u64,
+    pub player: Option<PlayerInfo>,
+    pub ship: Option<Ship>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct PlayerInfo {
+    pub id: Uuid,
+    pub name: String,
+}
+
+fn fraction(value: f32, max: f32) -> f32 {
+    if max > 0.0 { value / max } else { 0.0 }
+}
+
+/// Quantize and pack a snapshot for the wire
+pub fn encode(snapshot: &GameSnapshot, arena: Arena) -> PackedSnapshot {
+    let mut writer = BitWriter::new();
+    writer.write(snapshot.entities.len() as u32, COUNT_BITS);
+    for entity in &snapshot.entities {
+        encode_entity(&mut writer, entity, &arena);
+    }
+
+    PackedSnapshot {
+        sequence: snapshot.sequence,
+        tick: snapshot.tick,
+        timestamp: snapshot.timestamp,
+        arena,
+        data: writer.finish(),
+        deferred: snapshot.deferred.clone(),
+    }
+}
+
+fn encode_entity(writer: &mut BitWriter, entity: &EntitySnapshot, arena: &Arena) {
+    let kind = EntityKind::of(&entity.entity_type);
+    writer.write_varint(entity.entity_id);
+    writer.write(kind as u32, KIND_BITS);
+
+    for axis in 0..2 {
+        writer.write(
+            quantize(
+                entity.transform.position[axis],
+                arena.min[axis],
+                arena.max[axis],
+                POSITION_BITS,
+            ),
+            POSITION_BITS,
+        );
+    }
+    // Normalize to -PI..PI before quantizing
+    let rotation = (entity.transform.rotation + PI).rem_euclid(2.0 * PI) - PI;
+    writer.write(quantize(rotation, -PI, PI, ROTATION_BITS), ROTATION_BITS);
+
+    for axis in 0..2 {
+        writer.write(
+            quantize(
+                entity.velocity.linear[axis],
+                -MAX_VELOCITY,
+                MAX_VELOCITY,
+                VELOCITY_BITS,
+            ),
+            VELOCITY_BITS,
+        );
+    }
+    writer.write(
+        quantize(
+            entity.velocity.angular,
+            -MAX_ANGULAR_VELOCITY,
+            MAX_ANGULAR_VELOCITY,
+            ANGULAR_VELOCITY_BITS,
+        ),
+        ANGULAR_VELOCITY_BITS,
+    );
+
+    if kind.has_health() {
+        let health = &entity.health;
+        writer.write(
+            quantize(
+                fraction(health.current, health.max),
+                0.0,
+                1.0,
+                FRACTION_BITS,
+            ),
+            FRACTION_BITS,
+        );
+        writer.write(
+            quantize(
+                fraction(health.shield, health.shield_max),
+                0.0,
+                1.0,
+                FRACTION_BITS,
+            ),
+            FRACTION_BITS,
+        );
+    }
+}
+
+/// Unpack the entity states of a packed snapshot
+pub fn decode(snapshot: &PackedSnapshot) -> Result<Vec<EntityState>, String> {
+    let mut reader = BitReader::new(&snapshot.data);
+    let count = reader.read(COUNT_BITS)?;
+    (0..count)
+        .map(|_| decode_entity(&mut reader, &snapshot.arena))
+        .collect()
+}
+
+fn decode_entity(reader: &mut BitReader, arena: &Arena) -> Result<EntityState, String> {
+    let entity_id = reader.read_varint()?;
+    let kind = EntityKind::from_bits(reader.read(KIND_BITS)?)?;
+
+    let mut position = [0.0; 2];
+    for (axis, value) in position.iter_mut().enumerate() {
+        *value = dequantize(
+            reader.read(POSITION_BITS)?,
+            arena.min[axis],
+            arena.max[axis],
+            POSITION_BITS,
+        );
+    }
+    let rotation = dequantize(reader.read(ROTATION_BITS)?, -PI, PI, ROTATION_BITS);
+
+    let mut linear_velocity = [0.0; 2];
+    for value in linear_velocity.iter_mut() {
+        *value = dequantize(
+            reader.read(VELOCITY_BITS)?,
+            -MAX_VELOCITY,
+            MAX_VELOCITY,
+            VELOCITY_BITS,
+        );
+    }
+    let angular_velocity = dequantize(
+        reader.read(ANGULAR_VELOCITY_BITS)?,
+        -MAX_ANGULAR_VELOCITY,
+        MAX_ANGULAR_VELOCITY,
+        ANGULAR_VELOCITY_BITS,
+    );
+
+    let (health, shield) = if kind.has_health() {
+        (
+            Some(dequantize(
+                reader.read(FRACTION_BITS)?,
+                0.0,
+                1.0,
+                FRACTION_BITS,
+            )),
+            Some(dequantize(
+                reader.read(FRACTION_BITS)?,
+                0.0,
+                1.0,
+                FRACTION_BITS,
+            )),
+        )
+    } else {
+        (None, None)
+    };
+
+    Ok(EntityState {
+        entity_id,
+        kind,
+        position,
+        rotation,
+        linear_velocity,
+        angular_velocity,
+        health,
+        shield,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitpack::max_error;
+    use crate::components::{Health, Player, Projectile, Transform, Velocity};
+    use crate::simulation::EntitySnapshot;
+
+    fn arena() -> Arena {
+        Arena::from_bounds(&GameBounds::default())
+    }
+
+    fn player(entity_id: u64, position: [f32; 2], rotation: f32) -> EntitySnapshot {
+        EntitySnapshot {
+            entity_id,
+            entity_type: EntityType::Player(Player {
+                id: Uuid::new_v4(),
+                name: "Pilot".to_string(),
+                score: 0,
+                kills: 0,
+                deaths: 0,
+                credits: 0,
+            }),
+            transform: Transform { position, rotation },
+            velocity: Velocity {
+                linear: [-87.3, 412.9],
+                angular: -3.7,
+            },
+            health: Health {
+                current: 63.0,
+                max: 150.0,
+                shield: 10.0,
+                shield_max: 40.0,
+                ..Health::default()
+            },
+            ship: None,
+        }
+    }
+
+    fn snapshot(entities: Vec<EntitySnapshot>) -> GameSnapshot {
+        GameSnapshot {
+            sequence: 7,
+            tick: 42,
+            timestamp: 1_000,
+            entities,
+            deferred: vec![3],
+        }
+    }
+
+    #[test]
+    fn round_trip_stays_within_quantization_error() {
+        let arena = arena();
+        let entities = vec![
+            player(1, [-512.25, 311.8], 2.9),
+            player(4_000_000, arena.max, -3.1),
+            player(u32::MAX as u64 + 5, arena.max, 1.2),
+            player(u64::MAX, arena.min, -0.4),
+            player(9, arena.min, 0.0),
+        ];
+        let packed = encode(&snapshot(entities.clone()), arena);
+        let decoded = decode(&packed).unwrap();
+        assert_eq!(decoded.len(), entities.len());
+        assert_eq!(packed.deferred, vec![3]);
+
+        let position_error = (0..2)
+            .map(|axis| max_error(arena.min[axis], arena.max[axis], POSITION_BITS))
+            .fold(0.0, f32::max);
+        let rotation_error = max_error(-PI, PI, ROTATION_BITS);
+        let velocity_error = max_error(-MAX_VELOCITY, MAX_VELOCITY, VELOCITY_BITS);
+        let angular_error = max_error(
+            -MAX_ANGULAR_VELOCITY,
+            MAX_ANGULAR_VELOCITY,
+            ANGULAR_VELOCITY_BITS,
+        );
+        let fraction_error = max_error(0.0, 1.0, FRACTION_BITS);
+        // Float rounding in the dequantize arithmetic
+        let slack = 1e-3;
+
+        for (original, state) in entities.iter().zip(&decoded) {
+            assert_eq!(state.entity_id, original.entity_id);
+            assert_eq!(state.kind, EntityKind::Player);
+            for axis in 0..2 {
+                assert!(
+                    (state.position[axis] - original.transform.position[axis]).abs()
+                        <= position_error + slack
+                );
+                assert!(
+                    (state.linear_velocity[axis] - original.velocity.linear[axis]).abs()
+                        <= velocity_error + slack
+                );
+            }
+            assert!((state.rotation - original.transform.rotation).abs() <= rotation_error + slack);
+            assert!(
+                (state.angular_velocity - original.velocity.angular).abs() <= angular_error + slack
+            );
+            assert!((state.health.unwrap() - 63.0 / 150.0).abs() <= fraction_error + slack);
+            assert!((state.shield.unwrap() - 10.0 / 40.0).abs() <= fraction_error + slack);
+        }
+    }
+
+    #[test]
+    fn rotation_wraps_into_range() {
+        let packed = encode(
+            &snapshot(vec![player(1, [0.0, 0.0], 2.0 * PI + 0.5)]),
+            arena(),
+        );
+        let decoded = decode(&packed).unwrap();
+        assert!((decoded[0].rotation - 0.5).abs() <= max_error(-PI, PI, ROTATION_BITS) + 1e-3);
+    }
+
+    #[test]
+    fn out_of_range_values_clamp() {
+        let arena = arena();
+        let mut entity = player(1, [arena.max[0] + 500.0, arena.min[1] - 500.0], 0.0);
+        entity.velocity.linear = [MAX_VELOCITY * 3.0, 0.0];
+        let decoded = decode(&encode(&snapshot(vec![entity]), arena)).unwrap();
+        assert_eq!(decoded[0].position, [arena.max[0], arena.min[1]]);
+        assert_eq!(decoded[0].linear_velocity[0], MAX_VELOCITY);
+    }
+
+    #[test]
+    fn projectiles_omit_health_and_pack_smaller() {
+        let projectile = EntitySnapshot {
+            entity_type: EntityType::Projectile(Projectile {
+                damage: 25.0,
+                lifetime: 1.0,
+                speed: 300.0,
+                owner_id: Uuid::new_v4(),
+            }),
+            ..player(2, [10.0, 20.0], 1.0)
+        };
+        let ship = encode(&snapshot(vec![player(1, [10.0, 20.0], 1.0)]), arena());
+        let shot = encode(&snapshot(vec![projectile]), arena());
+        assert!(shot.data.len() < ship.data.len());
+
+        let decoded = decode(&shot).unwrap();
+        assert_eq!(decoded[0].kind, EntityKind::Projectile);
+        assert_eq!(decoded[0].health, None);
+    }
+
+    #[test]
+    fn truncated_data_is_an_error() {
+        let mut packed = encode(&snapshot(vec![player(1, [0.0, 0.0], 0.0)]), arena());
+        packed.data.truncate(packed.data.len() / 2);
+        assert!(decode(&packed).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+//! Stable hashing of game state
+//!
+//! Replays compare per-tick hashes of the simulation across runs and content
+//! is identified by a fingerprint, so both need a hash whose output never
+//! changes between builds.
+
+/// FNV-1a, chosen over `DefaultHasher` because its output is fixed across
+/// Rust releases and platforms
+pub struct StateHasher(u64);
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl StateHasher {
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_u128(&mut self, value: u128) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    /// Hash the exact bits, so any drift at all shows up
+    pub fn write_f32(&mut self, value: f32) {
+        self.write_bytes(&value.to_bits().to_le_bytes());
+    }
+
+    pub fn write_f64(&mut self, value: f64) {
+        self.write_bytes(&value.to_bits().to_le_bytes());
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_bytes(&[value as u8]);
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::Ship;
+    use crate::simulation::GameSimulation;
+    use rapier2d::prelude::Vector;
+    use uuid::Uuid;
+
+    #[test]
+    fn matches_fnv1a_reference_values() {
+        assert_eq!(StateHasher::default().finish(), 0xcbf2_9ce4_8422_2325);
+
+        let mut hasher = StateHasher::default();
+        hasher.write_bytes(b"a");
+        assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+
+        let mut hasher = StateHasher::default();
+        hasher.write_bytes(b"foobar");
+        assert_eq!(hasher.finish(), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn state_hash_changes_with_physics_state() {
+        let mut simulation = GameSimulation::new();
+        let entity = simulation.spawn_player_ship(
+            Uuid::new_v4(),
+            "a".to_string(),
+            "interceptor",
+            Vector::new(0.0, 0.0),
+        );
+        let before = simulation.state_hash();
+
+        let handle = simulation.entity_to_body[&entity];
+        simulation.physics.rigid_body_set[handle].set_angvel(0.5, true);
+        assert_ne!(simulation.state_hash(), before);
+    }
+
+    #[test]
+    fn state_hash_changes_with_ship_tuning() {
+        let mut simulation = GameSimulation::new();
+        let entity = simulation.spawn_player_ship(
+            Uuid::new_v4(),
+            "a".to_string(),
+            "interceptor",
+            Vector::new(0.0, 0.0),
+        );
+        let before = simulation.state_hash();
+
+        simulation.world.get::<&mut Ship>(entity).unwrap().turn_rate *= 2.0;
+        assert_ne!(simulation.state_hash(), before);
+    }
+}
@@ -0,0 +1,171 @@
+//! Server clock and connection latency estimation
+//!
+//! All server timestamps sent to clients come from one monotonic clock that
+//! starts with the process, so they never jump when the wall clock is
+//! adjusted. Simulation time is derived from the tick counter on the same
+//! base (see `GameSimulation::tick_time_ms`).
+//!
+//! Clients synchronize with Ping/Pong: the Pong carries the server's receive
+//! and send times and the current tick, which is enough for the client to
+//! estimate both round trip time and clock offset. In the other direction the
+//! server sends a Probe every [`PROBE_INTERVAL`] and times the reply to keep
+//! its own per-connection RTT and jitter estimates.
+
+use serde::Serialize;
+use std::{
+    sync::{
+        OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// How often the server measures each connection's round trip
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Smoothing factors from TCP's RTT estimator (RFC 6298)
+const RTT_GAIN: f32 = 1.0 / 8.0;
+const JITTER_GAIN: f32 = 1.0 / 4.0;
+
+/// A monotonic millisecond clock that can be moved forward but never back
+pub struct ServerClock {
+    epoch: OnceLock<Instant>,
+    /// How far the clock has been moved forward by [`Self::advance_to`]
+    advanced_ms: AtomicU64,
+}
+
+impl ServerClock {
+    pub const fn new() -> Self {
+        Self {
+            epoch: OnceLock::new(),
+            advanced_ms: AtomicU64::new(0),
+        }
+    }
+
+    /// Milliseconds since the clock was first read, plus any advances
+    pub fn now_ms(&self) -> u64 {
+        self.advanced_ms.load(Ordering::Relaxed)
+            + self.epoch.get_or_init(Instant::now).elapsed().as_millis() as u64
+    }
+
+    /// Move the clock forward so it reads at least `ms`
+    pub fn advance_to(&self, ms: u64) {
+        let now = self.now_ms();
+        if ms > now {
+            self.advanced_ms.fetch_add(ms - now, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The clock every server timestamp comes from
+pub static SERVER_CLOCK: ServerClock = ServerClock::new();
+
+/// Milliseconds on the server's monotonic clock
+pub fn server_time_ms() -> u64 {
+    SERVER_CLOCK.now_ms()
+}
+
+/// Move the server clock forward so it reads at least `ms`; it never moves back
+///
+/// Rooms restored after a restart need the clock to be past the time their
+/// simulation already covers.
+pub fn advance_server_time(ms: u64) {
+    SERVER_CLOCK.advance_to(ms);
+}
+
+/// Smoothed round trip time and jitter for one connection
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct LatencyEstimate {
+    pub rtt_ms: f32,
+    pub jitter_ms: f32,
+    pub samples: u32,
+}
+
+impl LatencyEstimate {
+    /// Fold in one measured round trip
+    pub fn record(&mut self, sample_ms: f32) {
+        if self.samples == 0 {
+            self.rtt_ms = sample_ms;
+            self.jitter_ms = sample_ms / 2.0;
+        } else {
+            self.jitter_ms += JITTER_GAIN * ((sample_ms - self.rtt_ms).abs() - self.jitter_ms);
+            self.rtt_ms += RTT_GAIN * (sample_ms - self.rtt_ms);
+        }
+        self.samples += 1;
+    }
+
+    /// One-way delay estimate for lag compensation
+    pub fn one_way_ms(&self) -> f32 {
+        self.rtt_ms / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{GameSimulation, SIM_TICK_RATE};
+
+    #[test]
+    fn first_sample_seeds_estimate() {
+        let mut latency = LatencyEstimate::default();
+        latency.record(80.0);
+        assert_eq!(latency.rtt_ms, 80.0);
+        assert_eq!(latency.jitter_ms, 40.0);
+        assert_eq!(latency.one_way_ms(), 40.0);
+    }
+
+    #[test]
+    fn spike_moves_estimate_by_gain() {
+        let mut latency = LatencyEstimate::default();
+        latency.record(80.0);
+        latency.record(160.0);
+        assert_eq!(latency.rtt_ms, 90.0);
+        assert_eq!(latency.jitter_ms, 50.0);
+        assert_eq!(latency.samples, 2);
+    }
+
+    #[test]
+    fn steady_samples_converge() {
+        let mut latency = LatencyEstimate::default();
+        latency.record(200.0);
+        for _ in 0..100 {
+            latency.record(50.0);
+        }
+        assert!((latency.rtt_ms - 50.0).abs() < 0.01);
+        assert!(latency.jitter_ms < 0.01);
+    }
+
+    #[test]
+    fn clock_only_moves_forward() {
+        let clock = ServerClock::new();
+        let start = clock.now_ms();
+        clock.advance_to(start + 5_000);
+        assert!(clock.now_ms() >= start + 5_000);
+
+        clock.advance_to(start);
+        assert!(clock.now_ms() >= start + 5_000);
+        assert!(clock.now_ms() < start + 5_100);
+    }
+
+    #[test]
+    fn held_clock_keeps_tick_time_current() {
+        // A private clock, so other tests don't see it jump
+        let clock = ServerClock::new();
+        let mut simulation = GameSimulation::new();
+        for _ in 0..30 {
+            simulation.step(1.0 / SIM_TICK_RATE);
+        }
+
+        // A fresh clock hasn't run as long as the simulation has
+        simulation.resume_clock_on(&clock);
+        assert!(clock.now_ms() >= simulation.elapsed_ms());
+        assert!(clock.now_ms() - simulation.tick_time_ms() < 100);
+
+        // As if the room had sat out five idle seconds without stepping
+        clock.advance_to(clock.now_ms() + 5_000);
+        assert!(clock.now_ms() - simulation.tick_time_ms() >= 5_000);
+
+        simulation.resume_clock_on(&clock);
+        assert!(clock.now_ms() - simulation.tick_time_ms() < 100);
+    }
+}
@@ -0,0 +1,356 @@
+//! Saving and restoring a whole simulation mid-match
+//!
+//! A save holds every entity with all of its components, the rapier sets and
+//! solver state, and the simulation's own counters and RNG position, so a
+//! restored simulation fed the same inputs continues exactly like the
+//! original. Entities keep their `hecs::Entity` handles and are respawned in
+//! their original iteration order, which keeps system processing order (and
+//! with it network ID allocation) unchanged.
+//!
+//! The blob is a 4-byte magic and a little-endian format version followed by
+//! MessagePack. Content is not included: restoring uses whatever content it is
+//! given and warns if it differs from what the save was made with.
+
+use crate::components::*;
+use crate::content::ContentRegistry;
+use crate::rewind::RewindHistory;
+use crate::simulation::{GameBounds, GameSimulation, PhysicsWorld};
+use crate::timesync::{SERVER_CLOCK, ServerClock};
+use hecs::{Entity, EntityBuilder};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// First bytes of every world save
+const MAGIC: &[u8; 4] = b"CCWS";
+
+/// Bumped whenever the layout of [`SavedWorld`] changes
+pub const FORMAT_VERSION: u16 = 1;
+
+/// One entity and every component it carries
+#[derive(Serialize, Deserialize)]
+struct SavedEntity {
+    entity: Entity,
+    net_id: Option<NetId>,
+    transform: Option<Transform>,
+    velocity: Option<Velocity>,
+    health: Option<Health>,
+    player: Option<Player>,
+    input_buffer: Option<InputBuffer>,
+    ship: Option<Ship>,
+    weapon: Option<Weapon>,
+    projectile: Option<Projectile>,
+    lifetime: Option<Lifetime>,
+    enemy: Option<Enemy>,
+    rigid_body: Option<crate::components::RigidBody>,
+    collider: Option<crate::components::Collider>,
+    frozen: Option<Frozen>,
+    always_relevant: Option<AlwaysRelevant>,
+}
+
+impl SavedEntity {
+    fn capture(entity: hecs::EntityRef<'_>) -> Self {
+        fn get<T: hecs::Component + Clone>(entity: &hecs::EntityRef<'_>) -> Option<T> {
+            entity.get::<&T>().map(|component| (*component).clone())
+        }
+
+        Self {
+            entity: entity.entity(),
+            net_id: get(&entity),
+            transform: get(&entity),
+            velocity: get(&entity),
+            health: get(&entity),
+            player: get(&entity),
+            input_buffer: get(&entity),
+            ship: get(&entity),
+            weapon: get(&entity),
+            projectile: get(&entity),
+            lifetime: get(&entity),
+            enemy: get(&entity),
+            rigid_body: get(&entity),
+            collider: get(&entity),
+            frozen: get(&entity),
+            always_relevant: get(&entity),
+        }
+    }
+
+    fn into_builder(self) -> EntityBuilder {
+        fn add<T: hecs::Component>(builder: &mut EntityBuilder, component: Option<T>) {
+            if let Some(component) = component {
+                builder.add(component);
+            }
+        }
+
+        let mut builder = EntityBuilder::new();
+        add(&mut builder, self.net_id);
+        add(&mut builder, self.transform);
+        add(&mut builder, self.velocity);
+        add(&mut builder, self.health);
+        add(&mut builder, self.player);
+        add(&mut builder, self.input_buffer);
+        add(&mut builder, self.ship);
+        add(&mut builder, self.weapon);
+        add(&mut builder, self.projectile);
+        add(&mut builder, self.lifetime);
+        add(&mut builder, self.enemy);
+        add(&mut builder, self.rigid_body);
+        add(&mut builder, self.collider);
+        add(&mut builder, self.frozen);
+        add(&mut builder, self.always_relevant);
+        builder
+    }
+}
+
+/// Rapier state; the pipeline itself only holds caches and is rebuilt
+#[derive(Serialize, Deserialize)]
+struct SavedPhysics {
+    rigid_body_set: RigidBodySet,
+    collider_set: ColliderSet,
+    gravity: Vector<f32>,
+    integration_parameters: IntegrationParameters,
+    island_manager: IslandManager,
+    broad_phase: BroadPhaseBvh,
+    narrow_phase: NarrowPhase,
+    impulse_joint_set: ImpulseJointSet,
+    multibody_joint_set: MultibodyJointSet,
+    ccd_solver: CCDSolver,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedWorld {
+    content_version: u64,
+    tick: u64,
+    tick_epoch_ms: u64,
+    snapshot_sequence: u32,
+    next_net_id: u64,
+    bounds: GameBounds,
+    friendly_fire: bool,
+    seed: u64,
+    rng_word_pos: u128,
+    player_latency: HashMap<Uuid, u64>,
+    rewind: RewindHistory,
+    entities: Vec<SavedEntity>,
+    physics: SavedPhysics,
+}
+
+impl GameSimulation {
+    /// Serialize the whole simulation into a versioned blob
+    pub fn save_state(&self) -> Result<Vec<u8>, String> {
+        let physics = &self.physics;
+        let saved = SavedWorld {
+            content_version: self.content.fingerprint(),
+            tick: self.tick,
+            tick_epoch_ms: self.tick_epoch_ms,
+            snapshot_sequence: self.snapshot_sequence,
+            next_net_id: self.next_net_id,
+            bounds: self.bounds.clone(),
+            friendly_fire: self.friendly_fire,
+            seed: self.seed,
+            rng_word_pos: self.rng.get_word_pos(),
+            player_latency: self.player_latency.clone(),
+            rewind: self.rewind.clone(),
+            // World iteration order is the order systems visit entities in
+            entities: self.world.iter().map(SavedEntity::capture).collect(),
+            physics: SavedPhysics {
+                rigid_body_set: physics.rigid_body_set.clone(),
+                collider_set: physics.collider_set.clone(),
+                gravity: physics.gravity,
+                integration_parameters: physics.integration_parameters,
+                island_manager: physics.island_manager.clone(),
+                broad_phase: physics.broad_phase.clone(),
+                narrow_phase: physics.narrow_phase.clone(),
+                impulse_joint_set: physics.impulse_joint_set.clone(),
+                multibody_joint_set: physics.multibody_joint_set.clone(),
+                ccd_solver: physics.ccd_solver.clone(),
+            },
+        };
+
+        let body = rmp_serde::to_vec_named(&saved)
+            .map_err(|e| format!("Failed to encode world save: {}", e))?;
+        let mut bytes = Vec::with_capacity(MAGIC.len() + 2 + body.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&body);
+        Ok(bytes)
+    }
+
+    /// Rebuild a simulation from [`Self::save_state`] output
+    ///
+    /// The restored simulation keeps the saved clock; call
+    /// [`Self::resume_clock`] to re-anchor it after a server restart.
+    pub fn restore_state(bytes: &[u8], content: Arc<ContentRegistry>) -> Result<Self, String> {
+        if bytes.len() < MAGIC.len() + 2 || &bytes[..MAGIC.len()] != MAGIC {
+            return Err("Not a world save".to_string());
+        }
+        let version = u16::from_le_bytes([bytes[MAGIC.len()], bytes[MAGIC.len() + 1]]);
+        if version != FORMAT_VERSION {
+            return Err(format!(
+                "Unsupported world save version {} (expected {})",
+                version, FORMAT_VERSION
+            ));
+        }
+        let saved: SavedWorld = rmp_serde::from_slice(&bytes[MAGIC.len() + 2..])
+            .map_err(|e| format!("Failed to decode world save: {}", e))?;
+
+        if saved.content_version != content.fingerprint() {
+            warn!("Restoring a world saved with different content; it won't continue identically");
+        }
+
+        let mut simulation = GameSimulation::with_seed(content, saved.seed);
+        simulation.tick = saved.tick;
+        simulation.tick_epoch_ms = saved.tick_epoch_ms;
+        simulation.snapshot_sequence = saved.snapshot_sequence;
+        simulation.next_net_id = saved.next_net_id;
+        simulation.bounds = saved.bounds;
+        simulation.friendly_fire = saved.friendly_fire;
+        simulation.rng = ChaCha8Rng::seed_from_u64(saved.seed);
+        simulation.rng.set_word_pos(saved.rng_word_pos);
+        simulation.player_latency = saved.player_latency;
+        simulation.rewind = saved.rewind;
+
+        let physics = saved.physics;
+        simulation.physics = PhysicsWorld {
+            rigid_body_set: physics.rigid_body_set,
+            collider_set: physics.collider_set,
+            gravity: physics.gravity,
+            integration_parameters: physics.integration_parameters,
+            island_manager: physics.island_manager,
+            broad_phase: physics.broad_phase,
+            narrow_phase: physics.narrow_phase,
+            impulse_joint_set: physics.impulse_joint_set,
+            multibody_joint_set: physics.multibody_joint_set,
+            ccd_solver: physics.ccd_solver,
+            ..PhysicsWorld::default()
+        };
+
+        let entity_count = saved.entities.len();
+        for saved_entity in saved.entities {
+            let entity = saved_entity.entity;
+            simulation
+                .world
+                .spawn_at(entity, saved_entity.into_builder().build());
+
+            if let Ok(body) = simulation
+                .world
+                .get::<&crate::components::RigidBody>(entity)
+            {
+                simulation.entity_to_body.insert(entity, body.handle);
+                simulation.body_to_entity.insert(body.handle, entity);
+            }
+            if let Ok(net_id) = simulation.world.get::<&NetId>(entity) {
+                simulation.net_ids.insert(net_id.0, entity);
+            }
+        }
+
+        info!(
+            "Restored world at tick {} with {} entities",
+            simulation.tick, entity_count
+        );
+        Ok(simulation)
+    }
+
+    /// Move the simulation's clock so the current tick lands at `tick_epoch_ms`
+    /// plus its elapsed time, keeping hit history consistent with it
+    pub fn set_tick_epoch(&mut self, tick_epoch_ms: u64) {
+        let delta = tick_epoch_ms as i64 - self.tick_epoch_ms as i64;
+        self.rewind.shift(delta);
+        self.tick_epoch_ms = tick_epoch_ms;
+    }
+
+    /// Simulated time from tick 0 to the current tick
+    pub fn elapsed_ms(&self) -> u64 {
+        self.tick_time_ms() - self.tick_epoch_ms
+    }
+
+    /// Re-anchor the simulation's clock so its current tick is now
+    ///
+    /// Used for restored simulations and for rooms that sat out ticks with
+    /// nobody connected. A fresh process's clock may not have run as long as
+    /// the simulation has, so it is moved forward first. When restoring
+    /// several simulations, advance the clock past the furthest along before
+    /// resuming any, or the earlier ones fall behind the later jumps.
+    pub fn resume_clock(&mut self) {
+        self.resume_clock_on(&SERVER_CLOCK);
+    }
+
+    /// [`Self::resume_clock`] against `clock` instead of the server's
+    pub(crate) fn resume_clock_on(&mut self, clock: &ServerClock) {
+        let elapsed = self.elapsed_ms();
+        clock.advance_to(elapsed);
+        self.set_tick_epoch(clock.now_ms() - elapsed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::SIM_TICK_RATE;
+
+    /// Both players fly, turn and shoot for `ticks` ticks, continuing the
+    /// input sequence from `first_sequence`
+    fn fly(simulation: &mut GameSimulation, players: [Uuid; 2], first_sequence: u32, ticks: u32) {
+        for sequence in first_sequence..first_sequence + ticks {
+            for (i, player_id) in players.iter().enumerate() {
+                simulation.add_player_input(
+                    *player_id,
+                    InputData {
+                        sequence,
+                        timestamp: sequence as u64 * 66,
+                        thrust: 0.5 + i as f32 * 0.5,
+                        turn: if sequence % 10 < 5 { 0.3 } else { -0.3 },
+                        primary_fire: (sequence + i as u32).is_multiple_of(2),
+                        secondary_fire: false,
+                    },
+                );
+            }
+            simulation.step(1.0 / SIM_TICK_RATE);
+        }
+    }
+
+    fn started_match(players: [Uuid; 2]) -> GameSimulation {
+        let mut simulation = GameSimulation::new();
+        simulation.friendly_fire = true;
+        for (i, player_id) in players.iter().enumerate() {
+            simulation.spawn_player_ship(
+                *player_id,
+                format!("pilot{}", i),
+                "interceptor",
+                Vector::new(-200.0 + 200.0 * i as f32, 0.0),
+            );
+        }
+        simulation.set_player_latency(players[0], 40.0);
+        simulation
+    }
+
+    #[test]
+    fn restored_world_continues_identically() {
+        let players = [Uuid::new_v4(), Uuid::new_v4()];
+        let mut original = started_match(players);
+        fly(&mut original, players, 1, 60);
+
+        let saved = original.save_state().unwrap();
+        let mut restored = GameSimulation::restore_state(&saved, original.content.clone()).unwrap();
+        assert_eq!(restored.tick, original.tick);
+        assert_eq!(restored.state_hash(), original.state_hash());
+
+        fly(&mut original, players, 61, 30);
+        fly(&mut restored, players, 61, 30);
+        assert_eq!(restored.tick, original.tick);
+        assert_eq!(restored.state_hash(), original.state_hash());
+    }
+
+    #[test]
+    fn rejects_other_formats() {
+        let content = Arc::new(ContentRegistry::default());
+        let mut bytes = started_match([Uuid::new_v4(), Uuid::new_v4()])
+            .save_state()
+            .unwrap();
+        bytes[MAGIC.len()] += 1;
+        assert!(GameSimulation::restore_state(&bytes, content.clone()).is_err());
+        assert!(GameSimulation::restore_state(b"not a save", content).is_err());
+    }
+}